@@ -0,0 +1,336 @@
+//! Asynchronous client for an uosql server, built on tokio.
+//!
+//! `AsyncConnection` speaks the same protocol as `uosql::Connection`, but
+//! waiting for a result does not block the thread, so one runtime can run
+//! the queries of many connections at once:
+//!
+//! ```no_run
+//! # use uosql_async::{AsyncConnection, Error, QueryResult};
+//! # async fn run() -> Result<(), Error> {
+//! let mut conn = AsyncConnection::connect("127.0.0.1", 4242, "admin", "pw").await?;
+//! conn.use_db("test").await?;
+//! match conn.execute("select id, name from users").await? {
+//!     QueryResult::Rows(data) => for row in data {
+//!         println!("{:?} {:?}", row.get_i64(0), row.get_str(1));
+//!     },
+//!     QueryResult::Affected(n) => println!("{} rows changed", n),
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! It is a crate of its own, since `async fn` needs a newer edition than the
+//! `uosql` crate has. Errors and results are the ones of `uosql`.
+
+use std::cmp;
+use std::io::{self, Read};
+
+use bincode::SizeLimit;
+use bincode::rustc_serialize::{decode_from, encode, DecodingError};
+use rustc_serialize::{Decodable, Encodable};
+use server::auth;
+use server::net::types::{preprocess, ClientErrMsg, Command, Greeting, Login, PkgType};
+use server::storage::ResultSet;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+pub use uosql::{Error, QueryResult, DEFAULT_MAX_MESSAGE_SIZE};
+pub use uosql::types;
+
+/// Bytes read from the socket at once
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// A connection to an uosql server. Results are sent uncompressed.
+///
+/// Every command takes the connection mutably, so a connection runs one
+/// query at a time; open one connection per concurrent query. If the future
+/// of a command is dropped before it finished, the answer would be read by
+/// the next command, so the connection fails with `Error::Poisoned` then.
+pub struct AsyncConnection {
+    channel: Channel,
+    greeting: Greeting,
+    max_size: SizeLimit,
+    /// Set while a command waits for its answer
+    busy: bool,
+}
+
+impl AsyncConnection {
+    /// Establish a connection to the server at `addr` and `port`, which may
+    /// be an IP address or a host name, and log in.
+    pub async fn connect(addr: &str, port: u16, user: &str, password: &str)
+        -> Result<AsyncConnection, Error>
+    {
+        let stream = TcpStream::connect((addr, port)).await?;
+        let mut channel = Channel { stream, received: Vec::new() };
+
+        channel.receive(PkgType::Greet, SizeLimit::Bounded(1024)).await?;
+        let greeting: Greeting = channel.decode(SizeLimit::Bounded(1024)).await?;
+        if greeting.protocol_version != server::net::PROTOCOL_VERSION {
+            return Err(Error::ProtocolMismatch { client: server::net::PROTOCOL_VERSION,
+                server: greeting.protocol_version })
+        }
+
+        // Login package, only containing the salted hash of the password
+        let login = Login { username: user.into(),
+            password_hash: auth::hash_password(&greeting.salt, password),
+            compression: false };
+        channel.write(&PkgType::Login, &login, SizeLimit::Bounded(1024)).await?;
+
+        // Either user is authorized or unauthorized
+        let status: PkgType = channel.decode(SizeLimit::Bounded(1024)).await?;
+        match status {
+            PkgType::AccGranted => Ok(AsyncConnection { channel, greeting,
+                max_size: SizeLimit::Bounded(DEFAULT_MAX_MESSAGE_SIZE), busy: false }),
+            PkgType::AccDenied => Err(Error::Auth),
+            // e.g. too many connections
+            PkgType::Error =>
+                Err(Error::Server(channel.decode(SizeLimit::Bounded(1024)).await?)),
+            _ => Err(Error::UnexpectedPkg)
+        }
+    }
+
+    /// Limit the size of queries sent and results received over this
+    /// connection to `size` bytes, like `Connection::with_max_message_size`.
+    pub fn with_max_message_size(mut self, size: u64) -> AsyncConnection {
+        self.max_size = SizeLimit::Bounded(size);
+        self
+    }
+
+    /// The id the server gave this connection
+    pub fn connection_id(&self) -> u64 {
+        self.greeting.connection_id
+    }
+
+    /// Send a query to the server and receive its result.
+    pub async fn execute(&mut self, query: &str) -> Result<QueryResult, Error> {
+        self.send(Command::Query(query.into())).await?;
+        let res = self.receive_result().await;
+        self.finish(res)
+    }
+
+    /// Select the database for the following queries. Fails with
+    /// `Error::Server` if the database does not exist.
+    pub async fn use_db(&mut self, name: &str) -> Result<(), Error> {
+        self.send(Command::Use(name.into())).await?;
+        let res = self.channel.receive(PkgType::Ok, self.max_size).await;
+        self.finish(res)
+    }
+
+    /// Send ping-command to server and receive Ok-package
+    pub async fn ping(&mut self) -> Result<(), Error> {
+        self.send(Command::Ping).await?;
+        let res = self.channel.receive(PkgType::Ok, self.max_size).await;
+        self.finish(res)
+    }
+
+    /// Send quit-command to server and receive Ok-package
+    pub async fn quit(mut self) -> Result<(), Error> {
+        self.send(Command::Quit).await?;
+        self.channel.receive(PkgType::Ok, self.max_size).await
+    }
+
+    /// Sends the command, fails if the answer of the last one is still due
+    async fn send(&mut self, cmd: Command) -> Result<(), Error> {
+        if self.busy {
+            return Err(Error::Poisoned)
+        }
+        self.busy = true;
+        self.channel.write(&PkgType::Command, &cmd, self.max_size).await
+    }
+
+    /// Marks the command as answered, unless its answer was not read
+    /// completely
+    fn finish<T>(&mut self, res: Result<T, Error>) -> Result<T, Error> {
+        match res {
+            Ok(_) | Err(Error::Server(_)) | Err(Error::Cancelled) => self.busy = false,
+            _ => {}
+        }
+        res
+    }
+
+    /// Receives the rows of a query or the number of changed rows
+    async fn receive_result(&mut self) -> Result<QueryResult, Error> {
+        let expected = [PkgType::Response, PkgType::AffectedRows];
+        match self.channel.receive_any(&expected, self.max_size).await? {
+            PkgType::Response => {
+                let rows: ResultSet = self.channel.decode(self.max_size).await?;
+                Ok(QueryResult::Rows(preprocess(&rows)))
+            },
+            _ => Ok(QueryResult::Affected(self.channel.decode(SizeLimit::Bounded(1024)).await?))
+        }
+    }
+}
+
+/// The stream with the bytes received but not decoded yet
+struct Channel {
+    stream: TcpStream,
+    received: Vec<u8>,
+}
+
+impl Channel {
+    /// Sends a package header and its payload
+    async fn write<T: Encodable>(&mut self, pkg: &PkgType, payload: &T, size: SizeLimit)
+        -> Result<(), Error>
+    {
+        let mut data = encode(pkg, SizeLimit::Bounded(1024))?;
+        data.extend(encode(payload, size)?);
+        self.stream.write_all(&data).await?;
+        Ok(())
+    }
+
+    async fn receive(&mut self, pkg: PkgType, size: SizeLimit) -> Result<(), Error> {
+        self.receive_any(&[pkg], size).await.map(|_| ())
+    }
+
+    /// Like `receive_any` of the blocking client: returns the package header
+    /// if it is one of `pkgs`, errors of the server are returned as error.
+    async fn receive_any(&mut self, pkgs: &[PkgType], size: SizeLimit)
+        -> Result<PkgType, Error>
+    {
+        let status: PkgType = match self.decode(SizeLimit::Bounded(1024)).await {
+            Ok(status) => status,
+            // no package type known to this library
+            Err(Error::Decode(DecodingError::InvalidEncoding(_))) =>
+                return Err(Error::UnexpectedPkg),
+            Err(e) => return Err(e)
+        };
+
+        if status == PkgType::Error {
+            let err: ClientErrMsg = self.decode(size).await?;
+            if err.is_cancelled() {
+                return Err(Error::Cancelled)
+            }
+            return Err(Error::Server(err))
+        }
+        if !pkgs.contains(&status) {
+            return Err(Error::UnexpectedPkg)
+        }
+        Ok(status)
+    }
+
+    /// Decodes the next message. Messages have no length prefix, so the
+    /// received bytes are decoded again whenever more of them arrived.
+    async fn decode<T: Decodable>(&mut self, size: SizeLimit) -> Result<T, Error> {
+        loop {
+            let mut reader = Received { data: &self.received, pos: 0 };
+            match decode_from(&mut reader, size) {
+                Ok(value) => {
+                    let pos = reader.pos;
+                    self.received.drain(..pos);
+                    return Ok(value)
+                },
+                Err(DecodingError::IoError(ref e)) if e.kind() == io::ErrorKind::WouldBlock => {},
+                Err(e) => return Err(e.into())
+            }
+
+            let mut chunk = vec![0; CHUNK_SIZE];
+            let count = self.stream.read(&mut chunk).await?;
+            if count == 0 {
+                return Err(Error::Io(io::Error::new(io::ErrorKind::UnexpectedEof,
+                    "connection closed by the server")))
+            }
+            self.received.extend_from_slice(&chunk[..count]);
+        }
+    }
+}
+
+/// Reads the received bytes. At their end it fails with `WouldBlock`, so a
+/// message that arrived partly is not decoded as a shorter one.
+struct Received<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Read for Received<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0)
+        }
+        let rest = &self.data[self.pos..];
+        if rest.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::WouldBlock, "message is incomplete"))
+        }
+        let count = cmp::min(rest.len(), buf.len());
+        buf[..count].copy_from_slice(&rest[..count]);
+        self.pos += count;
+        Ok(count)
+    }
+}
+
+// starts a server in a directory of its own, which accepts logins as admin/pw
+#[cfg(test)]
+fn start_server(name: &str)
+    -> (std::sync::Arc<server::Server>, u16, std::thread::JoinHandle<()>, String)
+{
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::sync::Arc;
+
+    let dir = std::env::temp_dir().join(format!("uosql-async-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("db")).unwrap();
+    let dir = dir.to_string_lossy().into_owned();
+
+    let config = server::Config {
+        max_connections: 10,
+        worker_threads: 4,
+        shutdown_timeout: 1,
+        log_queries: false,
+        users: vec![auth::Credentials {
+            username: "admin".into(),
+            password_hash: auth::stored_hash("pw")
+        }],
+        query_cache_size: 0,
+        parse_cache_size: 0,
+        .. server::Config::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 0, dir.clone())
+    };
+    let server = Arc::new(server::Server::bind(config).unwrap());
+    let port = server.local_addr().unwrap().port();
+    let runner = {
+        let server = server.clone();
+        std::thread::spawn(move || server.run())
+    };
+    (server, port, runner, dir)
+}
+
+#[tokio::test]
+async fn test_concurrent_queries() {
+    let (server, port, runner, dir) = start_server("concurrent");
+    let mut a = AsyncConnection::connect("127.0.0.1", port, "admin", "pw").await.unwrap();
+    let mut b = AsyncConnection::connect("127.0.0.1", port, "admin", "pw").await.unwrap();
+    assert!(a.connection_id() != b.connection_id());
+    a.use_db("db").await.unwrap();
+    b.use_db("db").await.unwrap();
+    a.execute("create table foo (id int primary key)").await.unwrap();
+    for id in 1..4 {
+        a.execute(&format!("insert into foo values ({})", id)).await.unwrap();
+    }
+
+    // both queries run at once on the single thread of the test runtime
+    let (ra, rb) = tokio::join!(a.execute("select id from foo where id > 1"),
+        b.execute("delete from foo where id = 7"));
+    match ra.unwrap() {
+        QueryResult::Rows(data) => {
+            let mut ids: Vec<_> = data.into_iter().map(|row| row.get_i64(0).unwrap()).collect();
+            ids.sort();
+            assert_eq!(ids, vec![2, 3]);
+        },
+        QueryResult::Affected(_) => panic!("expected rows"),
+    }
+    match rb.unwrap() {
+        QueryResult::Affected(count) => assert_eq!(count, 0),
+        QueryResult::Rows(_) => panic!("expected the number of deleted rows"),
+    }
+
+    // errors of the server don't break the connection
+    match b.execute("select id from bar").await {
+        Err(Error::Server(_)) => {},
+        _ => panic!("expected a server error"),
+    }
+    b.ping().await.unwrap();
+
+    a.quit().await.unwrap();
+    b.quit().await.unwrap();
+    server.shutdown();
+    runner.join().unwrap();
+    std::fs::remove_dir_all(dir).unwrap();
+}
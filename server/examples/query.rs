@@ -13,7 +13,8 @@ fn main() {
 
     print!("Username: ");
     let username = read_query();
-    let mut user = auth::User { _name: username.into(), _currentDatabase: None };
+    let mut user = auth::User { _name: username.into(), _currentDatabase: None,
+        data_dir: ".".into() };
     println!("to exit program type 'exit'");
     print!("Sql Query: ");
     let mut query = read_query();
@@ -33,7 +34,9 @@ fn execute(query: &str, user: & mut auth::User) {
         Ok(tree) => {
                 println!("{:?}", tree);
                 match query::execute_from_ast(tree, user) {
-                    Ok(s) => display(&mut net::types::preprocess(&s)),
+                    Ok(query::ExecutionResult::Rows(s)) =>
+                        display(&mut net::types::preprocess(&s)),
+                    Ok(query::ExecutionResult::Affected(n)) => println!("{} rows affected", n),
                     Err(error) => println!("{:?}", error),
                 };
             },
@@ -91,6 +94,15 @@ fn display_data(table: &mut DataSet) {
             },
             SqlType::Char(size) => {
                 cols.push(max(size as usize, table.get_col_name(i).unwrap().len()));
+            },
+            SqlType::VarChar(size) => {
+                cols.push(max(size as usize, table.get_col_name(i).unwrap().len()));
+            },
+            SqlType::Float | SqlType::Double => {
+                cols.push(max(12, table.get_col_name(i).unwrap().len()));
+            },
+            SqlType::Date | SqlType::Timestamp => {
+                cols.push(max(19, table.get_col_name(i).unwrap().len()));
             }
         }
     }
@@ -129,9 +141,18 @@ fn display_data(table: &mut DataSet) {
                                     print!("| {1: ^0$} ", min(30, cols[i]), val),
                                 None => print!("| {1: ^0$} ", min(30, cols[i]), "none"),
                             },
-                        SqlType::Char(_) =>
+                        SqlType::Char(_) | SqlType::VarChar(_) =>
+                            print!("| {1: ^0$} ", min(30, cols[i]),
+                                    table.next_char_by_idx(i).unwrap_or("none".into())),
+                        SqlType::Float | SqlType::Double =>
+                            match table.next_float_by_idx(i) {
+                                Some(val) =>
+                                    print!("| {1: ^0$} ", min(30, cols[i]), val),
+                                None => print!("| {1: ^0$} ", min(30, cols[i]), "none"),
+                            },
+                        SqlType::Date | SqlType::Timestamp =>
                             print!("| {1: ^0$} ", min(30, cols[i]),
-                                    table.next_char_by_idx(i).unwrap_or("none".into()))
+                                    table.next_datetime_by_idx(i).unwrap_or("none".into())),
                     }
                 },
                 None => continue
@@ -1,26 +1,38 @@
 extern crate docopt;
+#[cfg(unix)]
+extern crate libc;
 #[macro_use]
 extern crate log;
+extern crate rand;
 extern crate rustc_serialize;
 extern crate server;
 
 use rustc_serialize::json;
-use std::fs::File;
-use std::io::Read;
+use rustc_serialize::hex::FromHex;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::fmt;
+use std::path::Path;
 use docopt::Docopt;
-use std::net::Ipv4Addr;
+use rand::Rng;
+use std::net::{IpAddr, Ipv4Addr};
 use std::str::FromStr;
+use std::sync::Arc;
+use log::LogLevelFilter;
 
 /// For console input, manages flags and arguments
 const USAGE: &'static str = "
 Usage: uosql-server [--cfg=<file>] [--bind=<address>] [--port=<port>]
 [--dir=<directory>]
+       uosql-server --init-config=<file> [--force]
 
 Options:
-    --cfg=<file>        Enter a configuration file.
-    --bind=<address>    Change the bind address.
-    --port=<port>       Change the port.
-    --dir=<directory>   Change the path of the database.
+    --cfg=<file>            Enter a configuration file.
+    --bind=<address>        Change the bind address.
+    --port=<port>           Change the port.
+    --dir=<directory>       Change the path of the database.
+    --init-config=<file>    Write a config file with the default settings.
+    --force                 Overwrite an existing file with --init-config.
 ";
 
 #[derive(Debug, RustcDecodable)]
@@ -28,29 +40,89 @@ struct Args {
    flag_cfg: Option<String>,
    flag_bind: Option<String>,
    flag_port: Option<u16>,
-   flag_dir: Option<String>
+   flag_dir: Option<String>,
+   flag_init_config: Option<String>,
+   flag_force: bool
 }
 
+/// Level of the log if the config file does not set a valid one
+const DEFAULT_LOG_LEVEL: LogLevelFilter = LogLevelFilter::Trace;
+/// The log is written to this file if the config file does not set one
+const DEFAULT_LOG_FILE: &'static str = "log.txt";
+/// Port and database directory if the config file does not set them
+const DEFAULT_PORT: u16 = 4242;
+const DEFAULT_DIR: &'static str = "data";
+
 /// Entry point for server.
 fn main() {
-    // Configure and enable the logger. We may `unwrap` here, because a panic
-    // would happen right after starting the program
-    server::logger::with_loglevel(log::LogLevelFilter::Trace)
-        .with_logfile(std::path::Path::new("log.txt"))
-        .enable().unwrap();
-    info!("Starting uoSQL server...");
-
     // Getting the information for a possible configuration
     let args : Args = Docopt::new(USAGE).and_then(|d| d.decode())
                                         .unwrap_or_else(|e| e.exit());
 
-    // If a cfg is entered, use this file name to set configurations
-    let mut config = read_conf_from_json(args.flag_cfg
-                                .unwrap_or("config.json".into()));
+    // Only write a config file to start with
+    if let Some(name) = args.flag_init_config {
+        match write_default_conf(&name, args.flag_force) {
+            Ok(_) => println!("Wrote the default configuration to {}", name),
+            Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                let _ = writeln!(io::stderr(), "{} already exists, use --force to overwrite it",
+                    name);
+                std::process::exit(1);
+            },
+            Err(e) => {
+                let _ = writeln!(io::stderr(), "Could not write {}: {}", name, e);
+                std::process::exit(1);
+            }
+        }
+        return
+    }
+
+    // If a cfg is entered, use this file name to set configurations. Without
+    // one, "config.json" is used if it exists
+    let file = match args.flag_cfg {
+        Some(name) => read_conf_from_json(name),
+        None if Path::new("config.json").exists() =>
+            read_conf_from_json("config.json".into()),
+        None => decode_conf("{}"),
+    };
+
+    // Configure and enable the logger before anything else is logged, with
+    // the defaults if the config file is broken. We may `unwrap` here,
+    // because a panic would happen right after starting the program
+    let (level, log_file) = match file {
+        Ok(ref f) => (f.log_level.clone(), f.log_file.clone()),
+        Err(_) => (None, None),
+    };
+    let log_file = log_file.unwrap_or(DEFAULT_LOG_FILE.into());
+    let parsed_level = level.as_ref().and_then(|l| parse_log_level(l));
+    server::logger::with_loglevel(parsed_level.unwrap_or(DEFAULT_LOG_LEVEL))
+        .with_logfile(Path::new(&log_file))
+        .enable().unwrap();
+    info!("Starting uoSQL server...");
+    match (level, parsed_level) {
+        (Some(level), None) =>
+            warn!("Unknown log level '{}', using '{}' instead", level, DEFAULT_LOG_LEVEL),
+        _ => {},
+    }
+
+    let config = file.and_then(build_conf);
+    let mut config = match config {
+        Ok(c) => c,
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    };
 
     // Change the bind address if flag is set
-    config.address = args.flag_bind.and_then(|b| Ipv4Addr::from_str(&b)
-                                   .ok()).unwrap_or(config.address);
+    if let Some(b) = args.flag_bind {
+        config.address = match parse_address(&b) {
+            Ok(a) => a,
+            Err(e) => {
+                error!("{}", e);
+                std::process::exit(1);
+            }
+        };
+    }
 
     // Change port if flag is set
     config.port = args.flag_port.unwrap_or(config.port);
@@ -58,50 +130,378 @@ fn main() {
     // Change directory is flag is set
     config.dir = args.flag_dir.unwrap_or(config.dir);
 
-    info!("Bind: {}  Port: {}  Directory: {}",
-                        config.address, config.port, config.dir);
+    info!("Bind: {}  Port: {}  Directory: {}  Max. connections: {}",
+                        config.address, config.port, config.dir,
+                        config.max_connections);
 
     // Start listening for incoming Tcp connections
-    server::listen(config);
+    let server = match server::Server::bind(config) {
+        Ok(s) => Arc::new(s),
+        Err(e) => {
+            error!("Could not bind: {}", e);
+            std::process::exit(1);
+        }
+    };
+    stop_on_signal(server.clone());
+    server.run();
 }
 
+/// Shuts the server down on SIGINT or SIGTERM
+#[cfg(unix)]
+fn stop_on_signal(server: Arc<server::Server>) {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    static SIGNALLED: AtomicBool = AtomicBool::new(false);
+
+    // Only sets the flag, everything else is not safe in a signal handler
+    extern "C" fn on_signal(_: libc::c_int) {
+        SIGNALLED.store(true, Ordering::SeqCst);
+    }
+
+    unsafe {
+        libc::signal(libc::SIGINT, on_signal as libc::sighandler_t);
+        libc::signal(libc::SIGTERM, on_signal as libc::sighandler_t);
+    }
+
+    thread::spawn(move || {
+        while !SIGNALLED.load(Ordering::SeqCst) {
+            thread::sleep(Duration::from_millis(100));
+        }
+        info!("Received signal, shutting down...");
+        server.shutdown();
+    });
+}
+
+#[cfg(not(unix))]
+fn stop_on_signal(_: Arc<server::Server>) {}
+
+
+/// Errors that can occur while reading the configuration
+#[derive(Debug)]
+enum ConfigError {
+    /// The file could not be opened or read
+    UnreadableFile(String, io::Error),
+    /// The file is not valid JSON or has fields of the wrong type
+    InvalidJson(json::DecoderError),
+    /// The address is neither an IPv4 nor an IPv6 address
+    MalformedAddress(String),
+    /// A part of the address is not a number between 0 and 255
+    BadOctet(String),
+    /// The password hash of the user is not a hex string
+    BadPasswordHash(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &ConfigError::UnreadableFile(ref name, ref e) =>
+                write!(f, "could not read config file '{}': {}", name, e),
+            &ConfigError::InvalidJson(ref e) =>
+                write!(f, "invalid config file: {}", e),
+            &ConfigError::MalformedAddress(ref s) =>
+                write!(f, "malformed bind address '{}'", s),
+            &ConfigError::BadOctet(ref s) =>
+                write!(f, "invalid octet '{}' in bind address", s),
+            &ConfigError::BadPasswordHash(ref s) =>
+                write!(f, "password hash of user '{}' is not a hex string", s),
+        }
+    }
+}
+
+#[derive(Debug, RustcDecodable)]
+struct CfgUser {
+    username: String,
+    password_hash: String
+}
+
+/// The content of a config file, every field may be left out
+#[derive(Debug, RustcDecodable, Default)]
+struct CfgFile {
+    address: Option<String>,
+    port: Option<u16>,
+    dir: Option<String>,
+    max_connections: Option<usize>,
+    worker_threads: Option<usize>,
+    shutdown_timeout: Option<u64>,
+    log_queries: Option<bool>,
+    query_log_length: Option<usize>,
+    users: Option<Vec<CfgUser>>,
+    tls_cert: Option<String>,
+    tls_key: Option<String>,
+    /// One of "off", "error", "warn", "info", "debug" and "trace"
+    log_level: Option<String>,
+    log_file: Option<String>,
+    query_cache_size: Option<usize>,
+    parse_cache_size: Option<usize>,
+    metrics_port: Option<u16>
+}
+
+/// Reads a config file, see `decode_conf`
+fn read_conf_from_json(name: String) -> Result<CfgFile, ConfigError> {
+    let mut s = String::new();
+    if let Err(e) = File::open(&name).and_then(|mut f| f.read_to_string(&mut s)) {
+        return Err(ConfigError::UnreadableFile(name, e))
+    }
+    decode_conf(&s)
+}
+
+/// Decodes the content of a config file
+fn decode_conf(s: &str) -> Result<CfgFile, ConfigError> {
+    json::decode(s).map_err(|e| ConfigError::InvalidJson(e))
+}
+
+/// Creates a Config struct out of the content of a config file
+fn parse_conf(s: &str) -> Result<server::Config, ConfigError> {
+    decode_conf(s).and_then(build_conf)
+}
 
 /// Creates a Config struct out of a config file
 /// returns default values for everything that is
 /// not entered manually
-fn read_conf_from_json(name: String) -> server::Config {
-
-    #[derive(Debug, RustcDecodable, Default)]
-    struct CfgFile {
-        address: Option<String>,
-        port: Option<u16>,
-        dir: Option<String>
-    }
-
-    // Read from JSON file and decode to CfgFile
-    let mut config = CfgFile::default();
-    if let Ok(mut f) = File::open(name) {
-        let mut s = String::new();
-        if let Err(e) = f.read_to_string(&mut s) {
-            error!("Could not read JSON-file: {:?}", e)
-        } else {
-            config = json::decode(&s).unwrap();
-        }
-    }
+fn build_conf(config: CfgFile) -> Result<server::Config, ConfigError> {
+    let bind = match config.address {
+        Some(s) => try!(parse_address(&s)),
+        None => IpAddr::V4(Ipv4Addr::new(127,0,0,1))
+    };
 
-    let s = config.address.unwrap_or("127.0.0.1".into());
-    let bind = match Ipv4Addr::from_str(&s) {
-        Ok(n) => n,
-        Err(_) => {
-            warn!("Invalid bind address, set to default");
-            Ipv4Addr::new(127,0,0,1)
+    // Without configured users only "admin" may log in, with a random
+    // password that is logged once
+    let users = match config.users {
+        Some(users) => {
+            let mut creds = Vec::new();
+            for u in users {
+                let hash = match u.password_hash.from_hex() {
+                    Ok(h) => h,
+                    Err(_) => return Err(ConfigError::BadPasswordHash(u.username))
+                };
+                creds.push(server::auth::Credentials {
+                    username: u.username,
+                    password_hash: hash
+                });
+            }
+            creds
+        },
+        None => {
+            let password: String = rand::thread_rng().gen_ascii_chars()
+                                                     .take(16).collect();
+            warn!("No users configured, log in as 'admin' with password '{}'",
+                  password);
+            vec![server::auth::Credentials {
+                username: "admin".into(),
+                password_hash: server::auth::stored_hash(&password)
+            }]
         }
     };
 
     // Return configuration, all None datafields set to default
-    server::Config {
-        address: bind,
-        port: config.port.unwrap_or(4242),
-        dir: config.dir.unwrap_or("data".into())
+    let defaults = server::Config::new(bind, config.port.unwrap_or(DEFAULT_PORT),
+        config.dir.unwrap_or(DEFAULT_DIR.into()));
+    Ok(server::Config {
+        max_connections: config.max_connections.unwrap_or(defaults.max_connections),
+        worker_threads: config.worker_threads.unwrap_or(defaults.worker_threads),
+        shutdown_timeout: config.shutdown_timeout.unwrap_or(defaults.shutdown_timeout),
+        log_queries: config.log_queries.unwrap_or(defaults.log_queries),
+        query_log_length: config.query_log_length.unwrap_or(defaults.query_log_length),
+        users: users,
+        tls_cert: config.tls_cert,
+        tls_key: config.tls_key,
+        query_cache_size: config.query_cache_size.unwrap_or(defaults.query_cache_size),
+        parse_cache_size: config.parse_cache_size.unwrap_or(defaults.parse_cache_size),
+        metrics_port: config.metrics_port,
+        .. defaults
+    })
+}
+
+/// The content of a config file with the defaults of all settings except
+/// `users`, `tls_cert`, `tls_key` and `metrics_port`. JSON has no comments, so the
+/// explanation is an entry that is ignored when reading the file.
+fn default_conf() -> String {
+    let config = server::Config::new(IpAddr::V4(Ipv4Addr::new(127,0,0,1)), DEFAULT_PORT,
+        DEFAULT_DIR.into());
+    format!(r#"{{
+    "_comment" : [
+        "Every entry may be left out to use its default.",
+        "Without users, only 'admin' may log in with a password that is logged",
+        "at the start. log_level is one of off, error, warn, info, debug and trace."
+    ],
+    "address" : "{}",
+    "port" : {},
+    "dir" : {},
+    "max_connections" : {},
+    "worker_threads" : {},
+    "shutdown_timeout" : {},
+    "log_queries" : {},
+    "query_log_length" : {},
+    "log_level" : "{}",
+    "log_file" : {},
+    "query_cache_size" : {},
+    "parse_cache_size" : {}
+}}
+"#, config.address, config.port, json::as_json(&config.dir), config.max_connections,
+        config.worker_threads, config.shutdown_timeout, config.log_queries,
+        config.query_log_length, DEFAULT_LOG_LEVEL.to_string().to_lowercase(),
+        json::as_json(&DEFAULT_LOG_FILE), config.query_cache_size, config.parse_cache_size)
+}
+
+/// Writes `default_conf` to the file `name`. An existing file is only
+/// overwritten if `force` is set, otherwise the error is `AlreadyExists`.
+fn write_default_conf(name: &str, force: bool) -> io::Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true);
+    if force {
+        options.create(true).truncate(true);
+    } else {
+        options.create_new(true);
+    }
+    let mut file = try!(options.open(name));
+    file.write_all(default_conf().as_bytes())
+}
+
+/// Parses a log level like "info" or "WARN", `None` if there is no such level
+fn parse_log_level(s: &str) -> Option<LogLevelFilter> {
+    LogLevelFilter::from_str(s.trim()).ok()
+}
+
+/// Parses an IPv4 address like "127.0.0.1" or an IPv6 address like "::1"
+fn parse_address(s: &str) -> Result<IpAddr, ConfigError> {
+    if let Ok(addr) = IpAddr::from_str(s) {
+        return Ok(addr)
+    }
+
+    // Not a valid address, find out what is wrong with it
+    let parts: Vec<_> = s.split('.').collect();
+    if s.contains(':') || parts.len() != 4 {
+        return Err(ConfigError::MalformedAddress(s.into()))
+    }
+    for part in parts {
+        if part.parse::<u8>().is_err() {
+            return Err(ConfigError::BadOctet(part.into()))
+        }
+    }
+    Err(ConfigError::MalformedAddress(s.into()))
+}
+
+#[test]
+fn test_bad_octet() {
+    match parse_conf(r#"{ "address": "300.1.1.1" }"#) {
+        Err(ConfigError::BadOctet(ref s)) if s == "300" => {},
+        other => panic!("expected a bad octet error, got {:?}", other)
+    }
+}
+
+#[test]
+fn test_malformed_address() {
+    match parse_conf(r#"{ "address": "1.2.3" }"#) {
+        Err(ConfigError::MalformedAddress(ref s)) if s == "1.2.3" => {},
+        other => panic!("expected a malformed address error, got {:?}", other)
+    }
+}
+
+#[test]
+fn test_missing_config_file() {
+    match read_conf_from_json("does/not/exist.json".into()) {
+        Err(ConfigError::UnreadableFile(..)) => {},
+        other => panic!("expected an unreadable file error, got {:?}", other)
+    }
+}
+
+#[test]
+fn test_default_config() {
+    let config = parse_conf("{}").unwrap();
+    assert_eq!(config.address, IpAddr::V4(Ipv4Addr::new(127,0,0,1)));
+    assert_eq!(config.port, 4242);
+    assert_eq!(config.max_connections, 100);
+    assert_eq!(config.worker_threads, 16);
+    assert_eq!(config.metrics_port, None);
+    assert_eq!(config.users.len(), 1);
+    assert_eq!(config.users[0].username, "admin");
+    assert!(config.users[0].password_hash != server::auth::stored_hash("admin"));
+
+    // Every start gets another password
+    let other = parse_conf("{}").unwrap();
+    assert!(config.users[0].password_hash != other.users[0].password_hash);
+}
+
+#[test]
+fn test_config_users() {
+    let config = parse_conf(r#"{ "users": [
+        { "username": "alice",
+          "password_hash": "2bb80d537b1da3e38bd30361aa855686bde0eacd7162fef6a25fe97bf527a25b" },
+        { "username": "bob",
+          "password_hash": "f52fbd32b2b3b86ff88ef6c490628285f482af15ddcb29541f94bcf526a3f6c7" }
+    ] }"#).unwrap();
+    assert_eq!(config.users.len(), 2);
+    assert_eq!(config.users[1].password_hash, server::auth::stored_hash("hunter2"));
+
+    match parse_conf(r#"{ "users": [ { "username": "eve", "password_hash": "xyz" } ] }"#) {
+        Err(ConfigError::BadPasswordHash(ref s)) if s == "eve" => {},
+        other => panic!("expected a bad password hash error, got {:?}", other)
+    }
+}
+
+#[test]
+fn test_metrics_port() {
+    let config = parse_conf(r#"{ "metrics_port": 9100 }"#).unwrap();
+    assert_eq!(config.metrics_port, Some(9100));
+}
+
+#[test]
+fn test_log_level() {
+    assert_eq!(parse_log_level("info"), Some(LogLevelFilter::Info));
+    assert_eq!(parse_log_level("WARN"), Some(LogLevelFilter::Warn));
+    assert_eq!(parse_log_level(" Error "), Some(LogLevelFilter::Error));
+    assert_eq!(parse_log_level("off"), Some(LogLevelFilter::Off));
+    assert_eq!(parse_log_level("verbose"), None);
+    assert_eq!(parse_log_level(""), None);
+
+    let file = decode_conf(r#"{ "log_level": "debug", "log_file": "/var/log/uosql.txt" }"#)
+        .unwrap();
+    assert_eq!(file.log_level, Some("debug".to_string()));
+    assert_eq!(file.log_file, Some("/var/log/uosql.txt".to_string()));
+}
+
+#[test]
+fn test_ipv6_address() {
+    let config = parse_conf(r#"{ "address": "::1" }"#).unwrap();
+    assert_eq!(config.address, IpAddr::from_str("::1").unwrap());
+
+    match parse_conf(r#"{ "address": "::1::2" }"#) {
+        Err(ConfigError::MalformedAddress(_)) => {},
+        other => panic!("expected a malformed address error, got {:?}", other)
+    }
+}
+
+#[test]
+fn test_init_config() {
+    use std::env;
+    use std::fs;
+
+    let name = env::temp_dir().join(format!("uosql-init-{}.json", std::process::id()));
+    let name = name.to_str().unwrap();
+    let _ = fs::remove_file(name);
+
+    write_default_conf(name, false).unwrap();
+    let file = read_conf_from_json(name.into()).unwrap();
+    assert_eq!(file.log_level, Some("trace".to_string()));
+    assert_eq!(file.log_file, Some(DEFAULT_LOG_FILE.to_string()));
+    let config = build_conf(file).unwrap();
+    let defaults = parse_conf("{}").unwrap();
+    assert_eq!(config.address, defaults.address);
+    assert_eq!(config.port, defaults.port);
+    assert_eq!(config.dir, defaults.dir);
+    assert_eq!(config.max_connections, defaults.max_connections);
+    assert_eq!(config.worker_threads, defaults.worker_threads);
+    assert_eq!(config.query_cache_size, defaults.query_cache_size);
+
+    // an existing file is only replaced with --force
+    fs::File::create(name).unwrap();
+    match write_default_conf(name, false) {
+        Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => {},
+        other => panic!("expected the file to exist, got {:?}", other)
     }
+    write_default_conf(name, true).unwrap();
+    assert!(read_conf_from_json(name.into()).is_ok());
+    fs::remove_file(name).unwrap();
 }
@@ -5,14 +5,18 @@
 //! - check if a username-password pair is valid
 //! - load the corresponding user
 //! - check user permissions for every query
+//! - hash passwords for the login handshake
 //!
 
 use super::storage;
+use rand::{self, Rng};
 /// Contains information about the user that opened the connection. Is used
 /// for every type of access control.
 pub struct User {
     pub _name: String,
     pub _currentDatabase: Option<storage::Database>,
+    /// Directory that contains the databases as subdirectories
+    pub data_dir: String,
 }
 
 /// Errors that may occur during user authentication
@@ -21,18 +25,180 @@ pub enum AuthError {
     WrongPassword,
 }
 
-/// Validates username and password and returns the matched user.
-///
-/// **Note:** Currently nothing is checked yet and a meaningless `User` object
-/// is returned!
+/// Login data of a user as configured for the server. The password is only
+/// stored as its hash, see `stored_hash`.
+#[derive(Debug, Clone)]
+pub struct Credentials {
+    pub username: String,
+    pub password_hash: Vec<u8>,
+}
+
+/// Validates username and password against the configured users and returns
+/// the matched user. The client only sends the salted hash of its password,
+/// see `hash_password`.
 ///
 /// # Failures
 /// If the user was not found or the password does not match, an `Err` value
-/// is returned. See `AuthError` for more information.
-pub fn find_user(_name: &str, _passwd: &str) -> Result<User, AuthError> {
-    debug!("User '{}' was succesfully (pseudo-!) authenticated", _name);
+/// is returned. See `AuthError` for more information. Both should be
+/// reported the same way to the client.
+pub fn find_user(users: &[Credentials], name: &str, passwd_hash: &[u8], salt: &[u8])
+    -> Result<User, AuthError>
+{
+    let creds = match users.iter().find(|c| c.username == name) {
+        Some(c) => c,
+        None => return Err(AuthError::UserNotFound)
+    };
+    if !constant_time_eq(&salted_hash(salt, &creds.password_hash), passwd_hash) {
+        return Err(AuthError::WrongPassword)
+    }
+
+    debug!("User '{}' was succesfully authenticated", name);
     Ok(User {
-        _name: _name.into(),
+        _name: name.into(),
         _currentDatabase: None,
+        data_dir: ".".into(),
     })
 }
+
+/// Compares two hashes without stopping at the first difference, so the
+/// time taken does not tell how much of the hash was correct.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Length of the salt sent with every greeting.
+pub const SALT_LEN: usize = 16;
+
+/// Creates a random salt for one login handshake.
+pub fn make_salt() -> Vec<u8> {
+    let mut salt = vec![0; SALT_LEN];
+    rand::thread_rng().fill_bytes(&mut salt);
+    salt
+}
+
+/// Hashes the password for storing it in the server config: SHA-256 of the
+/// password.
+pub fn stored_hash(password: &str) -> Vec<u8> {
+    sha256(password.as_bytes())
+}
+
+/// Hashes the password for the login handshake: SHA-256 of the salt followed
+/// by the stored hash of the password. The server can check it without
+/// knowing the password.
+pub fn hash_password(salt: &[u8], password: &str) -> Vec<u8> {
+    salted_hash(salt, &stored_hash(password))
+}
+
+fn salted_hash(salt: &[u8], stored: &[u8]) -> Vec<u8> {
+    let mut data = salt.to_vec();
+    data.extend(stored.iter().cloned());
+    sha256(&data)
+}
+
+const SHA256_K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// SHA-256 digest of `data`.
+fn sha256(data: &[u8]) -> Vec<u8> {
+    let mut h: [u32; 8] = [
+        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a,
+        0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+    ];
+
+    // pad to a multiple of 64 bytes: 0x80, zeros, bit length (big endian)
+    let mut msg = data.to_vec();
+    let bit_len = (data.len() as u64).wrapping_mul(8);
+    msg.push(0x80);
+    while msg.len() % 64 != 56 {
+        msg.push(0);
+    }
+    for i in 0..8 {
+        msg.push((bit_len >> (56 - 8 * i)) as u8);
+    }
+
+    for chunk in msg.chunks(64) {
+        let mut w = [0u32; 64];
+        for i in 0..16 {
+            w[i] = (chunk[4 * i] as u32) << 24 | (chunk[4 * i + 1] as u32) << 16
+                | (chunk[4 * i + 2] as u32) << 8 | chunk[4 * i + 3] as u32;
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+        }
+
+        let mut v = h;
+        for i in 0..64 {
+            let s1 = v[4].rotate_right(6) ^ v[4].rotate_right(11) ^ v[4].rotate_right(25);
+            let ch = (v[4] & v[5]) ^ (!v[4] & v[6]);
+            let t1 = v[7].wrapping_add(s1).wrapping_add(ch).wrapping_add(SHA256_K[i])
+                .wrapping_add(w[i]);
+            let s0 = v[0].rotate_right(2) ^ v[0].rotate_right(13) ^ v[0].rotate_right(22);
+            let maj = (v[0] & v[1]) ^ (v[0] & v[2]) ^ (v[1] & v[2]);
+            let t2 = s0.wrapping_add(maj);
+            v = [t1.wrapping_add(t2), v[0], v[1], v[2], v[3].wrapping_add(t1), v[4], v[5], v[6]];
+        }
+        for i in 0..8 {
+            h[i] = h[i].wrapping_add(v[i]);
+        }
+    }
+
+    let mut digest = Vec::with_capacity(32);
+    for word in h.iter() {
+        for i in 0..4 {
+            digest.push((word >> (24 - 8 * i)) as u8);
+        }
+    }
+    digest
+}
+
+#[test]
+fn test_sha256() {
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    assert_eq!(hex(&sha256(b"")),
+        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+    assert_eq!(hex(&sha256(b"abc")),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    assert_eq!(hex(&sha256(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")),
+        "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1");
+}
+
+#[test]
+fn test_hash_password() {
+    let salt = make_salt();
+    assert_eq!(salt.len(), SALT_LEN);
+    assert_eq!(hash_password(&salt, "pw"), hash_password(&salt, "pw"));
+    assert!(hash_password(&salt, "pw") != hash_password(&make_salt(), "pw"));
+    assert!(hash_password(&salt, "pw") != hash_password(&salt, "pw2"));
+}
+
+#[test]
+fn test_find_user() {
+    let users = vec![
+        Credentials { username: "alice".into(), password_hash: stored_hash("secret") },
+        Credentials { username: "bob".into(), password_hash: stored_hash("hunter2") },
+    ];
+    let salt = make_salt();
+
+    assert!(find_user(&users, "alice", &hash_password(&salt, "secret"), &salt).is_ok());
+    match find_user(&users, "bob", &hash_password(&salt, "secret"), &salt) {
+        Err(AuthError::WrongPassword) => {},
+        _ => panic!("bob logged in with alice's password")
+    }
+    match find_user(&users, "carol", &hash_password(&salt, "secret"), &salt) {
+        Err(AuthError::UserNotFound) => {},
+        _ => panic!("unknown user logged in")
+    }
+}
@@ -0,0 +1,81 @@
+//! The configuration of a server
+//!
+//! `server.rs` reads it from a JSON file, embedders and tests can build it
+//! with `Config::new` and change the fields they need.
+use std::net::IpAddr;
+use auth;
+
+/// A struct for managing configurations
+#[derive(Debug)]
+pub struct Config {
+    pub address: IpAddr,
+    pub port: u16,
+    pub dir: String,
+    /// Every connection occupies a worker thread while it is open, so at
+    /// most `worker_threads` connections are accepted, even if this is higher
+    pub max_connections: usize,
+    pub worker_threads: usize,
+    /// Seconds to wait for open connections on shutdown before they are
+    /// closed forcibly
+    pub shutdown_timeout: u64,
+    /// Whether executed queries are logged with their execution time
+    pub log_queries: bool,
+    /// Queries are shortened to this many characters in the log
+    pub query_log_length: usize,
+    /// The users that may log in
+    pub users: Vec<auth::Credentials>,
+    /// PEM files with the certificate and PKCS #8 key of the server. If set,
+    /// only TLS connections are accepted (needs the `tls` feature)
+    pub tls_cert: Option<String>,
+    pub tls_key: Option<String>,
+    /// Number of select results that are kept to answer identical queries,
+    /// 0 disables the cache
+    pub query_cache_size: usize,
+    /// Number of parsed queries that are kept, so queries that are sent
+    /// again are not parsed again. 0 disables the cache
+    pub parse_cache_size: usize,
+    /// Port on `address` serving the counters of the server for Prometheus
+    /// at `/metrics`, none by default
+    pub metrics_port: Option<u16>
+}
+
+impl Config {
+    /// A server listening on `address` and `port` with its databases in
+    /// `dir`. All other fields have the defaults of a missing entry in the
+    /// config file, except that no user may log in before one is added to
+    /// `users`.
+    pub fn new(address: IpAddr, port: u16, dir: String) -> Config {
+        Config {
+            address: address,
+            port: port,
+            dir: dir,
+            max_connections: 100,
+            worker_threads: 16,
+            shutdown_timeout: 10,
+            log_queries: true,
+            query_log_length: 200,
+            users: vec![],
+            tls_cert: None,
+            tls_key: None,
+            query_cache_size: 100,
+            parse_cache_size: 100,
+            metrics_port: None
+        }
+    }
+}
+
+#[test]
+fn test_new() {
+    use std::net::Ipv4Addr;
+
+    let address = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+    let config = Config { worker_threads: 2, .. Config::new(address, 4242, "data".into()) };
+    assert_eq!(config.address, address);
+    assert_eq!(config.port, 4242);
+    assert_eq!(config.dir, "data");
+    assert_eq!(config.worker_threads, 2);
+    assert_eq!(config.max_connections, 100);
+    assert!(config.users.is_empty());
+    assert_eq!(config.tls_cert, None);
+    assert_eq!(config.metrics_port, None);
+}
@@ -1,30 +1,212 @@
 //! Contains the entry point code for handling an incoming connection.
 //!
-use std::net::TcpStream;
+use net::Stream;
 use net;
+use metrics::CountingStream;
 use auth;
 use parse;
 use super::query;
 use net::types::*;
-use storage::{ResultSet};
-use storage::types::{SqlType, Column};
+use parse::ast::{Query, DefStmt, CreateStmt, AltStmt, DropStmt, ManipulationStmt};
+use storage::{self, Database, Column, SqlType, ResultSet, QueryCache};
 use std::error::Error;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::fmt::Debug;
+use std::io::Write;
+use std::time::{Duration, Instant};
+use super::Config;
 
-pub fn handle(mut stream: TcpStream) {
+/// Cancel keys and flags of the running queries of all connections by
+/// connection id.
+///
+/// Every connection is handled by its own thread, which is busy while it
+/// executes a query. To cancel it, a client sends `Command::Cancel` with the
+/// secret key from the greeting over a second connection, whose thread sets
+/// the flag of the first one.
+pub type CancelRegistry = Arc<Mutex<HashMap<u64, (u64, Arc<AtomicBool>)>>>;
+
+/// Seconds a connection beyond the connection limit may take to send its
+/// cancel request
+pub const CANCEL_TIMEOUT: u64 = 3;
+
+/// Server wide numbers reported by `Command::ServerInfo` and exported by
+/// `metrics`.
+pub struct ServerStats {
+    /// Seconds since the Unix epoch when the server was started
+    pub started: u64,
+    /// Number of open connections, as counted by the connection limit
+    pub open: Arc<AtomicUsize>,
+    /// Connections accepted, including the rejected ones
+    pub accepted: AtomicUsize,
+    /// Queries executed, counted like the logged queries
+    pub queries: AtomicUsize,
+    /// Queries that failed
+    pub errors: AtomicUsize,
+    /// Bytes read from and written to logged in clients
+    pub bytes_received: AtomicUsize,
+    pub bytes_sent: AtomicUsize,
+}
+
+impl ServerStats {
+    /// Stats of a server started now, all counters are zero
+    pub fn new(started: u64, open: Arc<AtomicUsize>) -> ServerStats {
+        ServerStats {
+            started: started,
+            open: open,
+            accepted: AtomicUsize::new(0),
+            queries: AtomicUsize::new(0),
+            errors: AtomicUsize::new(0),
+            bytes_received: AtomicUsize::new(0),
+            bytes_sent: AtomicUsize::new(0),
+        }
+    }
+}
+
+/// Registers the cancel flag of a connection and removes it again when the
+/// connection is closed.
+struct Registration {
+    id: u64,
+    registry: CancelRegistry,
+}
+
+impl Registration {
+    fn new(id: u64, key: u64, flag: Arc<AtomicBool>, registry: CancelRegistry)
+        -> Registration
+    {
+        registry.lock().unwrap().insert(id, (key, flag));
+        Registration { id: id, registry: registry }
+    }
+}
+
+/// Sets the cancel flag of connection `target` if `key` is its cancel key.
+/// A wrong key is reported like an unknown connection.
+fn cancel_query(registry: &CancelRegistry, target: u64, key: u64) -> Result<(), net::Error> {
+    match registry.lock().unwrap().get(&target) {
+        Some(&(ref k, ref flag)) if *k == key => {
+            flag.store(true, Ordering::SeqCst);
+            Ok(())
+        },
+        _ => Err(net::Error::UnknownConnection)
+    }
+}
+
+/// Answers a cancel request with `Ok` or an error package
+fn send_cancel_result<S: Write>(stream: &mut S, res: Result<(), net::Error>) {
+    let sent = match res {
+        Ok(_) => net::send_info_package(stream, PkgType::Ok),
+        Err(e) => net::send_error_package(stream, e.into())
+    };
+    match sent {
+        Ok(_) => { },
+        Err(_) => warn!("Failed to send packet.")
+    }
+}
+
+/// Handles a connection beyond the connection limit, which doesn't get a
+/// worker of its own. It may only cancel the query of another connection,
+/// a login is answered with `TooManyConnections`.
+pub fn handle_cancel(mut stream: Stream, id: u64, registry: CancelRegistry) {
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(CANCEL_TIMEOUT)));
+    match net::do_handshake(&mut stream, id, ::rand::random()) {
+        Ok((net::Hello::Cancel(target, key), _)) =>
+            send_cancel_result(&mut stream, cancel_query(&registry, target, key)),
+        Ok(_) => {
+            warn!("Connection limit reached, rejecting connection {}", id);
+            let _ = net::send_error_package(&mut stream, net::Error::TooManyConnections.into());
+        },
+        Err(e) => debug!("Connection {} closed: {:?}", id, e),
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        self.registry.lock().unwrap().remove(&self.id);
+    }
+}
+
+/// An open transaction. Queries write to the tables right away, so every
+/// database is snapshotted before the transaction first runs a query in it.
+/// A transaction that is dropped without `commit` is rolled back, e.g. if
+/// the client disconnects. Restoring a snapshot evicts the cached results of
+/// its database.
+struct Transaction {
+    snapshots: Vec<storage::Snapshot>,
+    cache: Arc<QueryCache>,
+}
+
+impl Transaction {
+    fn new(cache: Arc<QueryCache>) -> Transaction {
+        Transaction { snapshots: Vec::new(), cache: cache }
+    }
+
+    /// Takes a snapshot of `db`, unless the transaction already has one
+    fn track(&mut self, db: Option<&Database>) -> Result<(), storage::Error> {
+        if let Some(db) = db {
+            if !self.snapshots.iter().any(|s| s.database() == db.name) {
+                self.snapshots.push(try!(db.snapshot()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Keeps the changes of the transaction
+    fn commit(mut self) {
+        self.snapshots.clear();
+    }
+
+    /// Undoes the changes of the transaction
+    fn rollback(mut self) -> Result<(), storage::Error> {
+        for snapshot in self.snapshots.drain(..) {
+            let res = snapshot.restore();
+            self.cache.evict_database(snapshot.database());
+            try!(res);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Transaction {
+    fn drop(&mut self) {
+        for snapshot in self.snapshots.drain(..) {
+            if let Err(e) = snapshot.restore() {
+                error!("Rollback of database {} failed: {:?}", snapshot.database(), e);
+            }
+            self.cache.evict_database(snapshot.database());
+        }
+    }
+}
+
+pub fn handle(mut stream: Stream, id: u64, registry: CancelRegistry, config: Arc<Config>,
+    stats: Arc<ServerStats>, cache: Arc<QueryCache>, parse_cache: Arc<parse::ParseCache>)
+{
     // Logging about the new connection
     let addr = stream.peer_addr()
         .map(|a| a.to_string())
         .unwrap_or("???".into());
-    info!("Handling connection from {}", addr);
+    info!("Handling connection {} from {}", id, addr);
+
+    let cancel = Arc::new(AtomicBool::new(false));
+    let cancel_key = ::rand::random();
+    let _registration = Registration::new(id, cancel_key, cancel.clone(), registry.clone());
 
     // Perform handshake, check user login.
-    let res = net::do_handshake(&mut stream);
+    let res = net::do_handshake(&mut stream, id, cancel_key);
 
     let mut user;
+    let mut compress = false;
     match res {
-        Ok((name, pw)) => {
+        // a second connection of a client, only to stop a query
+        Ok((net::Hello::Cancel(target, key), _)) => {
+            send_cancel_result(&mut stream, cancel_query(&registry, target, key));
+            return
+        },
+        Ok((net::Hello::Login(login), salt)) => {
+            compress = login.compression;
             info!("Connection established. Handshake sent");
-            user = match auth::find_user(&name, &pw) {
+            user = match auth::find_user(&config.users, &login.username,
+                &login.password_hash, &salt) {
                 Ok(u) => {
                     match net::send_info_package(&mut stream,
                         PkgType::AccGranted)
@@ -48,6 +230,19 @@ pub fn handle(mut stream: TcpStream) {
         }
     };
 
+    // Only the traffic of logged in clients is counted
+    let mut stream = CountingStream::new(stream, stats.clone());
+
+    // The databases of this server live in the configured directory
+    user.data_dir = config.dir.clone();
+
+    // The transaction the client started with `Command::Begin`
+    let mut transaction = None;
+
+    // Prepared statements of this connection by id
+    let mut prepared = HashMap::new();
+    let mut next_prepared_id = 0;
+
     // Read commands from the client (with help of `net`)
     loop {
         //get the command from the stream
@@ -76,51 +271,441 @@ pub fn handle(mut stream: TcpStream) {
                         Err(_) => warn!("Failed to send packet.")
                     }
                 },
+                // report version, start time and open connections
+                Command::ServerInfo => {
+                    let info = ServerInfo {
+                        version: env!("CARGO_PKG_VERSION").into(),
+                        protocol_version: net::PROTOCOL_VERSION,
+                        started: stats.started,
+                        connections: stats.open.load(Ordering::SeqCst) as u64,
+                    };
+                    match net::send_server_info_package(&mut stream, info) {
+                        Ok(_) => { },
+                        Err(_) => warn!("Failed to send packet.")
+                    }
+                },
+                // open a transaction, closed by commit or rollback
+                Command::Begin | Command::Commit | Command::Rollback => {
+                    let res = match (cmd, transaction.take()) {
+                        (Command::Begin, Some(t)) => {
+                            transaction = Some(t);
+                            Err(net::Error::NestedTransaction)
+                        },
+                        (Command::Begin, None) => {
+                            transaction = Some(Transaction::new(cache.clone()));
+                            Ok(())
+                        },
+                        (Command::Commit, Some(t)) => Ok(t.commit()),
+                        (_, Some(t)) => t.rollback().map_err(|e|
+                            net::Error::Execution(format!("rollback failed: {:?}", e))),
+                        (_, None) => Err(net::Error::NoTransaction),
+                    };
+                    let sent = match res {
+                        Ok(_) => net::send_info_package(&mut stream, PkgType::Ok),
+                        Err(e) => net::send_error_package(&mut stream, e.into())
+                    };
+                    match sent {
+                        Ok(_) => { },
+                        Err(_) => warn!("Failed to send packet.")
+                    }
+                },
                 // send the query string for parsing
                 Command::Query(q) => {
+                    execute_query(&mut stream, &mut transaction, &q, &mut user, &cancel,
+                        &config, &stats, &cache, &parse_cache, &addr,
+                        ResponseMode::whole(compress));
+                    continue
+                },
+                // the same, but the rows are sent in batches
+                Command::StreamQuery(q) => {
+                    execute_query(&mut stream, &mut transaction, &q, &mut user, &cancel,
+                        &config, &stats, &cache, &parse_cache, &addr, ResponseMode::Streamed);
+                },
+                // execute the queries one after another, then mark the end
+                Command::Batch(queries, mode) => {
+                    for q in &queries {
+                        let ok = execute_query(&mut stream, &mut transaction, q, &mut user,
+                            &cancel, &config, &stats, &cache, &parse_cache, &addr,
+                            ResponseMode::whole(compress));
+                        // a cancelled query cancels the whole batch
+                        let cancelled = cancel.load(Ordering::SeqCst);
+                        if cancelled || (!ok && mode == BatchMode::StopOnError) {
+                            break
+                        }
+                    }
+                    match net::send_info_package(&mut stream, PkgType::BatchEnd) {
+                        Ok(_) => { },
+                        Err(_) => warn!("Failed to send packet.")
+                    }
+                },
+                // select the database for the following queries
+                Command::Use(name) => {
+                    let sent = match Database::load_in(&config.dir, &name) {
+                        Ok(db) => {
+                            user._currentDatabase = Some(db);
+                            net::send_info_package(&mut stream, PkgType::Ok)
+                        },
+                        Err(_) => net::send_error_package(&mut stream,
+                            net::Error::UnknownDatabase.into())
+                    };
+                    match sent {
+                        Ok(_) => { },
+                        Err(_) => warn!("Failed to send packet.")
+                    }
+                },
+                // send the table names of the current database
+                Command::ListTables => {
+                    let names = match user._currentDatabase {
+                        Some(ref db) => db.table_names().map_err(|e|
+                            net::Error::Execution(format!("{:?}", e))),
+                        None => Err(net::Error::NoDatabaseSelected)
+                    };
+                    let sent = match names {
+                        Ok(names) => net::send_response_package(&mut stream,
+                            string_result("table", &names)),
+                        Err(e) => net::send_error_package(&mut stream, e.into())
+                    };
+                    match sent {
+                        Ok(_) => { },
+                        Err(_) => warn!("Failed to send packet.")
+                    }
+                },
+                // send the columns of the table without any rows
+                Command::Describe(table) => {
+                    let columns = match user._currentDatabase {
+                        Some(ref db) => db.load_table(&table)
+                            .map(|t| t.columns().to_vec())
+                            .map_err(|_| net::Error::UnknownTable),
+                        None => Err(net::Error::NoDatabaseSelected)
+                    };
+                    let sent = match columns {
+                        Ok(columns) => net::send_response_package(&mut stream,
+                            ResultSet { data: vec![], columns: columns }),
+                        Err(e) => net::send_error_package(&mut stream, e.into())
+                    };
+                    match sent {
+                        Ok(_) => { },
+                        Err(_) => warn!("Failed to send packet.")
+                    }
+                },
+                // check the syntax of the query without executing it
+                Command::Validate(q) => {
+                    let sent = match parse::parse(&q) {
+                        Ok(_) => net::send_info_package(&mut stream, PkgType::Ok),
+                        Err(error) =>
+                            net::send_error_package(&mut stream, net::Error::UnEoq(error).into())
+                    };
+                    match sent {
+                        Ok(_) => { },
+                        Err(_) => warn!("Failed to send packet.")
+                    }
+                },
+                // send the parsed query as SQL without executing it
+                Command::Explain(q) => {
+                    let sent = match parse::parse(&q) {
+                        Ok(tree) => net::send_explain_package(&mut stream, &tree.to_sql()),
+                        Err(error) =>
+                            net::send_error_package(&mut stream, net::Error::UnEoq(error).into())
+                    };
+                    match sent {
+                        Ok(_) => {},
+                        Err(_) => warn!("Failed to send packet.")
+                    }
+                },
+                // stop the running query of another connection
+                Command::Cancel(target, key) =>
+                    send_cancel_result(&mut stream, cancel_query(&registry, target, key)),
+                // store the statement, its placeholders are bound on execution
+                Command::Prepare(q) => {
+                    let sent = match parse::count_params(&q) {
+                        Ok(count) => {
+                            next_prepared_id += 1;
+                            prepared.insert(next_prepared_id, q);
+                            net::send_prepared_package(&mut stream, PreparedInfo {
+                                id: next_prepared_id,
+                                param_count: count as u64
+                            })
+                        },
+                        Err(error) =>
+                            net::send_error_package(&mut stream, net::Error::UnEoq(error).into())
+                    };
+                    match sent {
+                        Ok(_) => { },
+                        Err(_) => warn!("Failed to send packet.")
+                    }
+                },
+                Command::ExecutePrepared(id, params) => {
+                    let bound = match prepared.get(&id) {
+                        Some(q) => parse::bind_params(q, &params).map_err(|e| net::Error::UnEoq(e)),
+                        None => Err(net::Error::UnknownStatement)
+                    };
+                    match bound {
+                        Ok(q) => {
+                            execute_query(&mut stream, &mut transaction, &q, &mut user,
+                                &cancel, &config, &stats, &cache, &parse_cache, &addr,
+                            ResponseMode::whole(compress));
+                        },
+                        Err(e) => match net::send_error_package(&mut stream, e.into()) {
+                            Ok(_) => { },
+                            Err(_) => warn!("Failed to send error.")
+                        }
+                    }
+                }
+            },
+            // a package of the wrong type, which was read completely
+            Err(net::Error::UnexpectedPkg) => {
+                match net::send_error_package(&mut stream, net::Error::UnexpectedPkg.into()) {
+                    Ok(_) => { },
+                    Err(_) => warn!("Failed to send error.")
+                }
+            },
+            // the client went away or the server is shutting down. The
+            // transaction, cancel registration and connection slot are
+            // released when they are dropped.
+            Err(ref e) if e.is_disconnect() => {
+                debug!("Client of connection {} disconnected", id);
+                return
+            },
+            // the rest of the stream can't be interpreted anymore
+            Err(e) => {
+                warn!("Closing connection {}: {:?}", id, e);
+                return
+            }
+        }
+    }
+}
+
+/// How the rows of a result are sent to the client
+enum ResponseMode {
+    Whole,
+    Compressed,
+    Streamed,
+}
+
+impl ResponseMode {
+    /// The whole result in one package, compressed if the client asked for it
+    fn whole(compress: bool) -> ResponseMode {
+        if compress { ResponseMode::Compressed } else { ResponseMode::Whole }
+    }
+}
 
-                    debug!("Query received, dispatch query to parser.");
+/// Parses and executes the query and sends the result to the client as
+/// given by `mode`. The query is stopped if `cancel` is set while it runs
+/// or while its rows are streamed. Returns false if the query failed.
+fn execute_query<S: Write>(stream: &mut S, transaction: &mut Option<Transaction>, q: &str,
+    user: &mut auth::User, cancel: &Arc<AtomicBool>, config: &Config, stats: &ServerStats,
+    cache: &QueryCache, parse_cache: &parse::ParseCache, addr: &str, mode: ResponseMode) -> bool
+{
+    debug!("Query received, dispatch query to parser.");
+    stats.queries.fetch_add(1, Ordering::SeqCst);
 
-                    // Call parser to obtain AST
-                    let ast = parse::parse(&q);
+    // the changes of the query have to be undoable
+    if let Some(ref mut t) = *transaction {
+        if let Err(e) = t.track(user._currentDatabase.as_ref()) {
+            let err = net::Error::Execution(format!("snapshot failed: {:?}", e));
+            stats.errors.fetch_add(1, Ordering::SeqCst);
+            match net::send_error_package(stream, err.into()) {
+                Ok(_) => {},
+                Err(_) => warn!("Failed to send error.")
+            }
+            return false
+        }
+    }
 
-                    match ast {
-                        Ok(tree) => {
-                            debug!("{:?}", tree);
+    // Only parsing and execution are timed, not sending the result
+    let start = Instant::now();
 
-                            // Pass AST to query executer
-                            let mut r2 = query::execute_from_ast(tree, &mut user);
+    // a cancel request for an earlier query must not stop this one
+    cancel.store(false, Ordering::SeqCst);
 
-                            debug!("{:?}", r2);
+    // Call parser to obtain AST, unless the query was parsed before
+    let ast = parse_cache.parse(q);
 
-                            let r = r2.unwrap_or(
-                                    ResultSet { data: vec![], columns: vec![
-                                        Column::new("error", SqlType::Int, false,
-                                        "error mind the error, not an error again, I hate errors",
-                                        false)]
-                                    }
-                                );
-                            // Send response package
-                            match net::send_response_package(&mut stream, r) {
-                                Ok(_) => { },
-                                Err(_) => warn!("Failed to send packet.")
-                            }
-                        },
+    match ast {
+        Ok(tree) => {
+            debug!("{:?}", tree);
 
-                        Err(error) => {
-                            error!("{:?}", error);
-                            match net::send_error_package(&mut stream,
-                                net::Error::UnEoq(error).into())
-                            {
-                                Ok(_) => {},
-                                Err(_) => warn!("Failed to send error.")
-                            }
-                        }
+            // Identical selects are answered from the cache
+            let key = cache_key(&tree, user);
+            if let Some((ref db, ref text, _)) = key {
+                if let Some(rows) = cache.get(db, text) {
+                    debug!("Query answered from the cache");
+                    if config.log_queries {
+                        log_query(config, addr, q, start.elapsed(), None::<&query::ExecutionError>);
                     }
-                    continue
+                    send_result(stream, query::ExecutionResult::Rows(rows), mode, cancel);
+                    return true
                 }
-            },
-            Err(_) => continue // TODO: error handling
+            }
+            let generation = cache.generation();
+            let database = user._currentDatabase.as_ref().map(|db| db.name.clone());
+            let written = if key.is_none() { Some(tree.clone()) } else { None };
+
+            // Pass AST to query executer
+            let r2 = query::execute_from_ast_cancellable(tree, user, cancel.clone());
+
+            // even a failed query may have written some rows
+            if let Some(ref tree) = written {
+                evict_written(cache, database.as_ref().map(|db| &db[..]), tree);
+            }
+
+            debug!("{:?}", r2);
+            if config.log_queries {
+                log_query(config, addr, q, start.elapsed(), r2.as_ref().err());
+            }
+
+            let r = match r2 {
+                Ok(r) => r,
+                Err(e) => {
+                    let err = match e {
+                        query::ExecutionError::Cancelled => net::Error::Cancelled,
+                        query::ExecutionError::NoDatabaseSelected =>
+                            net::Error::NoDatabaseSelected,
+                        query::ExecutionError::ParseError(e) => net::Error::UnEoq(e),
+                        query::ExecutionError::UnknownColumn => net::Error::UnknownColumn,
+                        query::ExecutionError::StorageError(storage::Error::UnknownTable) =>
+                            net::Error::UnknownTable,
+                        query::ExecutionError::StorageError(
+                            storage::Error::PrimaryKeyValueExists) =>
+                            net::Error::ConstraintViolation("duplicate primary key".into()),
+                        query::ExecutionError::NullValue(column) => net::Error::ConstraintViolation(
+                            format!("column `{}` must not be null", column)),
+                        e => net::Error::Execution(format!("{:?}", e)),
+                    };
+                    stats.errors.fetch_add(1, Ordering::SeqCst);
+                    match net::send_error_package(stream, err.into()) {
+                        Ok(_) => {},
+                        Err(_) => warn!("Failed to send error.")
+                    }
+                    return false
+                }
+            };
+            if let (Some((db, text, tables)), &query::ExecutionResult::Rows(ref rows)) = (key, &r) {
+                cache.insert(&db, &text, tables, rows.clone(), generation);
+            }
+            send_result(stream, r, mode, cancel);
+            true
+        },
+
+        Err(error) => {
+            if config.log_queries {
+                log_query(config, addr, q, start.elapsed(), Some(&error));
+            }
+            error!("{:?}", error);
+            stats.errors.fetch_add(1, Ordering::SeqCst);
+            match net::send_error_package(stream,
+                net::Error::UnEoq(error).into())
+            {
+                Ok(_) => {},
+                Err(_) => warn!("Failed to send error.")
+            }
+            false
         }
     }
 }
+
+/// Sends response package: rows for queries, the number of changed rows for
+/// insert, update and delete
+fn send_result<S: Write>(stream: &mut S, r: query::ExecutionResult, mode: ResponseMode,
+    cancel: &AtomicBool)
+{
+    let sent = match r {
+        query::ExecutionResult::Rows(rows) => match mode {
+            ResponseMode::Whole => net::send_response_package(stream, rows),
+            ResponseMode::Compressed => net::send_result_package(stream, rows, true),
+            ResponseMode::Streamed => net::send_streamed_response(stream, rows, cancel),
+        },
+        query::ExecutionResult::Affected(count) =>
+            net::send_affected_rows_package(stream, count),
+    };
+    match sent {
+        Ok(_) => { },
+        Err(_) => warn!("Failed to send packet.")
+    }
+}
+
+/// The current database, the normalized text and the tables of a select
+/// whose result can be cached. The text has to parse to the same query
+/// again, otherwise different queries could share it (e.g. a quoted table
+/// name with a space and a table with an alias).
+fn cache_key(tree: &Query, user: &auth::User) -> Option<(String, String, Vec<String>)> {
+    let (db, tables) = match (tree, user._currentDatabase.as_ref()) {
+        (&Query::ManipulationStmt(ManipulationStmt::Select(ref stmt)), Some(db)) =>
+            (db.name.clone(), stmt.tid.iter().chain(stmt.joins.iter().map(|j| &j.right))
+                .cloned().collect()),
+        _ => return None,
+    };
+    let text = tree.to_sql();
+    match parse::parse(&text) {
+        Ok(ref reparsed) if reparsed == tree => Some((db, text, tables)),
+        _ => None,
+    }
+}
+
+/// Evicts the cached results of the tables the executed query may have
+/// written, a dropped database loses all of its results
+fn evict_written(cache: &QueryCache, database: Option<&str>, tree: &Query) {
+    let table = match tree {
+        &Query::DefStmt(DefStmt::Drop(DropStmt::Database(ref name))) => {
+            cache.evict_database(name);
+            return
+        },
+        &Query::DefStmt(DefStmt::Create(CreateStmt::Table(ref stmt))) => &stmt.tid,
+        &Query::DefStmt(DefStmt::Alter(AltStmt::Table(ref stmt))) => &stmt.tid,
+        &Query::DefStmt(DefStmt::Drop(DropStmt::Table(ref name))) => name,
+        &Query::ManipulationStmt(ManipulationStmt::Insert(ref stmt)) => &stmt.tid,
+        &Query::ManipulationStmt(ManipulationStmt::Update(ref stmt)) => &stmt.tid,
+        &Query::ManipulationStmt(ManipulationStmt::Delete(ref stmt)) => &stmt.tid,
+        _ => return,
+    };
+    if let Some(db) = database {
+        cache.evict_table(db, table);
+    }
+}
+
+/// Maximum length of the strings in a `string_result`
+const MAX_NAME_LEN: u16 = 128;
+
+/// Creates a result of one string column with one row per value
+fn string_result(column: &str, values: &[String]) -> ResultSet {
+    let sql_type = SqlType::VarChar(MAX_NAME_LEN);
+    let mut data = Vec::new();
+    for v in values {
+        // only fails for types other than strings
+        let _ = sql_type.encode_into(&mut data, &Value::Str(v.clone()));
+    }
+    ResultSet {
+        data: data,
+        columns: vec![Column::new(column, sql_type, false, "", false)]
+    }
+}
+
+/// Logs an executed query, failed ones with their error
+fn log_query<E: Debug>(config: &Config, addr: &str, q: &str, elapsed: Duration,
+    err: Option<&E>)
+{
+    let q = truncate_query(q, config.query_log_length);
+    let ms = elapsed.as_secs() as f64 * 1000.0 + elapsed.subsec_nanos() as f64 / 1_000_000.0;
+    match err {
+        None => info!("{} query '{}' took {:.3}ms", addr, q, ms),
+        Some(e) => warn!("{} query '{}' failed after {:.3}ms: {:?}", addr, q, ms, e),
+    }
+}
+
+/// Shortens the query to at most `len` characters
+fn truncate_query(q: &str, len: usize) -> String {
+    if q.chars().count() <= len {
+        q.into()
+    } else {
+        let mut s: String = q.chars().take(len).collect();
+        s.push_str("...");
+        s
+    }
+}
+
+#[test]
+fn test_truncate_query() {
+    assert_eq!(truncate_query("select * from t", 20), "select * from t");
+    assert_eq!(truncate_query("select * from t", 6), "select...");
+    assert_eq!(truncate_query("select 'äöü'", 10), "select 'äö...");
+}
@@ -1,50 +1,1344 @@
 extern crate bincode;
 extern crate byteorder;
+extern crate flate2;
+#[cfg(feature = "tls")]
+extern crate native_tls;
 #[macro_use]
 extern crate log;
+extern crate rand;
 extern crate rustc_serialize;
 extern crate term_painter as term;
 
 pub mod auth;
+pub mod config;
 pub mod conn;
 pub mod logger;
+pub mod metrics;
 pub mod net;
 pub mod parse;
+pub mod pool;
 pub mod query;
 pub mod storage;
+pub mod value;
 
-use std::net::{Ipv4Addr, SocketAddrV4};
+pub use config::Config;
 
-/// A struct for managing configurations
-#[derive(Debug)]
-pub struct Config {
-    pub address: Ipv4Addr,
-    pub port: u16,
-    pub dir: String
-}
+use std::cmp;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream, Shutdown};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::collections::HashMap;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 /// Listens for incoming TCP streams
 pub fn listen(config: Config) {
-    use std::net::TcpListener;
-    use std::thread;
-
-    // Converting configurations to a valid socket address
-    let sock_addr = SocketAddrV4::new(config.address, config.port);
-    let listener = TcpListener::bind(sock_addr).unwrap();
-
-    // Accept connections and process them
-    for stream in listener.incoming() {
-        match stream {
-            Ok(stream) => {
-                // Connection succeeded: Spawn thread and handle
-                thread::spawn(move|| {
-                    conn::handle(stream)
-                });
-            },
+    Server::bind(config).unwrap().run();
+}
+
+/// A bound server. `run` accepts connections until `shutdown` is called
+/// from another thread, so the server is usually shared in an `Arc`.
+pub struct Server {
+    listener: TcpListener,
+    config: Arc<Config>,
+    stopped: AtomicBool,
+    tls: Tls,
+    cache: Arc<storage::QueryCache>,
+    parse_cache: Arc<parse::ParseCache>,
+    metrics: Option<TcpListener>,
+}
+
+/// Server side of TLS, if configured
+#[cfg(feature = "tls")]
+type Tls = Option<Arc<native_tls::TlsAcceptor>>;
+#[cfg(not(feature = "tls"))]
+type Tls = ();
+
+#[cfg(feature = "tls")]
+fn load_tls(config: &Config) -> io::Result<Tls> {
+    match (&config.tls_cert, &config.tls_key) {
+        (&Some(ref cert), &Some(ref key)) =>
+            Ok(Some(Arc::new(try!(net::stream::tls_acceptor(cert, key))))),
+        (&None, &None) => Ok(None),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidInput,
+            "tls_cert and tls_key have to be set together")),
+    }
+}
+
+#[cfg(not(feature = "tls"))]
+fn load_tls(config: &Config) -> io::Result<Tls> {
+    if config.tls_cert.is_some() || config.tls_key.is_some() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput,
+            "the server was built without TLS support"))
+    }
+    Ok(())
+}
+
+/// Performs the TLS handshake if TLS is configured
+#[cfg(feature = "tls")]
+fn wrap_stream(tls: &Tls, tcp: TcpStream) -> io::Result<net::Stream> {
+    match tls {
+        &Some(ref acceptor) => net::stream::tls_accept(acceptor, tcp),
+        &None => Ok(net::Stream::Plain(tcp)),
+    }
+}
+
+#[cfg(not(feature = "tls"))]
+fn wrap_stream(_: &Tls, tcp: TcpStream) -> io::Result<net::Stream> {
+    Ok(net::Stream::Plain(tcp))
+}
+
+/// Loads the schemas of all tables in `dir` and logs the broken ones, which
+/// can not be used. Tables are read from their files for every query, so
+/// nothing is kept in memory.
+fn check_schemas(dir: &str) {
+    let databases = match storage::Database::names_in(dir) {
+        Ok(names) => names,
+        Err(e) => {
+            warn!("Could not read data directory {}: {:?}", dir, e);
+            return
+        }
+    };
+    let mut count = 0;
+    for name in databases {
+        let db = match storage::Database::load_in(dir, &name) {
+            Ok(db) => db,
             Err(e) => {
-                // Something went wrong...
-                warn!("Failed to accept incoming connection: {:?}", e);
+                warn!("Skipping database {}: {:?}", name, e);
+                continue
+            }
+        };
+        for table in db.table_names().unwrap_or(vec![]) {
+            match db.load_table(&table) {
+                Ok(_) => count += 1,
+                Err(e) => warn!("Skipping corrupt table {}.{}: {:?}", name, table, e),
+            }
+        }
+    }
+    info!("Found {} table(s) in {}", count, dir);
+}
+
+/// The open connections, to close them forcibly on shutdown
+type StreamRegistry = Arc<Mutex<HashMap<u64, TcpStream>>>;
+
+/// Decrements the number of open connections when the handling thread
+/// ends, even if it panics
+struct Slot {
+    id: u64,
+    open: Arc<AtomicUsize>,
+    streams: StreamRegistry,
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.streams.lock().unwrap().remove(&self.id);
+        self.open.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Server {
+    /// Binds a listener to the configured address, which may be an IPv4 or
+    /// an IPv6 address
+    pub fn bind(config: Config) -> io::Result<Server> {
+        let tls = try!(load_tls(&config));
+        if config.max_connections > config.worker_threads {
+            warn!("max_connections is {}, but only {} connections can be handled at once \
+                (worker_threads)", config.max_connections, config.worker_threads);
+        }
+        check_schemas(&config.dir);
+        let listener = try!(TcpListener::bind(SocketAddr::new(config.address, config.port)));
+        let metrics = match config.metrics_port {
+            Some(port) => Some(try!(TcpListener::bind(SocketAddr::new(config.address, port)))),
+            None => None,
+        };
+        Ok(Server {
+            listener: listener,
+            metrics: metrics,
+            cache: Arc::new(storage::QueryCache::new(config.query_cache_size)),
+            parse_cache: Arc::new(parse::ParseCache::new(config.parse_cache_size)),
+            config: Arc::new(config),
+            stopped: AtomicBool::new(false),
+            tls: tls,
+        })
+    }
+
+    /// The address the server listens on
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// The address the metrics are served on, if `metrics_port` is set
+    pub fn metrics_addr(&self) -> Option<SocketAddr> {
+        self.metrics.as_ref().and_then(|listener| listener.local_addr().ok())
+    }
+
+    /// The results of selects, shared by all connections
+    pub fn query_cache(&self) -> &storage::QueryCache {
+        &self.cache
+    }
+
+    /// The parsed queries, shared by all connections
+    pub fn parse_cache(&self) -> &parse::ParseCache {
+        &self.parse_cache
+    }
+
+    /// Accepts connections and hands them to a pool of `worker_threads`
+    /// threads. Connections beyond `max_connections` or `worker_threads`
+    /// may only cancel a query of another connection, a login gets an error
+    /// package.
+    ///
+    /// Returns after `shutdown` was called and all connections are closed.
+    pub fn run(&self) {
+        // Cancel flags of all connections, each connection gets its own id
+        let registry: conn::CancelRegistry = Arc::new(Mutex::new(HashMap::new()));
+        let streams: StreamRegistry = Arc::new(Mutex::new(HashMap::new()));
+        let mut next_id = 0;
+        let open = Arc::new(AtomicUsize::new(0));
+        let max_connections = cmp::min(self.config.max_connections,
+            self.config.worker_threads);
+        let config = self.config.clone();
+        let tls = self.tls.clone();
+        let cache = self.cache.clone();
+        let parse_cache = self.parse_cache.clone();
+
+        // Connections beyond the limit are handled by a thread of their own,
+        // so queries can be cancelled even if all workers are busy
+        let cancel_pool = {
+            let (registry, tls) = (registry.clone(), self.tls.clone());
+            pool::Pool::new(1, move |(stream, id): (TcpStream, u64)| {
+                match wrap_stream(&tls, stream) {
+                    Ok(stream) => conn::handle_cancel(stream, id, registry.clone()),
+                    Err(e) => warn!("TLS handshake of connection {} failed: {}", id, e),
+                }
+            })
+        };
+        let reject = |stream: TcpStream, id: u64| {
+            if let Err((mut stream, _)) = cancel_pool.try_submit((stream, id)) {
+                warn!("Connection limit of {} reached, rejecting connection", max_connections);
+                let _ = net::send_error_package(&mut stream,
+                    net::Error::TooManyConnections.into());
+            }
+        };
+        let stats = Arc::new(conn::ServerStats::new(
+            SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+            open.clone()));
+        let metrics_endpoint = self.metrics.as_ref().and_then(|listener| {
+            match listener.try_clone().and_then(|l| metrics::Endpoint::start(l, stats.clone())) {
+                Ok(endpoint) => Some(endpoint),
+                Err(e) => {
+                    warn!("Could not serve metrics: {}", e);
+                    None
+                },
+            }
+        });
+        let connection_stats = stats.clone();
+
+        // Every job is a stream with its connection id
+        let pool = pool::Pool::new(self.config.worker_threads,
+            move |(stream, id, slot): (TcpStream, u64, Slot)| {
+                let _slot = slot;
+                match wrap_stream(&tls, stream) {
+                    Ok(stream) => conn::handle(stream, id, registry.clone(), config.clone(),
+                        stats.clone(), cache.clone(), parse_cache.clone()),
+                    Err(e) => warn!("TLS handshake of connection {} failed: {}", id, e),
+                }
+            });
+
+        // Accept connections and process them
+        for stream in self.listener.incoming() {
+            if self.stopped.load(Ordering::SeqCst) {
+                break;
+            }
+            match stream {
+                Ok(stream) => {
+                    next_id += 1;
+                    connection_stats.accepted.fetch_add(1, Ordering::SeqCst);
+                    if open.fetch_add(1, Ordering::SeqCst) >= max_connections {
+                        open.fetch_sub(1, Ordering::SeqCst);
+                        reject(stream, next_id);
+                        continue;
+                    }
+                    let slot = Slot { id: next_id, open: open.clone(), streams: streams.clone() };
+                    if let Ok(clone) = stream.try_clone() {
+                        streams.lock().unwrap().insert(next_id, clone);
+                    }
+
+                    // Connection succeeded: Queue it for the workers. The
+                    // queue is only full if workers are still busy with
+                    // closed connections, waiting for them would block the
+                    // accept loop and thus `shutdown`.
+                    if let Err((stream, id, _slot)) = pool.try_submit((stream, next_id, slot)) {
+                        reject(stream, id);
+                    }
+                },
+                Err(e) => {
+                    // Something went wrong...
+                    warn!("Failed to accept incoming connection: {:?}", e);
+                },
+            }
+        }
+
+        // Give the open connections some time to finish, then close them
+        info!("Shutting down, waiting for {} connection(s)", open.load(Ordering::SeqCst));
+        let deadline = Instant::now() + Duration::from_secs(self.config.shutdown_timeout);
+        while open.load(Ordering::SeqCst) > 0 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(50));
+        }
+        for (_, stream) in streams.lock().unwrap().drain() {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+
+        // Waits for the workers to return
+        drop(pool);
+        drop(cancel_pool);
+        drop(metrics_endpoint);
+        info!("Server stopped");
+    }
+
+    /// Stops accepting connections, `run` returns once the open connections
+    /// are closed or `shutdown_timeout` has passed.
+    pub fn shutdown(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+
+        // Wake up the accept loop with a connection of our own
+        if let Ok(addr) = self.local_addr() {
+            wake_up(addr);
+        }
+    }
+}
+
+/// Connects to a listener bound to `addr`, so its blocking accept returns.
+/// A listener on all interfaces is reached via loopback.
+fn wake_up(mut addr: SocketAddr) {
+    if addr.ip() == IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)) {
+        addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), addr.port());
+    } else if addr.ip() == IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0)) {
+        addr = SocketAddr::new(IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1)),
+            addr.port());
+    }
+    let _ = TcpStream::connect(addr);
+}
+
+/// Returns a config with default values that listens on the loopback
+/// address with a port chosen by the OS. Only "admin" with password "admin"
+/// may log in.
+#[cfg(test)]
+fn test_config() -> Config {
+    Config {
+        worker_threads: 4,
+        shutdown_timeout: 1,
+        query_log_length: 100,
+        users: vec![auth::Credentials {
+            username: "admin".into(),
+            password_hash: auth::stored_hash("admin")
+        }],
+        query_cache_size: 10,
+        parse_cache_size: 10,
+        .. Config::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 0, "data".into())
+    }
+}
+
+/// A server that runs in a thread of its own during a test. It is shut down
+/// when it goes out of scope.
+#[cfg(test)]
+struct TestServer {
+    server: Arc<Server>,
+    runner: Option<thread::JoinHandle<()>>,
+}
+
+#[cfg(test)]
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.server.shutdown();
+        if let Some(runner) = self.runner.take() {
+            let _ = runner.join();
+        }
+    }
+}
+
+/// Binds and runs a server, returns it with the address it listens on
+#[cfg(test)]
+fn start_test_server(config: Config) -> (TestServer, SocketAddr) {
+    let server = Arc::new(Server::bind(config).unwrap());
+    let addr = server.local_addr().unwrap();
+    let runner = {
+        let server = server.clone();
+        thread::spawn(move || server.run())
+    };
+    (TestServer { server: server, runner: Some(runner) }, addr)
+}
+
+#[test]
+fn test_metrics_endpoint() {
+    use std::io::{Read, Write};
+    use net::types::{PkgType, Command};
+
+    // the value of a metric in the text of a scrape
+    fn scrape(addr: SocketAddr, name: &str) -> usize {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.0 200 OK\r\n"), "{}", response);
+        let prefix = format!("{} ", name);
+        let line = response.lines().find(|l| l.starts_with(&prefix)).unwrap();
+        line[prefix.len()..].parse().unwrap()
+    }
+
+    let config = Config { metrics_port: Some(0), .. test_config() };
+    let (server, addr) = start_test_server(config);
+    let metrics = server.server.metrics_addr().unwrap();
+    assert_eq!(scrape(metrics, "uosql_queries_total"), 0);
+
+    let mut stream = test_login(addr);
+    assert!(test_command(&mut stream, Command::Query("selec".into())) == PkgType::Error);
+    assert_eq!(scrape(metrics, "uosql_queries_total"), 1);
+    assert_eq!(scrape(metrics, "uosql_query_errors_total"), 1);
+    assert_eq!(scrape(metrics, "uosql_connections_accepted_total"), 1);
+    assert_eq!(scrape(metrics, "uosql_connections_open"), 1);
+    assert!(scrape(metrics, "uosql_received_bytes_total") > 0);
+    assert!(scrape(metrics, "uosql_sent_bytes_total") > 0);
+
+    // other paths are unknown
+    let mut stream = TcpStream::connect(metrics).unwrap();
+    stream.write_all(b"GET / HTTP/1.0\r\n\r\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.0 404 Not Found\r\n"));
+
+    // the request line is cut off after 8192 bytes
+    let mut stream = TcpStream::connect(metrics).unwrap();
+    let mut request = b"GET /metrics".to_vec();
+    request.resize(8192, b'a');
+    stream.write_all(&request).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.0 400 Bad Request\r\n"), "{}", response);
+    // the endpoint still answers afterwards
+    assert_eq!(scrape(metrics, "uosql_connections_open"), 1);
+}
+
+#[test]
+fn test_render_metrics() {
+    let stats = conn::ServerStats::new(0, Arc::new(AtomicUsize::new(2)));
+    stats.queries.fetch_add(5, Ordering::SeqCst);
+    let text = metrics::render(&stats);
+    assert!(text.contains("# TYPE uosql_queries_total counter\nuosql_queries_total 5\n"));
+    assert!(text.contains("# TYPE uosql_connections_open gauge\nuosql_connections_open 2\n"));
+}
+
+#[test]
+fn test_listen_ipv6() {
+    use std::str::FromStr;
+    use bincode::rustc_serialize::decode_from;
+    use bincode::SizeLimit;
+    use net::types::PkgType;
+
+    let config = Config { address: IpAddr::from_str("::1").unwrap(), .. test_config() };
+    let (_server, addr) = start_test_server(config);
+    assert!(addr.ip() == IpAddr::from_str("::1").unwrap());
+
+    // the server greets every new connection
+    let mut stream = TcpStream::connect(addr).unwrap();
+    let pkg: PkgType = decode_from(&mut stream, SizeLimit::Bounded(1024)).unwrap();
+    assert!(pkg == PkgType::Greet);
+}
+
+#[test]
+fn test_max_connections() {
+    use bincode::rustc_serialize::{decode_from, encode_into};
+    use bincode::SizeLimit;
+    use net::types::{PkgType, ClientErrMsg, Greeting, Login};
+
+    // the connections are limited by the workers, not only by max_connections
+    let config = Config {
+        max_connections: 100,
+        worker_threads: 3,
+        .. test_config()
+    };
+    let (_server, addr) = start_test_server(config);
+
+    // the first three connections log in and stay open
+    let open: Vec<_> = (0..3).map(|_| test_login(addr)).collect();
+
+    // the login of the fourth one is rejected
+    let mut stream = TcpStream::connect(addr).unwrap();
+    let pkg: PkgType = decode_from(&mut stream, SizeLimit::Bounded(1024)).unwrap();
+    assert!(pkg == PkgType::Greet);
+    let greet: Greeting = decode_from(&mut stream, SizeLimit::Bounded(1024)).unwrap();
+    let login = Login {
+        username: "admin".into(),
+        password_hash: auth::hash_password(&greet.salt, "admin"),
+        compression: false
+    };
+    encode_into(&PkgType::Login, &mut stream, SizeLimit::Bounded(1024)).unwrap();
+    encode_into(&login, &mut stream, SizeLimit::Bounded(1024)).unwrap();
+    let pkg: PkgType = decode_from(&mut stream, SizeLimit::Bounded(1024)).unwrap();
+    assert!(pkg == PkgType::Error);
+    let err: ClientErrMsg = decode_from(&mut stream, SizeLimit::Bounded(1024)).unwrap();
+    assert_eq!(err.msg, "too many connections");
+    drop(open);
+}
+
+#[test]
+fn test_cancel_beyond_limit() {
+    use bincode::rustc_serialize::{decode_from, encode_into};
+    use bincode::SizeLimit;
+    use net::types::{PkgType, Command, ClientErrMsg, Greeting};
+
+    let config = Config {
+        worker_threads: 1,
+        .. test_config()
+    };
+    let (_server, addr) = start_test_server(config);
+
+    // the only worker is busy with this connection
+    let (stream, greet) = test_login_greeting(addr);
+
+    // a cancel request needs no worker, but the key of the connection
+    let cancel = |key: u64| {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let pkg: PkgType = decode_from(&mut stream, SizeLimit::Bounded(1024)).unwrap();
+        assert!(pkg == PkgType::Greet);
+        let _: Greeting = decode_from(&mut stream, SizeLimit::Bounded(1024)).unwrap();
+        encode_into(&PkgType::Command, &mut stream, SizeLimit::Bounded(1024)).unwrap();
+        encode_into(&Command::Cancel(greet.connection_id, key), &mut stream,
+            SizeLimit::Bounded(1024)).unwrap();
+        let pkg: PkgType = decode_from(&mut stream, SizeLimit::Bounded(1024)).unwrap();
+        if pkg == PkgType::Error {
+            let err: ClientErrMsg = decode_from(&mut stream, SizeLimit::Bounded(1024)).unwrap();
+            assert_eq!(err.msg, "unknown connection");
+        }
+        pkg
+    };
+    assert!(cancel(greet.cancel_key.wrapping_add(1)) == PkgType::Error);
+    assert!(cancel(greet.cancel_key) == PkgType::Ok);
+    drop(stream);
+}
+
+#[test]
+fn test_shutdown() {
+    use bincode::rustc_serialize::decode_from;
+    use bincode::SizeLimit;
+    use net::types::PkgType;
+
+    let (server, addr) = start_test_server(test_config());
+
+    // an idle connection is closed after the shutdown timeout
+    let mut stream = TcpStream::connect(addr).unwrap();
+    let pkg: PkgType = decode_from(&mut stream, SizeLimit::Bounded(1024)).unwrap();
+    assert!(pkg == PkgType::Greet);
+    drop(server);
+
+    // the listener is closed, nobody accepts connections anymore
+    assert!(TcpStream::connect(addr).is_err());
+}
+
+#[test]
+fn test_client_disconnect() {
+    let config = Config {
+        shutdown_timeout: 10,
+        .. test_config()
+    };
+    let (server, addr) = start_test_server(config);
+
+    // the connection is closed without `Command::Quit`, its worker returns
+    // and the server stops long before the shutdown timeout
+    drop(test_login(addr));
+    let start = Instant::now();
+    drop(server);
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+/// Connects to the server and logs in as "admin" with password "admin"
+#[cfg(test)]
+fn test_login(addr: SocketAddr) -> TcpStream {
+    test_login_greeting(addr).0
+}
+
+/// Like `test_login`, but also returns the greeting of the server
+#[cfg(test)]
+fn test_login_greeting(addr: SocketAddr) -> (TcpStream, net::types::Greeting) {
+    use bincode::rustc_serialize::{decode_from, encode_into};
+    use bincode::SizeLimit;
+    use net::types::{PkgType, Greeting, Login};
+
+    let mut stream = TcpStream::connect(addr).unwrap();
+    let pkg: PkgType = decode_from(&mut stream, SizeLimit::Bounded(1024)).unwrap();
+    assert!(pkg == PkgType::Greet);
+    let greet: Greeting = decode_from(&mut stream, SizeLimit::Bounded(1024)).unwrap();
+    let login = Login {
+        username: "admin".into(),
+        password_hash: auth::hash_password(&greet.salt, "admin"),
+        compression: false
+    };
+    encode_into(&PkgType::Login, &mut stream, SizeLimit::Bounded(1024)).unwrap();
+    encode_into(&login, &mut stream, SizeLimit::Bounded(1024)).unwrap();
+    let pkg: PkgType = decode_from(&mut stream, SizeLimit::Bounded(1024)).unwrap();
+    assert!(pkg == PkgType::AccGranted);
+    (stream, greet)
+}
+
+/// Sends the command and returns the type of the answer, the rest of the
+/// answer is left in the stream
+#[cfg(test)]
+fn test_command(stream: &mut TcpStream, cmd: net::types::Command) -> net::types::PkgType {
+    use bincode::rustc_serialize::{decode_from, encode_into};
+    use bincode::SizeLimit;
+    use net::types::PkgType;
+
+    encode_into(&PkgType::Command, stream, SizeLimit::Bounded(1024)).unwrap();
+    encode_into(&cmd, stream, SizeLimit::Bounded(4096)).unwrap();
+    decode_from(stream, SizeLimit::Bounded(1024)).unwrap()
+}
+
+/// Creates an empty data directory for a test
+#[cfg(test)]
+fn test_data_dir(name: &str) -> String {
+    use std::fs;
+
+    let dir = std::env::temp_dir().join(format!("uosql-{}-{}", name, rand::random::<u32>()));
+    fs::create_dir_all(&dir).unwrap();
+    dir.to_string_lossy().into_owned()
+}
+
+#[test]
+fn test_use_database() {
+    use std::fs;
+    use bincode::rustc_serialize::decode_from;
+    use bincode::SizeLimit;
+    use net::types::{PkgType, Command, ClientErrMsg};
+
+    let dir = test_data_dir("use");
+    fs::create_dir(format!("{}/one", dir)).unwrap();
+    fs::create_dir(format!("{}/two", dir)).unwrap();
+
+    let config = Config {
+        dir: dir.clone(),
+        .. test_config()
+    };
+    let (_server, addr) = start_test_server(config);
+    let mut stream = test_login(addr);
+
+    // no database is selected yet
+    let query = || Command::Query("select * from foo".into());
+    assert!(test_command(&mut stream, query()) == PkgType::Error);
+    let err: ClientErrMsg = decode_from(&mut stream, SizeLimit::Bounded(1024)).unwrap();
+    assert_eq!(err.msg, "no database selected");
+
+    assert!(test_command(&mut stream, Command::Use("three".into())) == PkgType::Error);
+    let err: ClientErrMsg = decode_from(&mut stream, SizeLimit::Bounded(1024)).unwrap();
+    assert_eq!(err.msg, "unknown database");
+
+    // the table only exists in the database it was created in
+    assert!(test_command(&mut stream, Command::Use("one".into())) == PkgType::Ok);
+    let create = Command::Query("create table foo (id int primary key)".into());
+    assert!(test_command(&mut stream, create) == PkgType::Response);
+    let _: storage::ResultSet = decode_from(&mut stream, SizeLimit::Infinite).unwrap();
+    assert!(test_command(&mut stream, query()) == PkgType::Response);
+    let _: storage::ResultSet = decode_from(&mut stream, SizeLimit::Infinite).unwrap();
+
+    assert!(test_command(&mut stream, Command::Use("two".into())) == PkgType::Ok);
+    assert!(test_command(&mut stream, query()) == PkgType::Error);
+    let _: ClientErrMsg = decode_from(&mut stream, SizeLimit::Bounded(1024)).unwrap();
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_list_tables() {
+    use std::fs;
+    use bincode::rustc_serialize::decode_from;
+    use bincode::SizeLimit;
+    use net::types::{PkgType, Command, preprocess};
+
+    let dir = test_data_dir("tables");
+    fs::create_dir(format!("{}/db", dir)).unwrap();
+
+    let config = Config {
+        dir: dir.clone(),
+        .. test_config()
+    };
+    let (_server, addr) = start_test_server(config);
+    let mut stream = test_login(addr);
+    assert!(test_command(&mut stream, Command::Use("db".into())) == PkgType::Ok);
+
+    let list = |stream: &mut TcpStream| -> Vec<String> {
+        assert!(test_command(stream, Command::ListTables) == PkgType::Response);
+        let rows: storage::ResultSet = decode_from(stream, SizeLimit::Infinite).unwrap();
+        preprocess(&rows).into_iter().map(|r| r.get_str(0).unwrap().to_string()).collect()
+    };
+
+    // an empty database has no tables
+    assert!(list(&mut stream).is_empty());
+
+    for q in &["create table foo (id int primary key)", "create table bar (id int primary key)"] {
+        assert!(test_command(&mut stream, Command::Query(q.to_string())) == PkgType::Response);
+        let _: storage::ResultSet = decode_from(&mut stream, SizeLimit::Infinite).unwrap();
+    }
+    assert_eq!(list(&mut stream), vec!["bar".to_string(), "foo".to_string()]);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_describe_table() {
+    use std::fs;
+    use bincode::rustc_serialize::decode_from;
+    use bincode::SizeLimit;
+    use net::types::{PkgType, Command, ClientErrMsg, SqlType};
+
+    let dir = test_data_dir("describe");
+    fs::create_dir(format!("{}/db", dir)).unwrap();
+
+    let config = Config {
+        dir: dir.clone(),
+        .. test_config()
+    };
+    let (_server, addr) = start_test_server(config);
+    let mut stream = test_login(addr);
+    assert!(test_command(&mut stream, Command::Use("db".into())) == PkgType::Ok);
+
+    let create = "create table foo (id int primary key, name varchar(20) not null, \
+        active bool comment 'is active')";
+    assert!(test_command(&mut stream, Command::Query(create.into())) == PkgType::Response);
+    let _: storage::ResultSet = decode_from(&mut stream, SizeLimit::Infinite).unwrap();
+
+    assert!(test_command(&mut stream, Command::Describe("foo".into())) == PkgType::Response);
+    let rows: storage::ResultSet = decode_from(&mut stream, SizeLimit::Infinite).unwrap();
+    let cols: Vec<_> = rows.columns.iter()
+        .map(|c| (&c.name[..], c.sql_type, c.is_primary_key, c.allow_null, &c.description[..]))
+        .collect();
+    assert_eq!(cols, vec![
+        ("id", SqlType::Int, true, true, ""),
+        ("name", SqlType::VarChar(20), false, false, ""),
+        ("active", SqlType::Bool, false, true, "is active"),
+    ]);
+
+    assert!(test_command(&mut stream, Command::Describe("bar".into())) == PkgType::Error);
+    let err: ClientErrMsg = decode_from(&mut stream, SizeLimit::Bounded(1024)).unwrap();
+    assert_eq!(err.msg, "unknown table");
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_batch() {
+    use std::fs;
+    use bincode::rustc_serialize::decode_from;
+    use bincode::SizeLimit;
+    use net::types::{PkgType, Command, ClientErrMsg, BatchMode, preprocess};
+
+    let dir = test_data_dir("batch");
+    fs::create_dir(format!("{}/db", dir)).unwrap();
+
+    let config = Config {
+        dir: dir.clone(),
+        .. test_config()
+    };
+    let (_server, addr) = start_test_server(config);
+    let mut stream = test_login(addr);
+    assert!(test_command(&mut stream, Command::Use("db".into())) == PkgType::Ok);
+
+    // the kinds of the answers up to the end of the batch
+    let batch = |stream: &mut TcpStream, queries: &[&str], mode| -> Vec<PkgType> {
+        let queries = queries.iter().map(|q| q.to_string()).collect();
+        let mut pkg = test_command(stream, Command::Batch(queries, mode));
+        let mut answers = vec![];
+        while pkg != PkgType::BatchEnd {
+            match pkg {
+                PkgType::Response => {
+                    let _: storage::ResultSet = decode_from(stream, SizeLimit::Infinite).unwrap();
+                },
+                PkgType::Error => {
+                    let _: ClientErrMsg = decode_from(stream, SizeLimit::Infinite).unwrap();
+                },
+                _ => panic!("unexpected package")
+            }
+            answers.push(pkg);
+            pkg = decode_from(stream, SizeLimit::Bounded(1024)).unwrap();
+        }
+        answers
+    };
+    // the second query of both batches has a syntax error
+    let queries = ["create table a (id int)", "create tabel b (id int)", "create table c (id int)"];
+
+    // the third query is not executed
+    assert!(batch(&mut stream, &queries, BatchMode::StopOnError)
+        == vec![PkgType::Response, PkgType::Error]);
+    assert!(test_command(&mut stream, Command::ListTables) == PkgType::Response);
+    let rows: storage::ResultSet = decode_from(&mut stream, SizeLimit::Infinite).unwrap();
+    assert_eq!(preprocess(&rows).into_iter().count(), 1);
+
+    // the third query is executed anyway
+    let queries = ["create table d (id int)", "create tabel e (id int)", "create table f (id int)"];
+    assert!(batch(&mut stream, &queries, BatchMode::CollectAll)
+        == vec![PkgType::Response, PkgType::Error, PkgType::Response]);
+    assert!(test_command(&mut stream, Command::ListTables) == PkgType::Response);
+    let rows: storage::ResultSet = decode_from(&mut stream, SizeLimit::Infinite).unwrap();
+    assert_eq!(preprocess(&rows).into_iter().count(), 3);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_error_codes() {
+    use std::fs;
+    use bincode::rustc_serialize::decode_from;
+    use bincode::SizeLimit;
+    use net::types::{PkgType, Command, ClientErrMsg, ErrorCode};
+
+    let dir = test_data_dir("codes");
+    fs::create_dir(format!("{}/db", dir)).unwrap();
+
+    let config = Config {
+        dir: dir.clone(),
+        .. test_config()
+    };
+    let (_server, addr) = start_test_server(config);
+    let mut stream = test_login(addr);
+    assert!(test_command(&mut stream, Command::Use("db".into())) == PkgType::Ok);
+
+    let mut code = |q: &str| -> ErrorCode {
+        assert!(test_command(&mut stream, Command::Query(q.into())) == PkgType::Error);
+        let err: ClientErrMsg = decode_from(&mut stream, SizeLimit::Bounded(1024)).unwrap();
+        err.code()
+    };
+    assert_eq!(code("select * from missing"), ErrorCode::UnknownTable);
+    assert_eq!(code("selec * from missing"), ErrorCode::Parse);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_tables_survive_restart() {
+    use std::fs::{self, File};
+    use std::io::Write;
+    use bincode::rustc_serialize::decode_from;
+    use bincode::SizeLimit;
+    use net::types::{PkgType, Command};
+
+    let dir = test_data_dir("restart");
+    fs::create_dir(format!("{}/db", dir)).unwrap();
+    let config = || Config {
+        dir: dir.clone(),
+        shutdown_timeout: 0,
+        .. test_config()
+    };
+    let start = || {
+        let (server, addr) = start_test_server(config());
+        let mut stream = test_login(addr);
+        assert!(test_command(&mut stream, Command::Use("db".into())) == PkgType::Ok);
+        (server, stream)
+    };
+
+    let (server, mut stream) = start();
+    let create = "create table foo (id int primary key, name varchar(20))";
+    assert!(test_command(&mut stream, Command::Query(create.into())) == PkgType::Response);
+    let _: storage::ResultSet = decode_from(&mut stream, SizeLimit::Infinite).unwrap();
+    drop(stream);
+    drop(server);
+
+    // a corrupt schema file does not keep the server from starting
+    File::create(format!("{}/db/broken.tbl", dir)).unwrap().write_all(b"garbage").unwrap();
+
+    let (server, mut stream) = start();
+    assert!(test_command(&mut stream, Command::Describe("foo".into())) == PkgType::Response);
+    let rows: storage::ResultSet = decode_from(&mut stream, SizeLimit::Infinite).unwrap();
+    let names: Vec<_> = rows.columns.iter().map(|c| &c.name[..]).collect();
+    assert_eq!(names, vec!["id", "name"]);
+    assert!(test_command(&mut stream, Command::Describe("broken".into())) == PkgType::Error);
+    let _: net::types::ClientErrMsg = decode_from(&mut stream, SizeLimit::Bounded(1024)).unwrap();
+    drop(stream);
+    drop(server);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_rollback() {
+    use std::fs;
+    use bincode::rustc_serialize::decode_from;
+    use bincode::SizeLimit;
+    use net::types::{PkgType, Command};
+
+    let dir = test_data_dir("rollback");
+    fs::create_dir(format!("{}/db", dir)).unwrap();
+
+    let config = Config {
+        dir: dir.clone(),
+        .. test_config()
+    };
+    let (_server, addr) = start_test_server(config);
+    let mut stream = test_login(addr);
+    assert!(test_command(&mut stream, Command::Use("db".into())) == PkgType::Ok);
+
+    let query = |stream: &mut TcpStream, q: &str| {
+        let pkg = test_command(stream, Command::Query(q.into()));
+        match pkg {
+            PkgType::Response => {
+                let _: storage::ResultSet = decode_from(stream, SizeLimit::Infinite).unwrap();
             },
+            PkgType::AffectedRows => {
+                let _: u64 = decode_from(stream, SizeLimit::Infinite).unwrap();
+            },
+            _ => { },
         }
+        pkg
+    };
+    let rows = |stream: &mut TcpStream| {
+        let select = Command::Query("select * from foo".into());
+        assert!(test_command(stream, select) == PkgType::Response);
+        let rows: storage::ResultSet = decode_from(stream, SizeLimit::Infinite).unwrap();
+        rows.data.len()
+    };
+    assert!(query(&mut stream, "create table foo (id int primary key)") == PkgType::Response);
+
+    // neither the inserted row nor the new table survive the rollback
+    assert!(test_command(&mut stream, Command::Begin) == PkgType::Ok);
+    assert!(query(&mut stream, "insert into foo values (1)") == PkgType::AffectedRows);
+    assert!(query(&mut stream, "create table bar (id int)") == PkgType::Response);
+    assert!(rows(&mut stream) > 0);
+    assert!(test_command(&mut stream, Command::Rollback) == PkgType::Ok);
+    assert_eq!(rows(&mut stream), 0);
+    assert!(test_command(&mut stream, Command::Describe("bar".into())) == PkgType::Error);
+    let _: net::types::ClientErrMsg = decode_from(&mut stream, SizeLimit::Bounded(1024)).unwrap();
+
+    // committed changes stay
+    assert!(test_command(&mut stream, Command::Begin) == PkgType::Ok);
+    assert!(query(&mut stream, "insert into foo values (1)") == PkgType::AffectedRows);
+    assert!(test_command(&mut stream, Command::Commit) == PkgType::Ok);
+    assert!(rows(&mut stream) > 0);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_affected_rows() {
+    use std::fs;
+    use bincode::rustc_serialize::decode_from;
+    use bincode::SizeLimit;
+    use net::types::{PkgType, Command, preprocess};
+
+    let dir = test_data_dir("affected");
+    fs::create_dir(format!("{}/db", dir)).unwrap();
+    let (_server, addr) = start_test_server(Config { dir: dir.clone(), .. test_config() });
+    let mut stream = test_login(addr);
+    assert!(test_command(&mut stream, Command::Use("db".into())) == PkgType::Ok);
+
+    let affected = |stream: &mut TcpStream, q: &str| -> u64 {
+        assert!(test_command(stream, Command::Query(q.into())) == PkgType::AffectedRows);
+        decode_from(stream, SizeLimit::Bounded(1024)).unwrap()
+    };
+    let create = Command::Query("create table foo (id int primary key, name varchar(10))".into());
+    assert!(test_command(&mut stream, create) == PkgType::Response);
+    let _: storage::ResultSet = decode_from(&mut stream, SizeLimit::Infinite).unwrap();
+    for id in 1..4 {
+        let insert = format!("insert into foo values ({}, 'a')", id);
+        assert_eq!(affected(&mut stream, &insert), 1);
     }
+
+    assert_eq!(affected(&mut stream, "update foo set name = 'b' where id > 1"), 2);
+    assert_eq!(affected(&mut stream, "update foo set name = 'c' where id = 7"), 0);
+    assert_eq!(affected(&mut stream, "delete from foo where id = 3"), 1);
+
+    let select = Command::Query("select * from foo".into());
+    assert!(test_command(&mut stream, select) == PkgType::Response);
+    let rows: storage::ResultSet = decode_from(&mut stream, SizeLimit::Infinite).unwrap();
+    let mut rows: Vec<_> = preprocess(&rows).into_iter()
+        .map(|r| (r.get_i64(0).unwrap(), r.get_str(1).unwrap().to_string()))
+        .collect();
+    rows.sort();
+    assert_eq!(rows, vec![(1, "a".to_string()), (2, "b".to_string())]);
+
+    assert_eq!(affected(&mut stream, "update foo set name = 'd'"), 2);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_insert_null() {
+    use std::fs;
+    use bincode::rustc_serialize::decode_from;
+    use bincode::SizeLimit;
+    use net::types::{PkgType, Command, ClientErrMsg};
+
+    let dir = test_data_dir("null");
+    fs::create_dir(format!("{}/db", dir)).unwrap();
+    let (_server, addr) = start_test_server(Config { dir: dir.clone(), .. test_config() });
+    let mut stream = test_login(addr);
+    assert!(test_command(&mut stream, Command::Use("db".into())) == PkgType::Ok);
+
+    let create = "create table foo (id int primary key, name varchar(10) not null)";
+    assert!(test_command(&mut stream, Command::Query(create.into())) == PkgType::Response);
+    let _: storage::ResultSet = decode_from(&mut stream, SizeLimit::Infinite).unwrap();
+
+    let insert = Command::Query("insert into foo values (1, null)".into());
+    assert!(test_command(&mut stream, insert) == PkgType::Error);
+    let err: ClientErrMsg = decode_from(&mut stream, SizeLimit::Bounded(1024)).unwrap();
+    assert_eq!(err.msg, "constraint violated: column `name` must not be null");
+
+    // the string 'NULL' is no null value
+    let insert = Command::Query("insert into foo values (1, 'NULL')".into());
+    assert!(test_command(&mut stream, insert) == PkgType::AffectedRows);
+    let affected: u64 = decode_from(&mut stream, SizeLimit::Bounded(1024)).unwrap();
+    assert_eq!(affected, 1);
+
+    // nothing was stored by the failed insert
+    let select = Command::Query("select * from foo".into());
+    assert!(test_command(&mut stream, select) == PkgType::Response);
+    let rows: storage::ResultSet = decode_from(&mut stream, SizeLimit::Infinite).unwrap();
+    let rows: Vec<_> = net::types::preprocess(&rows).into_iter()
+        .map(|r| (r.get_i64(0).unwrap(), r.get_str(1).unwrap().to_string()))
+        .collect();
+    assert_eq!(rows, vec![(1, "NULL".to_string())]);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_store_null() {
+    use std::fs;
+    use bincode::rustc_serialize::decode_from;
+    use bincode::SizeLimit;
+    use net::types::{PkgType, Command, CellError, preprocess};
+
+    let dir = test_data_dir("store-null");
+    fs::create_dir(format!("{}/db", dir)).unwrap();
+    let (_server, addr) = start_test_server(Config { dir: dir.clone(), .. test_config() });
+    let mut stream = test_login(addr);
+    assert!(test_command(&mut stream, Command::Use("db".into())) == PkgType::Ok);
+
+    let create = "create table foo (id int primary key not null, name varchar(10), n int)";
+    assert!(test_command(&mut stream, Command::Query(create.into())) == PkgType::Response);
+    let _: storage::ResultSet = decode_from(&mut stream, SizeLimit::Infinite).unwrap();
+    for insert in &["insert into foo values (1, null, 5)",
+        "insert into foo values (2, 'b', null)"]
+    {
+        assert!(test_command(&mut stream, Command::Query(insert.to_string()))
+            == PkgType::AffectedRows);
+        let affected: u64 = decode_from(&mut stream, SizeLimit::Bounded(1024)).unwrap();
+        assert_eq!(affected, 1);
+    }
+
+    let select = Command::Query("select * from foo".into());
+    assert!(test_command(&mut stream, select) == PkgType::Response);
+    let set = preprocess(&decode_from(&mut stream, SizeLimit::Infinite).unwrap());
+    assert_eq!(set.row_count(), 2);
+    assert_eq!(set.is_null(0, 1), Ok(true));
+    assert_eq!(set.get_str(0, 1), Err(CellError::Null));
+    assert_eq!(set.get_i64(0, 2), Ok(5));
+    assert_eq!(set.get_str(1, 1), Ok("b"));
+    assert_eq!(set.is_null(1, 2), Ok(true));
+    assert_eq!(set.to_json(),
+        "[{\"id\":1,\"n\":5,\"name\":null},{\"id\":2,\"n\":null,\"name\":\"b\"}]");
+    let mut csv = Vec::new();
+    set.to_csv(&mut csv).unwrap();
+    assert_eq!(String::from_utf8(csv).unwrap(),
+        "id,name,n\r\n1,,5\r\n2,b,\r\n");
+    let rows: Vec<_> = set.into_iter().map(|r| (r.is_null(1), r.get_str(1).map(String::from)))
+        .collect();
+    assert_eq!(rows, vec![(Some(true), None), (Some(false), Some("b".to_string()))]);
+
+    // null matches no comparison, and can be written by an update
+    let select = Command::Query("select id from foo where n < 10".into());
+    assert!(test_command(&mut stream, select) == PkgType::Response);
+    let set = preprocess(&decode_from(&mut stream, SizeLimit::Infinite).unwrap());
+    assert_eq!((set.row_count(), set.get_i64(0, 0)), (1, Ok(1)));
+    let update = Command::Query("update foo set name = null where id = 2".into());
+    assert!(test_command(&mut stream, update) == PkgType::AffectedRows);
+    let affected: u64 = decode_from(&mut stream, SizeLimit::Bounded(1024)).unwrap();
+    assert_eq!(affected, 1);
+    let select = Command::Query("select name from foo where id = 2".into());
+    assert!(test_command(&mut stream, select) == PkgType::Response);
+    let set = preprocess(&decode_from(&mut stream, SizeLimit::Infinite).unwrap());
+    assert_eq!(set.is_null(0, 0), Ok(true));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_query_cache() {
+    use std::fs;
+    use bincode::rustc_serialize::decode_from;
+    use bincode::SizeLimit;
+    use net::types::{PkgType, Command, preprocess};
+
+    let dir = test_data_dir("cache");
+    fs::create_dir(format!("{}/db", dir)).unwrap();
+    let (server, addr) = start_test_server(Config { dir: dir.clone(), .. test_config() });
+    let mut stream = test_login(addr);
+    assert!(test_command(&mut stream, Command::Use("db".into())) == PkgType::Ok);
+
+    let mut run = |q: &str| -> Vec<i64> {
+        match test_command(&mut stream, Command::Query(q.into())) {
+            PkgType::Response => {
+                let rows: storage::ResultSet = decode_from(&mut stream, SizeLimit::Infinite)
+                    .unwrap();
+                preprocess(&rows).into_iter().map(|r| r.get_i64(0).unwrap()).collect()
+            },
+            PkgType::AffectedRows => {
+                let _: u64 = decode_from(&mut stream, SizeLimit::Bounded(1024)).unwrap();
+                vec![]
+            },
+            _ => panic!("query '{}' failed", q),
+        }
+    };
+    let cache = server.server.query_cache();
+    run("create table foo (id int primary key)");
+    run("insert into foo values (1)");
+
+    assert_eq!(run("select id from foo"), vec![1]);
+    assert_eq!(cache.hits(), 0);
+    assert_eq!(run("select id from foo"), vec![1]);
+    assert_eq!(cache.hits(), 1);
+    // the same query after normalization
+    assert_eq!(run("SELECT   id FROM foo -- again"), vec![1]);
+    assert_eq!(cache.hits(), 2);
+
+    // a write evicts the results of the table
+    run("insert into foo values (2)");
+    let mut ids = run("select id from foo");
+    ids.sort();
+    assert_eq!(ids, vec![1, 2]);
+    assert_eq!(cache.hits(), 2);
+    run("select id from foo");
+    assert_eq!(cache.hits(), 3);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_parse_cache() {
+    use net::types::{PkgType, Command};
+
+    let (server, addr) = start_test_server(test_config());
+    let cache = server.server.parse_cache();
+
+    // both connections send the same query, it is parsed once
+    for _ in 0..2 {
+        let mut stream = test_login(addr);
+        let select = Command::Query("select * from foo".into());
+        assert!(test_command(&mut stream, select) == PkgType::Error);
+        let _: net::types::ClientErrMsg = bincode::rustc_serialize::decode_from(&mut stream,
+            bincode::SizeLimit::Bounded(1024)).unwrap();
+    }
+    assert_eq!(cache.parsed(), 1);
+    assert_eq!(cache.hits(), 1);
+}
+
+#[test]
+fn test_disconnect_mid_query() {
+    use std::fs;
+    use std::io::Write;
+    use bincode::rustc_serialize::{decode_from, encode, encode_into};
+    use bincode::SizeLimit;
+    use net::types::{PkgType, Command, ServerInfo};
+
+    let dir = test_data_dir("disconnect");
+    fs::create_dir(format!("{}/db", dir)).unwrap();
+    let (_server, addr) = start_test_server(Config { dir: dir.clone(), .. test_config() });
+    let mut stream = test_login(addr);
+    assert!(test_command(&mut stream, Command::Use("db".into())) == PkgType::Ok);
+
+    let create = Command::Query("create table foo (id int primary key, name char(200))".into());
+    assert!(test_command(&mut stream, create) == PkgType::Response);
+    let _: storage::ResultSet = decode_from(&mut stream, SizeLimit::Infinite).unwrap();
+    for id in 1..11 {
+        let insert = Command::Query(format!("insert into foo values ({}, 'a')", id));
+        assert!(test_command(&mut stream, insert) == PkgType::AffectedRows);
+        let _: u64 = decode_from(&mut stream, SizeLimit::Bounded(1024)).unwrap();
+    }
+
+    // a client that closes the socket in the middle of a command
+    let mut partial = test_login(addr);
+    let cmd = encode(&Command::Query("select * from foo".into()), SizeLimit::Infinite).unwrap();
+    encode_into(&PkgType::Command, &mut partial, SizeLimit::Bounded(1024)).unwrap();
+    partial.write_all(&cmd[..cmd.len() / 2]).unwrap();
+    drop(partial);
+
+    // a client that writes in a transaction and leaves while its query runs
+    let mut leaving = test_login(addr);
+    assert!(test_command(&mut leaving, Command::Use("db".into())) == PkgType::Ok);
+    assert!(test_command(&mut leaving, Command::Begin) == PkgType::Ok);
+    let insert = Command::Query("insert into foo values (0, 'b')".into());
+    assert!(test_command(&mut leaving, insert) == PkgType::AffectedRows);
+    let _: u64 = decode_from(&mut leaving, SizeLimit::Bounded(1024)).unwrap();
+    let select = Command::Query("select * from foo".into());
+    encode_into(&PkgType::Command, &mut leaving, SizeLimit::Bounded(1024)).unwrap();
+    encode_into(&select, &mut leaving, SizeLimit::Bounded(4096)).unwrap();
+    drop(leaving);
+
+    // both connections are cleaned up, only the first one stays open
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        assert!(test_command(&mut stream, Command::ServerInfo) == PkgType::ServerInfo);
+        let info: ServerInfo = decode_from(&mut stream, SizeLimit::Bounded(1024)).unwrap();
+        if info.connections == 1 {
+            break
+        }
+        assert!(Instant::now() < deadline, "{} connections still open", info.connections);
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    // and the transaction of the client that left is rolled back
+    let select = Command::Query("select * from foo where id = 0".into());
+    assert!(test_command(&mut stream, select) == PkgType::Response);
+    let rows: storage::ResultSet = decode_from(&mut stream, SizeLimit::Infinite).unwrap();
+    assert!(net::types::preprocess(&rows).data_empty());
+    let mut other = test_login(addr);
+    assert!(test_command(&mut other, Command::Ping) == PkgType::Ok);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_rename_table() {
+    use std::fs;
+    use bincode::rustc_serialize::decode_from;
+    use bincode::SizeLimit;
+    use net::types::{PkgType, Command, ClientErrMsg, preprocess};
+
+    let dir = test_data_dir("rename");
+    fs::create_dir(format!("{}/db", dir)).unwrap();
+    let (_server, addr) = start_test_server(Config { dir: dir.clone(), .. test_config() });
+    let mut stream = test_login(addr);
+    assert!(test_command(&mut stream, Command::Use("db".into())) == PkgType::Ok);
+
+    let query = |stream: &mut TcpStream, q: &str| test_command(stream, Command::Query(q.into()));
+    for q in &["create table foo (id int primary key, name varchar(10))",
+        "alter table foo rename column name to title, rename to bar"]
+    {
+        assert!(query(&mut stream, q) == PkgType::Response);
+        let _: storage::ResultSet = decode_from(&mut stream, SizeLimit::Infinite).unwrap();
+    }
+    assert!(query(&mut stream, "insert into bar values (1, 'a')") == PkgType::AffectedRows);
+    let _: u64 = decode_from(&mut stream, SizeLimit::Bounded(1024)).unwrap();
+
+    assert!(query(&mut stream, "select title from bar") == PkgType::Response);
+    let rows: storage::ResultSet = decode_from(&mut stream, SizeLimit::Infinite).unwrap();
+    assert_eq!(rows.columns[0].name, "bar.title");
+    assert_eq!(preprocess(&rows).get_str(0, 0).unwrap(), "a");
+    assert!(query(&mut stream, "select * from foo") == PkgType::Error);
+    let err: ClientErrMsg = decode_from(&mut stream, SizeLimit::Bounded(1024)).unwrap();
+    assert_eq!(err.msg, "unknown table");
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_float_columns() {
+    use std::fs;
+    use bincode::rustc_serialize::decode_from;
+    use bincode::SizeLimit;
+    use net::types::{PkgType, Command, preprocess};
+
+    let dir = test_data_dir("float");
+    fs::create_dir(format!("{}/db", dir)).unwrap();
+    let (_server, addr) = start_test_server(Config { dir: dir.clone(), .. test_config() });
+    let mut stream = test_login(addr);
+    assert!(test_command(&mut stream, Command::Use("db".into())) == PkgType::Ok);
+
+    let query = |stream: &mut TcpStream, q: &str| test_command(stream, Command::Query(q.into()));
+    let create = "create table items (id int primary key, price double, weight float)";
+    assert!(query(&mut stream, create) == PkgType::Response);
+    let _: storage::ResultSet = decode_from(&mut stream, SizeLimit::Infinite).unwrap();
+    for q in &["insert into items values (1, 3.14, 0.5)", "insert into items values (2, 10, 2)"] {
+        assert!(query(&mut stream, q) == PkgType::AffectedRows);
+        let _: u64 = decode_from(&mut stream, SizeLimit::Bounded(1024)).unwrap();
+    }
+
+    assert!(query(&mut stream, "select * from items where price > 9.99") == PkgType::Response);
+    let rows = preprocess(&decode_from(&mut stream, SizeLimit::Infinite).unwrap());
+    assert_eq!(rows.get_i64(0, 0), Ok(2));
+    assert_eq!(rows.get_f64(0, 1), Ok(10.0));
+    assert_eq!(rows.get_f64(0, 2), Ok(2.0));
+    assert!(rows.get_f64(1, 1).is_err());
+    assert!(query(&mut stream, "select * from items where price = 3.14") == PkgType::Response);
+    let rows = preprocess(&decode_from(&mut stream, SizeLimit::Infinite).unwrap());
+    assert_eq!(rows.get_i64(0, 0), Ok(1));
+    assert_eq!(rows.get_f64(0, 2), Ok(0.5));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_date_columns() {
+    use std::fs;
+    use bincode::rustc_serialize::decode_from;
+    use bincode::SizeLimit;
+    use net::types::{PkgType, Command, preprocess};
+
+    let dir = test_data_dir("date");
+    fs::create_dir(format!("{}/db", dir)).unwrap();
+    let (_server, addr) = start_test_server(Config { dir: dir.clone(), .. test_config() });
+    let mut stream = test_login(addr);
+    assert!(test_command(&mut stream, Command::Use("db".into())) == PkgType::Ok);
+
+    let query = |stream: &mut TcpStream, q: &str| test_command(stream, Command::Query(q.into()));
+    assert!(query(&mut stream, "create table log (id int primary key, day date, at timestamp)")
+        == PkgType::Response);
+    let _: storage::ResultSet = decode_from(&mut stream, SizeLimit::Infinite).unwrap();
+    for q in &["insert into log values (1, date '1969-12-31', timestamp '1969-12-31 23:00:00')",
+        "insert into log values (2, date '2024-01-31', timestamp '2024-01-31 10:00:00')"]
+    {
+        assert!(query(&mut stream, q) == PkgType::AffectedRows);
+        let _: u64 = decode_from(&mut stream, SizeLimit::Bounded(1024)).unwrap();
+    }
+
+    assert!(query(&mut stream, "select * from log where day > date '1970-01-01'")
+        == PkgType::Response);
+    let rows = preprocess(&decode_from(&mut stream, SizeLimit::Infinite).unwrap());
+    assert_eq!(rows.get_i64(0, 0), Ok(2));
+    assert_eq!(rows.get_datetime(0, 1).unwrap(), "2024-01-31");
+    assert_eq!(rows.get_datetime(0, 2).unwrap(), "2024-01-31 10:00:00");
+    assert!(rows.get_datetime(1, 1).is_err());
+    assert!(query(&mut stream, "select * from log where at < timestamp '1970-01-01 00:00:00'")
+        == PkgType::Response);
+    let rows = preprocess(&decode_from(&mut stream, SizeLimit::Infinite).unwrap());
+    assert_eq!(rows.get_datetime(0, 1).unwrap(), "1969-12-31");
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_inner_join() {
+    use std::fs;
+    use bincode::rustc_serialize::decode_from;
+    use bincode::SizeLimit;
+    use net::types::{PkgType, Command, preprocess};
+
+    let dir = test_data_dir("join");
+    fs::create_dir(format!("{}/db", dir)).unwrap();
+    let (_server, addr) = start_test_server(Config { dir: dir.clone(), .. test_config() });
+    let mut stream = test_login(addr);
+    assert!(test_command(&mut stream, Command::Use("db".into())) == PkgType::Ok);
+
+    let query = |stream: &mut TcpStream, q: &str| test_command(stream, Command::Query(q.into()));
+    for create in &["create table users (id int primary key, name varchar(10))",
+                    "create table orders (id int primary key, uid int)"] {
+        assert!(query(&mut stream, create) == PkgType::Response);
+        let _: storage::ResultSet = decode_from(&mut stream, SizeLimit::Infinite).unwrap();
+    }
+    for q in &["insert into users values (1, 'a')", "insert into users values (2, 'b')",
+               "insert into orders values (10, 2)", "insert into orders values (11, 1)",
+               "insert into orders values (12, 2)"] {
+        assert!(query(&mut stream, q) == PkgType::AffectedRows);
+        let _: u64 = decode_from(&mut stream, SizeLimit::Bounded(1024)).unwrap();
+    }
+
+    let select = "select o.id from users u join orders o on u.id = o.uid where u.name = 'b'";
+    assert!(query(&mut stream, select) == PkgType::Response);
+    let rows = preprocess(&decode_from(&mut stream, SizeLimit::Infinite).unwrap());
+    assert_eq!(rows.row_count(), 2);
+    assert_eq!(rows.get_i64(0, 0), Ok(10));
+    assert_eq!(rows.get_i64(1, 0), Ok(12));
+
+    // a literal may be in front of the column
+    assert!(query(&mut stream, "select id from orders where 11 < id") == PkgType::Response);
+    let rows = preprocess(&decode_from(&mut stream, SizeLimit::Infinite).unwrap());
+    assert_eq!((rows.row_count(), rows.get_i64(0, 0)), (1, Ok(12)));
+
+    fs::remove_dir_all(dir).unwrap();
 }
@@ -0,0 +1,164 @@
+//! Export of the server counters in the Prometheus text format
+//!
+//! If `Config::metrics_port` is set, the server answers `GET /metrics` on
+//! that port with the counters of its `ServerStats`. The HTTP support is
+//! just enough for a scraper: one request per connection, no keep-alive.
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use conn::ServerStats;
+
+/// Seconds a scraper may take to send its request or read the answer
+const REQUEST_TIMEOUT: u64 = 3;
+
+/// Bytes of the request line and headers which are read at most
+const MAX_REQUEST_SIZE: u64 = 8192;
+
+/// Renders the counters in the Prometheus text format
+pub fn render(stats: &ServerStats) -> String {
+    let metrics = [
+        ("uosql_connections_accepted_total", "counter", "Connections accepted by the server",
+            stats.accepted.load(Ordering::SeqCst)),
+        ("uosql_connections_open", "gauge", "Connections currently open",
+            stats.open.load(Ordering::SeqCst)),
+        ("uosql_queries_total", "counter", "Queries executed, including failed ones",
+            stats.queries.load(Ordering::SeqCst)),
+        ("uosql_query_errors_total", "counter", "Queries that failed",
+            stats.errors.load(Ordering::SeqCst)),
+        ("uosql_received_bytes_total", "counter", "Bytes received from logged in clients",
+            stats.bytes_received.load(Ordering::SeqCst)),
+        ("uosql_sent_bytes_total", "counter", "Bytes sent to logged in clients",
+            stats.bytes_sent.load(Ordering::SeqCst)),
+    ];
+    let mut text = String::new();
+    for &(name, kind, help, value) in metrics.iter() {
+        text.push_str(&format!("# HELP {} {}\n# TYPE {} {}\n{} {}\n",
+            name, help, name, kind, name, value));
+    }
+    text
+}
+
+/// Reads from a stream until a deadline for all reads together is over
+struct DeadlineReader<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
+}
+
+impl<'a> Read for DeadlineReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let now = Instant::now();
+        if now >= self.deadline {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "request took too long"));
+        }
+        try!(self.stream.set_read_timeout(Some(self.deadline - now)));
+        self.stream.read(buf)
+    }
+}
+
+/// Answers one HTTP request, only `GET /metrics` is known. Requests larger
+/// than `MAX_REQUEST_SIZE` are answered with `400 Bad Request`.
+fn answer(mut stream: TcpStream, stats: &ServerStats) -> io::Result<()> {
+    try!(stream.set_write_timeout(Some(Duration::from_secs(REQUEST_TIMEOUT))));
+    let mut request = String::new();
+    let complete = {
+        let deadline = Instant::now() + Duration::from_secs(REQUEST_TIMEOUT);
+        let reader = DeadlineReader { stream: &stream, deadline: deadline };
+        // a line without line break was cut off by the size limit
+        let mut reader = BufReader::new(reader.take(MAX_REQUEST_SIZE));
+        try!(reader.read_line(&mut request));
+        let mut complete = request.ends_with('\n');
+        // the headers are not needed, but have to be read before answering
+        let mut header = String::new();
+        while complete && try!(reader.read_line(&mut header)) > 0 && header.trim() != "" {
+            complete = header.ends_with('\n');
+            header.clear();
+        }
+        complete
+    };
+    let mut parts = request.split_whitespace();
+    let (status, body) = match (complete, parts.next(), parts.next()) {
+        (false, _, _) => ("400 Bad Request", "request too large\n".to_string()),
+        (true, Some("GET"), Some("/metrics")) => ("200 OK", render(stats)),
+        _ => ("404 Not Found", "not found\n".to_string()),
+    };
+    write!(stream, "HTTP/1.0 {}\r\nContent-Type: text/plain; version=0.0.4\r\n\
+        Content-Length: {}\r\nConnection: close\r\n\r\n{}", status, body.len(), body)
+}
+
+/// Serves the metrics in a thread of its own until it is dropped
+pub struct Endpoint {
+    addr: SocketAddr,
+    stopped: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Endpoint {
+    /// Starts answering the requests of `listener`
+    pub fn start(listener: TcpListener, stats: Arc<ServerStats>) -> io::Result<Endpoint> {
+        let addr = try!(listener.local_addr());
+        let stopped = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let stopped = stopped.clone();
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if stopped.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    match stream {
+                        Ok(stream) => if let Err(e) = answer(stream, &stats) {
+                            debug!("Failed to answer metrics request: {}", e);
+                        },
+                        Err(e) => warn!("Failed to accept metrics request: {:?}", e),
+                    }
+                }
+            })
+        };
+        Ok(Endpoint { addr: addr, stopped: stopped, thread: Some(thread) })
+    }
+}
+
+impl Drop for Endpoint {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        super::wake_up(self.addr);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Counts the bytes read from and written to a stream in `ServerStats`
+pub struct CountingStream<S> {
+    inner: S,
+    stats: Arc<ServerStats>,
+}
+
+impl<S> CountingStream<S> {
+    pub fn new(inner: S, stats: Arc<ServerStats>) -> CountingStream<S> {
+        CountingStream { inner: inner, stats: stats }
+    }
+}
+
+impl<S: Read> Read for CountingStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = try!(self.inner.read(buf));
+        self.stats.bytes_received.fetch_add(n, Ordering::SeqCst);
+        Ok(n)
+    }
+}
+
+impl<S: Write> Write for CountingStream<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = try!(self.inner.write(buf));
+        self.stats.bytes_sent.fetch_add(n, Ordering::SeqCst);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -14,21 +14,38 @@
 //!
 //! ...
 //!
+pub mod stream;
 pub mod types;
 
+pub use self::stream::Stream;
+
 use std;
 use std::fmt;
 use std::io::{self, Write, Read};
+use std::sync::atomic::{AtomicBool, Ordering};
 // to encode and decode the structs to the given stream
 use bincode::rustc_serialize::{EncodingError, DecodingError, decode_from, encode_into};
 use bincode::SizeLimit;
+use flate2::Compression;
+use flate2::write::ZlibEncoder;
+use flate2::read::ZlibDecoder;
 use self::types::*;
 use storage::ResultSet;
 use parse::parser::ParseError;
 
-const PROTOCOL_VERSION: u8 = 1;
+/// Version of the encoding of all packages. Increase it with every change
+/// of a package, e.g. a new field of `Greeting` or a new `Command`.
+pub const PROTOCOL_VERSION: u8 = 9;
 const WELCOME_MSG: &'static str = "Welcome to the fabulous uoSQL database.";
 
+/// Encoded results smaller than this are never compressed, it would not save
+/// much and costs time.
+pub const COMPRESSION_THRESHOLD: usize = 1024;
+
+/// Maximum number of bytes in one batch of a streamed result. A batch holds
+/// at least one row, even if that is bigger.
+pub const STREAM_BATCH_SIZE: usize = 64 * 1024;
+
 /// Collection of possible errors while communicating with the client.
 #[derive(Debug)]
 pub enum Error {
@@ -38,6 +55,19 @@ pub enum Error {
     Encode(EncodingError),
     Decode(DecodingError),
     UnEoq(ParseError),
+    NoTransaction,
+    NestedTransaction,
+    UnknownStatement,
+    Cancelled,
+    UnknownConnection,
+    TooManyConnections,
+    UnknownDatabase,
+    NoDatabaseSelected,
+    UnknownTable,
+    Execution(String),
+    UnknownColumn,
+    ConstraintViolation(String),
+    TlsRequired,
 }
 
 /// Implement display for description of Error
@@ -56,11 +86,47 @@ impl std::error::Error for Error {
             &Error::UnknownCmd => "cannot interpret command: unknown",
             &Error::Encode(_) => "could not encode/ send package",
             &Error::Decode(_) => "could not decode/ receive package",
-            &Error::UnEoq(_) => "parsing error"
+            &Error::UnEoq(_) => "parsing error",
+            &Error::NoTransaction => "no transaction open",
+            &Error::NestedTransaction => "transaction already open",
+            &Error::UnknownStatement => "unknown prepared statement",
+            &Error::Cancelled => "query was cancelled",
+            &Error::UnknownConnection => "unknown connection",
+            &Error::TooManyConnections => "too many connections",
+            &Error::UnknownDatabase => "unknown database",
+            &Error::NoDatabaseSelected => "no database selected",
+            &Error::UnknownTable => "unknown table",
+            &Error::Execution(_) => "query execution failed",
+            &Error::UnknownColumn => "unknown column",
+            &Error::ConstraintViolation(_) => "constraint violated",
+            &Error::TlsRequired => "the server only accepts TLS connections",
         }
     }
 }
 
+impl Error {
+    /// True if the client closed the connection or it was reset
+    pub fn is_disconnect(&self) -> bool {
+        match self {
+            &Error::Io(ref e) => is_disconnect(e),
+            &Error::Encode(EncodingError::IoError(ref e)) => is_disconnect(e),
+            &Error::Decode(DecodingError::IoError(ref e)) => is_disconnect(e),
+            // bincode reports the end of the stream within a number this way
+            &Error::Decode(ref e @ DecodingError::InvalidEncoding(_)) =>
+                std::error::Error::description(e).starts_with("Unexpected EOF"),
+            _ => false,
+        }
+    }
+}
+
+fn is_disconnect(err: &io::Error) -> bool {
+    match err.kind() {
+        io::ErrorKind::UnexpectedEof | io::ErrorKind::ConnectionReset |
+        io::ErrorKind::ConnectionAborted | io::ErrorKind::BrokenPipe => true,
+        _ => false,
+    }
+}
+
 /// Implement the conversion from io::Error to NetworkError
 impl From<io::Error> for Error {
     fn from(err: io::Error) -> Error {
@@ -89,28 +155,39 @@ impl From<ParseError> for Error {
     }
 }
 
+/// The answer of a client to the greeting
+pub enum Hello {
+    /// Log in to send commands
+    Login(Login),
+    /// Cancel the query of another connection, given its id and cancel key.
+    /// The connection is closed afterwards.
+    Cancel(u64, u64),
+}
+
 /// Write a welcome-message to the given server-client-stream.
-pub fn do_handshake<W: Write + Read>(stream: &mut W)
-    -> Result<(String, String), Error>
+/// Returns the answer of the client and the salt its password was hashed
+/// with.
+pub fn do_handshake<W: Write + Read>(stream: &mut W, connection_id: u64, cancel_key: u64)
+    -> Result<(Hello, Vec<u8>), Error>
 {
-    let greet = Greeting::make_greeting(PROTOCOL_VERSION, WELCOME_MSG.into());
+    let greet = Greeting::make_greeting(PROTOCOL_VERSION, WELCOME_MSG.into(), connection_id,
+        cancel_key);
 
     // send handshake packet to client
     try!(encode_into(&PkgType::Greet, stream, SizeLimit::Bounded(1024)));
     try!(encode_into(&greet, stream, SizeLimit::Bounded(1024)));
 
     // receive login data from client
-    let login = read_login(stream);
-    match login {
-        Ok(sth) => Ok((sth.username, sth.password)),
+    let hello = read_hello(stream);
+    match hello {
+        Ok(sth) => Ok((sth, greet.salt)),
         Err(msg) => Err(msg)
     }
 }
 
-/// Read the data from the response to the handshake,
-/// username and password extracted and returned.
-pub fn read_login<R: Read + Write>(stream: &mut R)
-    -> Result<Login, Error>
+/// Read the response to the handshake: a login or a cancel request.
+pub fn read_hello<R: Read + Write>(stream: &mut R)
+    -> Result<Hello, Error>
 {
     // read package-type
     let status: PkgType = try!(decode_from(stream, SizeLimit::Bounded(1024)));
@@ -118,16 +195,28 @@ pub fn read_login<R: Read + Write>(stream: &mut R)
     match status {
         PkgType::Login =>
             // read the login data
-            decode_from(stream, SizeLimit::Bounded(1024)).map_err(|e| e.into()),
-        PkgType::Command => { // free the stream
-            let _ = decode_from::<R, Command>(stream, SizeLimit::Bounded(4096));
-            Err(Error::UnexpectedPkg)
+            decode_from(stream, SizeLimit::Bounded(1024)).map(Hello::Login)
+                .map_err(|e| e.into()),
+        PkgType::Command => match try!(decode_from(stream, SizeLimit::Bounded(4096))) {
+            Command::Cancel(id, key) => Ok(Hello::Cancel(id, key)),
+            _ => Err(Error::UnexpectedPkg)
         },
         _ =>
             Err(Error::UnexpectedPkg)
     }
 }
 
+/// Read the data from the response to the handshake,
+/// username and password hash extracted and returned.
+pub fn read_login<R: Read + Write>(stream: &mut R)
+    -> Result<Login, Error>
+{
+    match try!(read_hello(stream)) {
+        Hello::Login(login) => Ok(login),
+        Hello::Cancel(..) => Err(Error::UnexpectedPkg)
+    }
+}
+
 /// Read the sent bytes, extract the kind of command.
 pub fn read_commands<R: Read + Write>(stream: &mut R)
     -> Result<Command, Error>
@@ -173,6 +262,101 @@ pub fn send_response_package<W: Write>(mut stream: &mut W, data: ResultSet)
     Ok(())
 }
 
+/// Send Result package as response to a query. With `compress` set, results
+/// bigger than `COMPRESSION_THRESHOLD` are sent zlib compressed as
+/// `CompressedResponse`.
+pub fn send_result_package<W: Write>(mut stream: &mut W, data: ResultSet, compress: bool)
+    -> Result<(), Error>
+{
+    let mut encoded = Vec::new();
+    try!(encode_into(&data, &mut encoded, SizeLimit::Infinite));
+    if !compress || encoded.len() < COMPRESSION_THRESHOLD {
+        try!(encode_into(&PkgType::Response, stream, SizeLimit::Bounded(1024)));
+        try!(stream.write_all(&encoded));
+        return Ok(())
+    }
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::Default);
+    try!(encoder.write_all(&encoded));
+    let compressed = try!(encoder.finish());
+    try!(encode_into(&PkgType::CompressedResponse, stream, SizeLimit::Bounded(1024)));
+    try!(encode_into(&compressed, stream, SizeLimit::Infinite));
+    Ok(())
+}
+
+/// Read the body of a `CompressedResponse` package. `size` limits the
+/// compressed as well as the decompressed data.
+pub fn read_compressed_result<R: Read>(mut stream: &mut R, size: SizeLimit)
+    -> Result<ResultSet, DecodingError>
+{
+    let compressed: Vec<u8> = try!(decode_from(stream, size));
+    let mut decoder = ZlibDecoder::new(&compressed[..]);
+    decode_from(&mut decoder, size)
+}
+
+/// Send the rows in batches: first a `StreamStart` package with the
+/// columns, then `RowBatch` packages of at most `STREAM_BATCH_SIZE` bytes
+/// and a `StreamEnd` package at last. If `cancel` is set between two
+/// batches, the rest is not sent and the stream ends with a `Cancelled`
+/// error package instead.
+pub fn send_streamed_response<W: Write>(mut stream: &mut W, data: ResultSet,
+    cancel: &AtomicBool) -> Result<(), Error>
+{
+    try!(encode_into(&PkgType::StreamStart, stream, SizeLimit::Bounded(1024)));
+    try!(encode_into(&data.columns, stream, SizeLimit::Infinite));
+
+    let line_len = data.columns.iter().fold(0, |a, c| a + c.get_size() as usize);
+    if line_len > 0 {
+        let lines_per_batch = std::cmp::max(1, STREAM_BATCH_SIZE / line_len);
+        for batch in data.data.chunks(lines_per_batch * line_len) {
+            if cancel.load(Ordering::SeqCst) {
+                return send_error_package(stream, Error::Cancelled.into())
+            }
+            try!(encode_into(&PkgType::RowBatch, stream, SizeLimit::Bounded(1024)));
+            try!(encode_into(&batch, stream, SizeLimit::Infinite));
+        }
+    }
+
+    try!(encode_into(&PkgType::StreamEnd, stream, SizeLimit::Bounded(1024)));
+    Ok(())
+}
+
+/// Send the id and parameter count of a new prepared statement.
+pub fn send_prepared_package<W: Write>(mut stream: &mut W, info: PreparedInfo)
+    -> Result<(), Error>
+{
+    try!(encode_into(&PkgType::Prepared, stream, SizeLimit::Bounded(1024)));
+    try!(encode_into(&info, stream, SizeLimit::Bounded(1024)));
+    Ok(())
+}
+
+/// Send version, start time and load of the server.
+pub fn send_server_info_package<W: Write>(mut stream: &mut W, info: ServerInfo)
+    -> Result<(), Error>
+{
+    try!(encode_into(&PkgType::ServerInfo, stream, SizeLimit::Bounded(1024)));
+    try!(encode_into(&info, stream, SizeLimit::Bounded(1024)));
+    Ok(())
+}
+
+/// Send the parsed query, printed as SQL, as answer to `Command::Explain`.
+pub fn send_explain_package<W: Write>(mut stream: &mut W, text: &str)
+    -> Result<(), Error>
+{
+    try!(encode_into(&PkgType::Explain, stream, SizeLimit::Bounded(1024)));
+    try!(encode_into(&text, stream, SizeLimit::Infinite));
+    Ok(())
+}
+
+/// Send the number of rows changed by an insert, update or delete query.
+pub fn send_affected_rows_package<W: Write>(mut stream: &mut W, count: u64)
+    -> Result<(), Error>
+{
+    try!(encode_into(&PkgType::AffectedRows, stream, SizeLimit::Bounded(1024)));
+    try!(encode_into(&count, stream, SizeLimit::Bounded(1024)));
+    Ok(())
+}
+
 
 
 // # Some information for the `net` working group:
@@ -252,7 +436,8 @@ pub fn testlogin() {
     let mut vec = Vec::new();   // stream to write into
 
     // original struct
-    let login = Login { username: "elena".into(), password: "prakt".into() };
+    let login = Login { username: "elena".into(), password_hash: vec![1, 2, 3],
+        compression: false };
     let _ = encode_into(&PkgType::Login, &mut vec, SizeLimit::Bounded(1024));
     let _ = encode_into(&login, &mut vec, SizeLimit::Bounded(1024));
 
@@ -260,5 +445,173 @@ pub fn testlogin() {
 
     // test for equality
     assert_eq!(login_res.username, "elena");
-    assert_eq!(login_res.password, "prakt");
+    assert_eq!(login_res.password_hash, vec![1, 2, 3]);
+}
+
+#[test]
+pub fn test_dataset_rows() {
+    use storage::{Column, SqlType};
+
+    let columns = vec![Column::new("id", SqlType::Int, false, "", true),
+        Column::new("name", SqlType::Char(5), false, "", false),
+        Column::new("ok", SqlType::Bool, false, "", false)];
+    let data = vec![0, 0, 0, 7, 98, 111, 98, 0, 0, 1,
+        0, 0, 0, 0, 101, 118, 101, 108, 121, 0];
+    let rows: Vec<_> =
+        preprocess(&ResultSet { data: data, columns: columns }).into_iter().collect();
+
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0].get_i64(0), Some(7));
+    assert_eq!(rows[0].get_str(1), Some("bob"));
+    assert_eq!(rows[0].get_bool(2), Some(true));
+    assert_eq!(rows[1].get_str(1), Some("evely"));
+    assert_eq!(rows[1].get_bool(2), Some(false));
+    assert_eq!(rows[1].get_col_idx("name"), Some(1));
+
+    // wrong type or index out of bounds
+    assert_eq!(rows[0].get_i64(1), None);
+    assert_eq!(rows[0].get_str(3), None);
+}
+
+#[test]
+pub fn test_dataset_typed_getters() {
+    use storage::{Column, SqlType};
+
+    let columns = vec![Column::new("id", SqlType::Int, false, "", true),
+        Column::new("name", SqlType::VarChar(4), false, "", false)];
+    let data = vec![0, 0, 0, 7, 98, 111, 98, 0,
+        0, 0, 1, 0, 97, 110, 110, 97];
+    let set = preprocess(&ResultSet { data: data, columns: columns });
+
+    assert_eq!(set.row_count(), 2);
+    assert_eq!(set.column_name(1), "name");
+    assert_eq!(set.column_type(0), SqlType::Int);
+    assert_eq!(set.column_type(1), SqlType::VarChar(4));
+    assert_eq!(set.get_i64(0, 0), Ok(7));
+    assert_eq!(set.get_i64(1, 0), Ok(256));
+    assert_eq!(set.get_str(0, 1), Ok("bob"));
+    assert_eq!(set.get_str(1, 1), Ok("anna"));
+
+    // no coercion between the types
+    assert_eq!(set.get_str(0, 0), Err(CellError::WrongType(SqlType::Int)));
+    assert_eq!(set.get_i64(0, 1), Err(CellError::WrongType(SqlType::VarChar(4))));
+    assert_eq!(set.get_bool(0, 0), Err(CellError::WrongType(SqlType::Int)));
+
+    assert_eq!(set.get_i64(2, 0), Err(CellError::OutOfBounds));
+    assert_eq!(set.get_str(0, 2), Err(CellError::OutOfBounds));
+}
+
+#[test]
+pub fn test_dataset_to_json() {
+    use storage::{Column, SqlType};
+
+    let columns = vec![Column::new("id", SqlType::Int, false, "", true),
+        Column::new("name", SqlType::Char(5), false, "", false)];
+    let data = vec![0, 0, 0, 7, 98, 111, 98, 0, 0,
+        255, 255, 255, 254, 34, 113, 34, 0, 0];
+    let set = preprocess(&ResultSet { data: data, columns: columns });
+
+    assert_eq!(set.to_json(),
+        r#"[{"id":7,"name":"bob"},{"id":-2,"name":"\"q\""}]"#);
+
+    // a cell which is no valid value of its type
+    let columns = vec![Column::new("name", SqlType::Char(2), false, "", false)];
+    let set = preprocess(&ResultSet { data: vec![255, 0], columns: columns });
+    assert_eq!(set.to_json(), r#"[{"name":null}]"#);
+
+    let set = preprocess(&ResultSet { data: vec![], columns: vec![] });
+    assert_eq!(set.to_json(), "[]");
+}
+
+#[test]
+pub fn test_dataset_to_csv() {
+    use storage::{Column, SqlType};
+
+    let columns = vec![Column::new("id", SqlType::Int, false, "", true),
+        Column::new("name", SqlType::Char(6), false, "", false),
+        Column::new("ok", SqlType::Bool, false, "", false)];
+    let data = vec![0, 0, 0, 7, 98, 44, 32, 34, 120, 34, 1,
+        0, 0, 0, 8, 97, 10, 98, 0, 0, 0, 0,
+        0, 0, 0, 9, 255, 0, 0, 0, 0, 0, 0];
+    let set = preprocess(&ResultSet { data: data, columns: columns });
+
+    let mut csv = Vec::new();
+    set.to_csv(&mut csv).unwrap();
+    assert_eq!(csv, b"id,name,ok\r\n7,\"b, \"\"x\"\"\",true\r\n8,\"a\nb\",false\r\n9,,false\r\n"
+        .to_vec());
+}
+
+#[test]
+pub fn test_send_affected_rows_packet() {
+    let mut vec = Vec::new();
+
+    let res = send_affected_rows_package(&mut vec, 3);
+    assert_eq!(res.is_ok(), true);
+    assert_eq!(vec, vec![0, 0, 0, 8, 0, 0, 0, 0, 0, 0, 0, 3]);
+}
+
+#[test]
+pub fn test_compressed_result() {
+    use storage::{Column, SqlType};
+
+    fn result(rows: u8) -> ResultSet {
+        ResultSet {
+            data: (0..rows).flat_map(|i| vec![0, 0, 0, i]).collect(),
+            columns: vec![Column::new("id", SqlType::Int, false, "", true)]
+        }
+    }
+
+    fn round_trip(data: ResultSet, compress: bool) -> (PkgType, usize, ResultSet) {
+        let mut vec = Vec::new();
+        send_result_package(&mut vec, data, compress).unwrap();
+        let len = vec.len();
+        let mut reader = &vec[..];
+        let pkg: PkgType = decode_from(&mut reader, SizeLimit::Bounded(1024)).unwrap();
+        let res = match pkg {
+            PkgType::CompressedResponse =>
+                read_compressed_result(&mut reader, SizeLimit::Infinite).unwrap(),
+            _ => decode_from(&mut reader, SizeLimit::Infinite).unwrap()
+        };
+        (pkg, len, res)
+    }
+
+    let (pkg, plain_len, plain) = round_trip(result(255), false);
+    assert!(pkg == PkgType::Response);
+    let (pkg, compressed_len, compressed) = round_trip(result(255), true);
+    assert!(pkg == PkgType::CompressedResponse);
+    assert!(compressed_len < plain_len);
+    assert_eq!(compressed.data, plain.data);
+    assert_eq!(compressed.columns[0].name, plain.columns[0].name);
+
+    // small results are not worth it
+    let (pkg, _, _) = round_trip(result(2), true);
+    assert!(pkg == PkgType::Response);
+}
+
+#[test]
+pub fn test_cancelled_stream() {
+    use storage::{Column, SqlType};
+
+    let rows = || ResultSet {
+        data: vec![0; STREAM_BATCH_SIZE * 2],
+        columns: vec![Column::new("id", SqlType::Int, false, "", true)]
+    };
+    let read_pkg = |reader: &mut &[u8]| -> PkgType {
+        decode_from(reader, SizeLimit::Bounded(1024)).unwrap()
+    };
+
+    let mut vec = Vec::new();
+    send_streamed_response(&mut vec, rows(), &AtomicBool::new(false)).unwrap();
+    let full_len = vec.len();
+
+    // no batch is sent once the query is cancelled
+    let mut vec = Vec::new();
+    send_streamed_response(&mut vec, rows(), &AtomicBool::new(true)).unwrap();
+    assert!(vec.len() < full_len);
+    let mut reader = &vec[..];
+    assert!(read_pkg(&mut reader) == PkgType::StreamStart);
+    let _: Vec<Column> = decode_from(&mut reader, SizeLimit::Infinite).unwrap();
+    assert!(read_pkg(&mut reader) == PkgType::Error);
+    let err: ClientErrMsg = decode_from(&mut reader, SizeLimit::Bounded(1024)).unwrap();
+    assert!(err.is_cancelled());
 }
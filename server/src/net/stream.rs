@@ -0,0 +1,138 @@
+//! The stream a connection is spoken over: plain TCP, or TLS on top of TCP
+//! if the `tls` feature is enabled.
+//!
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+
+#[cfg(feature = "tls")]
+use native_tls::{TlsAcceptor, TlsConnector, TlsStream, Identity};
+#[cfg(feature = "tls")]
+pub use native_tls::Certificate;
+
+/// Time a client has to finish the TLS handshake.
+pub const TLS_HANDSHAKE_TIMEOUT: u64 = 3;
+
+/// Milliseconds a client has to start the TLS handshake. A plaintext client
+/// waits for the greeting instead and gets an error package afterwards.
+pub const TLS_HELLO_TIMEOUT_MS: u64 = 1000;
+
+/// The first byte of a TLS handshake
+#[cfg(feature = "tls")]
+const TLS_HANDSHAKE_RECORD: u8 = 0x16;
+
+/// A connection between client and server.
+pub enum Stream {
+    Plain(TcpStream),
+    #[cfg(feature = "tls")]
+    Tls(TlsStream<TcpStream>),
+}
+
+impl Stream {
+    /// The underlying TCP stream.
+    pub fn get_ref(&self) -> &TcpStream {
+        match self {
+            &Stream::Plain(ref s) => s,
+            #[cfg(feature = "tls")]
+            &Stream::Tls(ref s) => s.get_ref(),
+        }
+    }
+
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.get_ref().peer_addr()
+    }
+
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.get_ref().set_read_timeout(timeout)
+    }
+
+    pub fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.get_ref().set_write_timeout(timeout)
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            &mut Stream::Plain(ref mut s) => s.read(buf),
+            #[cfg(feature = "tls")]
+            &mut Stream::Tls(ref mut s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            &mut Stream::Plain(ref mut s) => s.write(buf),
+            #[cfg(feature = "tls")]
+            &mut Stream::Tls(ref mut s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            &mut Stream::Plain(ref mut s) => s.flush(),
+            #[cfg(feature = "tls")]
+            &mut Stream::Tls(ref mut s) => s.flush(),
+        }
+    }
+}
+
+#[cfg(feature = "tls")]
+fn tls_error<E: ::std::error::Error + Send + Sync + 'static>(e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, e)
+}
+
+/// Creates the server side of TLS from a certificate and a PKCS #8 private
+/// key, both PEM encoded.
+#[cfg(feature = "tls")]
+pub fn tls_acceptor(cert_file: &str, key_file: &str) -> io::Result<TlsAcceptor> {
+    use std::fs::File;
+
+    let mut cert = Vec::new();
+    try!(try!(File::open(cert_file)).read_to_end(&mut cert));
+    let mut key = Vec::new();
+    try!(try!(File::open(key_file)).read_to_end(&mut key));
+
+    let identity = try!(Identity::from_pkcs8(&cert, &key).map_err(tls_error));
+    TlsAcceptor::new(identity).map_err(tls_error)
+}
+
+/// Performs the server side of the TLS handshake. A client that does not
+/// start one gets an error package it can read without TLS.
+#[cfg(feature = "tls")]
+pub fn tls_accept(acceptor: &TlsAcceptor, mut tcp: TcpStream) -> io::Result<Stream> {
+    try!(tcp.set_read_timeout(Some(Duration::from_millis(TLS_HELLO_TIMEOUT_MS))));
+    let mut first = [0; 1];
+    let is_tls = match tcp.peek(&mut first) {
+        Ok(n) => n == 1 && first[0] == TLS_HANDSHAKE_RECORD,
+        Err(ref e) if e.kind() == io::ErrorKind::WouldBlock
+            || e.kind() == io::ErrorKind::TimedOut => false,
+        Err(e) => return Err(e),
+    };
+    if !is_tls {
+        let _ = super::send_error_package(&mut tcp, super::Error::TlsRequired.into());
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "client did not start TLS"))
+    }
+
+    try!(tcp.set_read_timeout(Some(Duration::from_secs(TLS_HANDSHAKE_TIMEOUT))));
+    let tls = try!(acceptor.accept(tcp).map_err(|e| io::Error::new(io::ErrorKind::Other,
+        e.to_string())));
+    try!(tls.get_ref().set_read_timeout(None));
+    Ok(Stream::Tls(tls))
+}
+
+/// Performs the client side of the TLS handshake. The server certificate
+/// has to be signed by one of `roots` and be valid for `domain`.
+#[cfg(feature = "tls")]
+pub fn tls_connect(tcp: TcpStream, domain: &str, roots: &[Certificate]) -> io::Result<Stream> {
+    let mut builder = TlsConnector::builder();
+    for cert in roots {
+        builder.add_root_certificate(cert.clone());
+    }
+    let connector = try!(builder.build().map_err(tls_error));
+    let tls = try!(connector.connect(domain, tcp).map_err(|e| io::Error::new(
+        io::ErrorKind::Other, e.to_string())));
+    Ok(Stream::Tls(tls))
+}
@@ -3,10 +3,18 @@
 /// about 3 months ;)
 
 use std::error::Error;
+use auth;
 use storage::ResultSet;
-use storage::{Column, SqlType};
+pub use storage::{Column, SqlType};
 use storage::types::FromSql;
+use value::{format_date, format_timestamp};
 use std::cmp::{max};
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::rc::Rc;
+use rustc_serialize::json::Json;
+use std::str;
+use std::vec;
 
 /// Representation of a ResultSet with its useful functions to get data.
 pub struct DataSet {
@@ -112,14 +120,157 @@ impl DataSet {
         }
     }
 
+    /// Return the number of rows.
+    pub fn row_count(&self) -> usize {
+        self.line_cnt
+    }
+
+    /// Return the type of column `idx`.
+    ///
+    /// # Panics
+    /// If `idx` is out of bounds.
+    pub fn column_type(&self, idx: usize) -> SqlType {
+        self.columns[idx].sql_type
+    }
+
+    /// Return the name of column `idx`.
+    ///
+    /// # Panics
+    /// If `idx` is out of bounds.
+    pub fn column_name(&self, idx: usize) -> &str {
+        &self.columns[idx].name
+    }
+
+    /// Return raw data and type of the cell in line `row` and column `col`.
+    fn get_cell(&self, row: usize, col: usize) -> Result<(&[u8], SqlType), CellError> {
+        match self.data.get(row).and_then(|line| line.get(col)) {
+            Some(data) => match self.columns[col].value_of(data) {
+                Some(value) => Ok((value, self.columns[col].sql_type)),
+                None => Err(CellError::Null)
+            },
+            None => Err(CellError::OutOfBounds)
+        }
+    }
+
+    /// Return whether the cell in line `row` and column `col` holds null.
+    pub fn is_null(&self, row: usize, col: usize) -> Result<bool, CellError> {
+        match self.get_cell(row, col) {
+            Ok(_) => Ok(false),
+            Err(CellError::Null) => Ok(true),
+            Err(e) => Err(e)
+        }
+    }
+
+    /// Return value of the Int cell in line `row` and column `col`.
+    pub fn get_i64(&self, row: usize, col: usize) -> Result<i64, CellError> {
+        match try!(self.get_cell(row, col)) {
+            (data, SqlType::Int) =>
+                i32::from_sql(data).map(|i| i as i64).map_err(|_| CellError::Invalid),
+            (_, t) => Err(CellError::WrongType(t))
+        }
+    }
+
+    /// Return value of the Bool cell in line `row` and column `col`.
+    pub fn get_bool(&self, row: usize, col: usize) -> Result<bool, CellError> {
+        match try!(self.get_cell(row, col)) {
+            (data, SqlType::Bool) => bool::from_sql(data).map_err(|_| CellError::Invalid),
+            (_, t) => Err(CellError::WrongType(t))
+        }
+    }
+
+    /// Return value of the Float or Double cell in line `row` and column
+    /// `col`.
+    pub fn get_f64(&self, row: usize, col: usize) -> Result<f64, CellError> {
+        match try!(self.get_cell(row, col)) {
+            (data, SqlType::Float) =>
+                f32::from_sql(data).map(|f| f as f64).map_err(|_| CellError::Invalid),
+            (data, SqlType::Double) => f64::from_sql(data).map_err(|_| CellError::Invalid),
+            (_, t) => Err(CellError::WrongType(t))
+        }
+    }
+
+    /// Return value of the Date or Timestamp cell in line `row` and column
+    /// `col` as `YYYY-MM-DD` or `YYYY-MM-DD hh:mm:ss`.
+    pub fn get_datetime(&self, row: usize, col: usize) -> Result<String, CellError> {
+        match try!(self.get_cell(row, col)) {
+            (data, SqlType::Date) =>
+                i32::from_sql(data).map(|d| format_date(d as i64)).map_err(|_| CellError::Invalid),
+            (data, SqlType::Timestamp) =>
+                i64::from_sql(data).map(format_timestamp).map_err(|_| CellError::Invalid),
+            (_, t) => Err(CellError::WrongType(t))
+        }
+    }
+
+    /// Return value of the Char or VarChar cell in line `row` and column
+    /// `col`, without the trailing '0' bytes.
+    pub fn get_str(&self, row: usize, col: usize) -> Result<&str, CellError> {
+        match try!(self.get_cell(row, col)) {
+            (data, SqlType::Char(_)) | (data, SqlType::VarChar(_)) => {
+                let len = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+                str::from_utf8(&data[..len]).map_err(|_| CellError::Invalid)
+            },
+            (_, t) => Err(CellError::WrongType(t))
+        }
+    }
+
+    /// Return the rows as JSON array with one object per row, which maps the
+    /// column names to the values. Null cells and cells that can not be read
+    /// are `null`.
+    pub fn to_json(&self) -> String {
+        let mut rows = Vec::new();
+        for row in 0..self.line_cnt {
+            let mut object = BTreeMap::new();
+            for (col, column) in self.columns.iter().enumerate() {
+                let value = match column.sql_type {
+                    SqlType::Int => self.get_i64(row, col).map(|i| Json::I64(i)),
+                    SqlType::Bool => self.get_bool(row, col).map(|b| Json::Boolean(b)),
+                    SqlType::Char(_) | SqlType::VarChar(_) =>
+                        self.get_str(row, col).map(|s| Json::String(s.into())),
+                    SqlType::Float | SqlType::Double =>
+                        self.get_f64(row, col).map(|f| Json::F64(f)),
+                    SqlType::Date | SqlType::Timestamp =>
+                        self.get_datetime(row, col).map(|s| Json::String(s)),
+                };
+                object.insert(column.name.clone(), value.unwrap_or(Json::Null));
+            }
+            rows.push(Json::Object(object));
+        }
+        Json::Array(rows).to_string()
+    }
+
+    /// Write the rows as CSV (RFC 4180) with a header line of the column
+    /// names. Null cells and cells that can not be read are written as empty
+    /// fields.
+    pub fn to_csv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let names: Vec<_> = self.columns.iter().map(|c| csv_field(&c.name)).collect();
+        try!(write!(writer, "{}\r\n", names.join(",")));
+        for row in 0..self.line_cnt {
+            let mut fields = Vec::new();
+            for (col, column) in self.columns.iter().enumerate() {
+                let value = match column.sql_type {
+                    SqlType::Int => self.get_i64(row, col).map(|i| i.to_string()),
+                    SqlType::Bool => self.get_bool(row, col).map(|b| b.to_string()),
+                    SqlType::Char(_) | SqlType::VarChar(_) =>
+                        self.get_str(row, col).map(|s| csv_field(s)),
+                    SqlType::Float | SqlType::Double =>
+                        self.get_f64(row, col).map(|f| f.to_string()),
+                    SqlType::Date | SqlType::Timestamp => self.get_datetime(row, col),
+                };
+                fields.push(value.unwrap_or(String::new()));
+            }
+            try!(write!(writer, "{}\r\n", fields.join(",")));
+        }
+        Ok(())
+    }
+
     /// Return next data entry. next() has to be called first it initialize
     /// the pointer
     pub fn next_int_by_idx (&mut self, idx: usize) -> Option<i32> {
         if idx >= self.columns.len() { //idx out of bounds
             None
         } else {
-            match i32::from_sql(&self.data[self.current_pos - 1][idx][..]) {
-                Ok(val) => Some(val),
+            match self.get_cell(self.current_pos - 1, idx) {
+                Ok((data, _)) => i32::from_sql(data).ok(),
                 Err(e) => {println!("int by idx: {:?}", e); None}
             }
         }
@@ -131,29 +282,44 @@ impl DataSet {
         if idx >= self.columns.len() { //idx out of bounds
             None
         } else {
-            match bool::from_sql(&self.data[self.current_pos - 1][idx][..]) {
-                Ok(val) => Some(val),
+            match self.get_cell(self.current_pos - 1, idx) {
+                Ok((data, _)) => bool::from_sql(data).ok(),
                 Err(e) => {println!("bool by idx: {:?}", e); None}
             }
         }
     }
 
+    /// Return next data entry of a Float or Double column. next() has to be
+    /// called first it initialize the pointer
+    pub fn next_float_by_idx (&mut self, idx: usize) -> Option<f64> {
+        self.get_f64(self.current_pos - 1, idx).ok()
+    }
+
+    /// Return next data entry of a Date or Timestamp column, formatted like
+    /// `get_datetime`. next() has to be called first it initialize the pointer
+    pub fn next_datetime_by_idx (&mut self, idx: usize) -> Option<String> {
+        self.get_datetime(self.current_pos - 1, idx).ok()
+    }
+
     /// Return next data entry. next() has to be called first it initialize
     /// the pointer
     pub fn next_char_by_idx (&mut self, idx: usize) -> Option<String> {
         if idx >= self.columns.len() { //idx out of bounds
             None
         } else {
+            let data = match self.get_cell(self.current_pos - 1, idx) {
+                Ok((data, _)) => data,
+                Err(_) => return None
+            };
             // find the first pos that does not contain '0' value
             let mut pos = 0;
-            let data = &self.data[self.current_pos - 1][idx][..];
-            while pos < self.columns[idx].sql_type.size() as usize {
+            while pos < data.len() {
                 if data[pos] == 0 {
                     break;
                 }
                 pos += 1;
             }
-            match String::from_sql(&self.data[self.current_pos - 1][idx][0..pos]) {
+            match String::from_sql(&data[0..pos]) {
                 Ok(val) => { Some(val) },
                 Err(e) => { None }
             }
@@ -220,6 +386,170 @@ impl DataSet {
     }
 }
 
+/// Quote a CSV field if it contains a separator, quotation mark or line
+/// break. Quotation marks are escaped by doubling them.
+fn csv_field(s: &str) -> String {
+    if s.contains(|c| c == ',' || c == '"' || c == '\r' || c == '\n') {
+        format!("\"{}\"", s.replace("\"", "\"\""))
+    } else {
+        s.into()
+    }
+}
+
+/// Why a typed getter of a DataSet failed.
+#[derive(Debug, PartialEq)]
+pub enum CellError {
+    /// There is no cell with this line and column index
+    OutOfBounds,
+    /// The column holds values of another type
+    WrongType(SqlType),
+    /// The stored bytes are no valid value of the column type
+    Invalid,
+    /// The cell holds null
+    Null,
+}
+
+/// Iterate over the rows of a DataSet by value, e.g.
+/// `for row in dataset { ... }`.
+impl IntoIterator for DataSet {
+    type Item = Row;
+    type IntoIter = Rows;
+
+    fn into_iter(self) -> Rows {
+        Rows { data: self.data.into_iter(), columns: Rc::new(self.columns) }
+    }
+}
+
+/// Iterator over the rows of a DataSet, created by `DataSet::into_iter`.
+pub struct Rows {
+    data: vec::IntoIter<Vec<Vec<u8>>>,
+    columns: Rc<Vec<Column>>,
+}
+
+impl Rows {
+    /// Split the data of a batch of a streamed result into rows.
+    pub fn from_batch(data: &[u8], columns: Rc<Vec<Column>>) -> Rows {
+        let sizes: Vec<_> = columns.iter().map(|c| c.get_size() as usize).collect();
+        let line_len = sizes.iter().fold(0, |a, b| a + b);
+        let mut lines = Vec::new();
+        if line_len > 0 {
+            for line in data.chunks(line_len) {
+                let mut pos = 0;
+                let mut values = Vec::new();
+                for &size in &sizes {
+                    values.push(line[pos..pos + size].to_vec());
+                    pos += size;
+                }
+                lines.push(values);
+            }
+        }
+        Rows { data: lines.into_iter(), columns: columns }
+    }
+
+    /// Return the number of rows left in this iterator.
+    pub fn remaining(&self) -> usize {
+        self.data.len()
+    }
+}
+
+impl Iterator for Rows {
+    type Item = Row;
+
+    fn next(&mut self) -> Option<Row> {
+        self.data.next().map(|values| Row { values: values, columns: self.columns.clone() })
+    }
+}
+
+/// A single line of a DataSet. All accessors return None if the column index
+/// is out of bounds, the column does not hold the requested type or the value
+/// is null.
+pub struct Row {
+    values: Vec<Vec<u8>>,
+    columns: Rc<Vec<Column>>,
+}
+
+impl Row {
+
+    /// Return number of columns in this row.
+    pub fn get_col_cnt(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Return index of the column with the given name.
+    pub fn get_col_idx(&self, name: &str) -> Option<usize> {
+        self.columns.iter().position(|c| c.name == name)
+    }
+
+    /// Return raw data and type of column `idx`, None for null.
+    fn get_raw(&self, idx: usize) -> Option<(&[u8], SqlType)> {
+        if idx >= self.values.len() || idx >= self.columns.len() { //idx out of bounds
+            None
+        } else {
+            self.columns[idx].value_of(&self.values[idx]).map(|v| (v, self.columns[idx].sql_type))
+        }
+    }
+
+    /// Return whether column `idx` holds null.
+    pub fn is_null(&self, idx: usize) -> Option<bool> {
+        if idx >= self.values.len() || idx >= self.columns.len() { //idx out of bounds
+            None
+        } else {
+            Some(self.columns[idx].value_of(&self.values[idx]).is_none())
+        }
+    }
+
+    /// Return value of the Int column `idx`.
+    pub fn get_i64(&self, idx: usize) -> Option<i64> {
+        match self.get_raw(idx) {
+            Some((data, SqlType::Int)) => i32::from_sql(data).ok().map(|i| i as i64),
+            _ => None
+        }
+    }
+
+    /// Return value of the Bool column `idx`.
+    pub fn get_bool(&self, idx: usize) -> Option<bool> {
+        match self.get_raw(idx) {
+            Some((data, SqlType::Bool)) => bool::from_sql(data).ok(),
+            _ => None
+        }
+    }
+
+    /// Return value of the Float or Double column `idx`.
+    pub fn get_f64(&self, idx: usize) -> Option<f64> {
+        match self.get_raw(idx) {
+            Some((data, SqlType::Float)) => f32::from_sql(data).ok().map(|f| f as f64),
+            Some((data, SqlType::Double)) => f64::from_sql(data).ok(),
+            _ => None
+        }
+    }
+
+    /// Return value of the Date or Timestamp column `idx`, formatted like
+    /// `DataSet::get_datetime`.
+    pub fn get_datetime(&self, idx: usize) -> Option<String> {
+        match self.get_raw(idx) {
+            Some((data, SqlType::Date)) => i32::from_sql(data).ok().map(|d| format_date(d as i64)),
+            Some((data, SqlType::Timestamp)) => i64::from_sql(data).ok().map(format_timestamp),
+            _ => None
+        }
+    }
+
+    /// Return value of the Char or VarChar column `idx`, without the
+    /// trailing '0' bytes.
+    pub fn get_str(&self, idx: usize) -> Option<&str> {
+        match self.get_raw(idx) {
+            Some((data, SqlType::Char(_))) | Some((data, SqlType::VarChar(_))) => {
+                let len = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+                str::from_utf8(&data[..len]).ok()
+            },
+            _ => None
+        }
+    }
+}
+
+/// Metadata of a table column as sent for `Command::Describe`: name, type
+/// and constraints.
+pub type ColumnInfo = Column;
+
 /// Sort the Vec<u8> data into DataSet for further use.
 pub fn preprocess (data: &ResultSet) -> DataSet {
     let col_count = data.columns.len();
@@ -272,6 +602,15 @@ pub enum PkgType {
     Response,
     AccDenied,
     AccGranted,
+    AffectedRows,
+    Prepared,
+    StreamStart,
+    RowBatch,
+    StreamEnd,
+    CompressedResponse,
+    BatchEnd,
+    ServerInfo,
+    Explain,
 }
 
 /// Struct to send the kind of error and error message to the client
@@ -281,32 +620,158 @@ pub struct ClientErrMsg {
     pub msg: String
 }
 
+impl ClientErrMsg {
+    /// Whether the query was stopped by `Command::Cancel`.
+    pub fn is_cancelled(&self) -> bool {
+        self.code() == ErrorCode::Cancelled
+    }
+
+    /// The kind of the error, to tell errors apart without parsing `msg`.
+    pub fn code(&self) -> ErrorCode {
+        ErrorCode::from_u16(self.code)
+    }
+}
+
+/// Kind of an error reported by the server, sent as number in a
+/// `ClientErrMsg`. The numbers must not change.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorCode {
+    Io = 0,
+    UnexpectedPkg = 2,
+    UnknownCmd = 3,
+    Encode = 4,
+    Decode = 5,
+    /// The query has a syntax error
+    Parse = 6,
+    NoTransaction = 7,
+    NestedTransaction = 8,
+    UnknownStatement = 9,
+    Cancelled = 10,
+    UnknownConnection = 11,
+    TooManyConnections = 12,
+    UnknownDatabase = 13,
+    NoDatabaseSelected = 14,
+    /// Any other failure while executing a query
+    Execution = 15,
+    UnknownTable = 16,
+    UnknownColumn = 17,
+    /// E.g. a duplicate primary key
+    ConstraintViolation = 18,
+    /// Not sent by the server, only used by the client for failed logins
+    Auth = 19,
+    /// The client did not start TLS, but the server requires it
+    TlsRequired = 20,
+    /// A number unknown to this version
+    Unknown = 0xffff,
+}
+
+impl ErrorCode {
+    fn from_u16(code: u16) -> ErrorCode {
+        match code {
+            0 => ErrorCode::Io,
+            2 => ErrorCode::UnexpectedPkg,
+            3 => ErrorCode::UnknownCmd,
+            4 => ErrorCode::Encode,
+            5 => ErrorCode::Decode,
+            6 => ErrorCode::Parse,
+            7 => ErrorCode::NoTransaction,
+            8 => ErrorCode::NestedTransaction,
+            9 => ErrorCode::UnknownStatement,
+            10 => ErrorCode::Cancelled,
+            11 => ErrorCode::UnknownConnection,
+            12 => ErrorCode::TooManyConnections,
+            13 => ErrorCode::UnknownDatabase,
+            14 => ErrorCode::NoDatabaseSelected,
+            15 => ErrorCode::Execution,
+            16 => ErrorCode::UnknownTable,
+            17 => ErrorCode::UnknownColumn,
+            18 => ErrorCode::ConstraintViolation,
+            19 => ErrorCode::Auth,
+            20 => ErrorCode::TlsRequired,
+            _ => ErrorCode::Unknown,
+        }
+    }
+}
+
 /// Convert the possible Error to a serializable ClientErrMsg struct
 impl From<super::Error> for ClientErrMsg {
     fn from(error: super::Error) -> ClientErrMsg {
         match error {
             super::Error::Io(_) => ClientErrMsg {
-                code: 0,
+                code: ErrorCode::Io as u16,
                 msg: error.description().into()
             },
             super::Error::UnexpectedPkg => ClientErrMsg {
-                code: 2,
+                code: ErrorCode::UnexpectedPkg as u16,
                 msg: error.description().into()
             },
             super::Error::UnknownCmd => ClientErrMsg {
-                code: 3,
+                code: ErrorCode::UnknownCmd as u16,
                 msg: error.description().into()
             },
             super::Error::Encode(_) => ClientErrMsg {
-                code: 4,
+                code: ErrorCode::Encode as u16,
                 msg: error.description().into()
             },
             super::Error::Decode(_) => ClientErrMsg {
-                code: 5,
+                code: ErrorCode::Decode as u16,
+                msg: error.description().into()
+            },
+            // the parse error tells where the query is wrong
+            super::Error::UnEoq(ref e) => ClientErrMsg {
+                code: ErrorCode::Parse as u16,
+                msg: format!("{}: {}", error.description(), e)
+            },
+            super::Error::NoTransaction => ClientErrMsg {
+                code: ErrorCode::NoTransaction as u16,
+                msg: error.description().into()
+            },
+            super::Error::NestedTransaction => ClientErrMsg {
+                code: ErrorCode::NestedTransaction as u16,
+                msg: error.description().into()
+            },
+            super::Error::UnknownStatement => ClientErrMsg {
+                code: ErrorCode::UnknownStatement as u16,
+                msg: error.description().into()
+            },
+            super::Error::Cancelled => ClientErrMsg {
+                code: ErrorCode::Cancelled as u16,
+                msg: error.description().into()
+            },
+            super::Error::UnknownConnection => ClientErrMsg {
+                code: ErrorCode::UnknownConnection as u16,
+                msg: error.description().into()
+            },
+            super::Error::TooManyConnections => ClientErrMsg {
+                code: ErrorCode::TooManyConnections as u16,
+                msg: error.description().into()
+            },
+            super::Error::UnknownDatabase => ClientErrMsg {
+                code: ErrorCode::UnknownDatabase as u16,
                 msg: error.description().into()
             },
-            super::Error::UnEoq(_) => ClientErrMsg {
-                code: 6,
+            super::Error::NoDatabaseSelected => ClientErrMsg {
+                code: ErrorCode::NoDatabaseSelected as u16,
+                msg: error.description().into()
+            },
+            super::Error::UnknownTable => ClientErrMsg {
+                code: ErrorCode::UnknownTable as u16,
+                msg: error.description().into()
+            },
+            super::Error::Execution(ref s) => ClientErrMsg {
+                code: ErrorCode::Execution as u16,
+                msg: format!("{}: {}", error.description(), s)
+            },
+            super::Error::UnknownColumn => ClientErrMsg {
+                code: ErrorCode::UnknownColumn as u16,
+                msg: error.description().into()
+            },
+            super::Error::ConstraintViolation(ref s) => ClientErrMsg {
+                code: ErrorCode::ConstraintViolation as u16,
+                msg: format!("{}: {}", error.description(), s)
+            },
+            super::Error::TlsRequired => ClientErrMsg {
+                code: ErrorCode::TlsRequired as u16,
                 msg: error.description().into()
             }
         }
@@ -314,25 +779,39 @@ impl From<super::Error> for ClientErrMsg {
 }
 
 /// This is the first packet being sent by the server after the TCP connection
-/// is established.
+/// is established. The salt is created for every connection and has to be
+/// used by the client to hash its password. The connection id and the
+/// secret cancel key are needed to cancel queries of this connection from
+/// another one. If `compression` is set, the client may ask for compressed
+/// responses in its `Login`.
 #[derive(RustcEncodable, RustcDecodable)]
 pub struct Greeting {
     pub protocol_version: u8,   // 1 byte
     pub message: String,        // n bytes
+    pub salt: Vec<u8>,          // n bytes
+    pub connection_id: u64,     // 8 bytes
+    pub compression: bool,      // 1 byte
+    pub cancel_key: u64,        // 8 bytes
 }
 
 impl Greeting {
-    pub fn make_greeting(version: u8, msg: String) -> Greeting {
-        Greeting { protocol_version: version, message: msg }
+    pub fn make_greeting(version: u8, msg: String, connection_id: u64, cancel_key: u64)
+        -> Greeting
+    {
+        Greeting { protocol_version: version, message: msg, salt: auth::make_salt(),
+            connection_id: connection_id, compression: true, cancel_key: cancel_key }
     }
 }
 
 /// The client responds with this packet to a `Greeting` packet, finishing the
-/// authentication handshake.
+/// authentication handshake. The password is never sent in plain text, only
+/// `auth::hash_password(salt, password)`. With `compression` set, big
+/// results are sent as `CompressedResponse`.
 #[derive(Default, RustcEncodable, RustcDecodable)]
 pub struct Login {
     pub username: String,
-    pub password: String
+    pub password_hash: Vec<u8>,
+    pub compression: bool
 }
 
 /// Sent by the client to the server.
@@ -345,6 +824,65 @@ pub enum Command {
     Quit,
     Ping,
     Query(String),
+    Begin,
+    Commit,
+    Rollback,
+    Prepare(String),
+    // prepared statement id and parameters; no struct variant, because
+    // bincode does not encode the variant index of those
+    ExecutePrepared(u64, Vec<Value>),
+    // connection id and cancel key of the query to cancel, may be sent
+    // instead of a `Login`
+    Cancel(u64, u64),
+    // name of the database to use for the following queries
+    Use(String),
+    // names of the tables in the current database
+    ListTables,
+    // column metadata of the table
+    Describe(String),
+    // like Query, but the rows are sent in batches
+    StreamQuery(String),
+    // queries executed one after another, each answered like Query
+    Batch(Vec<String>, BatchMode),
+    // version, start time and load of the server
+    ServerInfo,
+    // the query is only parsed, not executed
+    Validate(String),
+    // the parsed query is sent back as text, it is not executed
+    Explain(String),
     // Shutdown,
     // Statistics,
 }
+
+/// Answer to `Command::Prepare`: the id of the prepared statement and the
+/// number of parameters it expects.
+#[derive(RustcEncodable, RustcDecodable, Debug, PartialEq)]
+pub struct PreparedInfo {
+    pub id: u64,
+    pub param_count: u64,
+}
+
+/// Answer to `Command::ServerInfo`.
+#[derive(RustcEncodable, RustcDecodable, Debug, PartialEq)]
+pub struct ServerInfo {
+    /// Crate version of the server
+    pub version: String,
+    pub protocol_version: u8,
+    /// Seconds since the Unix epoch when the server was started
+    pub started: u64,
+    /// Number of open connections, including the asking one
+    pub connections: u64,
+}
+
+/// What happens to the remaining queries of `Command::Batch` after one of
+/// them failed.
+#[derive(RustcEncodable, RustcDecodable, Debug, Clone, Copy, PartialEq)]
+pub enum BatchMode {
+    /// The remaining queries are not executed
+    StopOnError,
+    /// The remaining queries are executed anyway
+    CollectAll,
+}
+
+/// A parameter of a prepared statement.
+pub use value::Value;
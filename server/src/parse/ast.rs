@@ -2,6 +2,7 @@
 use super::token;
 use super::super::storage::SqlType;
 use std::collections::HashMap;
+use std::fmt;
 #[derive(Debug, Clone, PartialEq)]
 pub enum Query {
     Dummy, // For Compiling
@@ -80,13 +81,15 @@ pub struct ColumnInfo {
     pub auto_increment: bool,
     pub not_null: bool,
     pub comment: Option<String>,
+    /// `CHECK (...)`: a condition every row has to fulfil
+    pub check: Option<Conditions>,
 }
 
 /// Information for table alteration
 #[derive(Debug, Clone, PartialEq)]
 pub struct AlterTableStmt {
     pub tid: String,
-    pub op: AlterOp
+    pub ops: Vec<AlterOp>
 }
 
 /// Possible operations for table alterations
@@ -94,7 +97,9 @@ pub struct AlterTableStmt {
 pub enum AlterOp {
     Add(ColumnInfo),
     Drop(String),
-    Modify(ColumnInfo)
+    Modify(ColumnInfo),
+    RenameTable(String),
+    RenameColumn { from: String, to: String },
 }
 
 /// Information for table update
@@ -112,14 +117,25 @@ pub struct SelectStmt {
     pub target: Vec<Target>,
     pub tid: Vec<String>,
     pub alias: HashMap<String, String>,
+    pub joins: Vec<Join>,
     pub cond: Option<Conditions>,
-    //pub groupby: Option<GroupBy>,
-    //pub orderby: Option<OrderBy>,
+    pub group_by: Option<Vec<ColumnRef>>,
+    // filters the groups, without a group by the result of the aggregates
+    pub having: Option<Conditions>,
+    pub distinct: bool,
     pub spec_op: Option<SpecOps>,
     pub order: Vec<Sort>,
     pub limit: Option<Limit>,
 }
 
+/// An inner join of the table `right` to the table `left` in front of it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Join {
+    pub left: String,
+    pub right: String,
+    pub on: Conditions,
+}
+
 /// Information for data selection
 #[derive(Debug, Clone, PartialEq)]
 pub struct Target {
@@ -134,17 +150,71 @@ pub enum Col {
     // select a specified column
     Specified(String),
     // for example: table.* => select every column in table
-    Every
+    Every,
+    // for example: count(*) or sum(amount), the target alias is unused
+    Aggregate(Aggregate),
+    // for example: price * 1.2, the target alias is unused
+    Expr(Expr),
+}
+
+/// An aggregate function over the selected rows
+#[derive(Debug, Clone, PartialEq)]
+pub struct Aggregate {
+    pub func: AggregateFunc,
+    pub arg: AggregateArg,
+}
+
+#[derive(Debug, Clone, PartialEq, Copy)]
+pub enum AggregateFunc {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+/// The argument of an aggregate, `*` is only allowed for `count`
+#[derive(Debug, Clone, PartialEq)]
+pub enum AggregateArg {
+    Star,
+    Column(ColumnRef),
+}
+
+/// An arithmetic expression over columns, literals and aggregates
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Column(ColumnRef),
+    Literal(token::Value),
+    // for example: `count(*)` in `having count(*) > 1`
+    Aggregate(Aggregate),
+    Binary(Box<Expr>, ArithOp, Box<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq, Copy)]
+pub enum ArithOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
 
+impl ArithOp {
+    // `*` and `/` bind tighter than `+` and `-`
+    fn precedence(&self) -> u8 {
+        match self {
+            &ArithOp::Add | &ArithOp::Sub => 1,
+            &ArithOp::Mul | &ArithOp::Div => 2,
+        }
+    }
 }
 
 /// Information for data output limiting
 #[derive(Debug, Clone, PartialEq)]
 pub struct Limit {
     //limit the count of the output
-    pub count: Option<i64>,
+    pub count: Option<u64>,
     //offset the output: 0 = no offset, n = display from the nth row
-    pub offset: Option<i64>,
+    pub offset: Option<u64>,
 }
 
 /// Information for data insertion
@@ -152,7 +222,7 @@ pub struct Limit {
 pub struct InsertStmt {
     pub tid: String,
     pub col: Vec<String>,
-    pub val: Vec<token::Lit>
+    pub val: Vec<token::Value>
 }
 
 /// Information for data deletion
@@ -176,6 +246,8 @@ pub enum SpecOps {
 #[derive(Debug, Clone, PartialEq)]
 pub enum Conditions {
     Leaf(Condition),
+    // a comparison which is no Leaf, like `price * 2 > 10` or `10 < price`
+    Compare(Expr, CompType, Expr),
     And(Box<Conditions>, Box<Conditions>),
     Or(Box<Conditions>, Box<Conditions>)
 }
@@ -196,11 +268,17 @@ pub struct Condition {
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Sort {
-    pub alias: Option<String>,
-    pub col: String,
+    pub col: ColumnRef,
     pub order: Option<Order>,
 }
 
+/// A column, `table.name` or just `name`. The table may be an alias.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnRef {
+    pub table: Option<String>,
+    pub name: String,
+}
+
 /// Allowed operators for where-clause
 #[derive(Debug, Clone, PartialEq, Copy)]
 pub enum CompType {
@@ -223,12 +301,23 @@ impl CompType {
             &CompType::SEThan => CompType::GThan,
         }
     }
+
+    /// The operator with swapped operands, `a < b` is `b > a`
+    pub fn flip(&self) -> CompType {
+        match self {
+            &CompType::GThan => CompType::SThan,
+            &CompType::SThan => CompType::GThan,
+            &CompType::GEThan => CompType::SEThan,
+            &CompType::SEThan => CompType::GEThan,
+            op => *op,
+        }
+    }
 }
 
 /// Allowed data types for where-clause
 #[derive(Debug, Clone, PartialEq)]
 pub enum CondType {
-    Literal(token::Lit),
+    Literal(token::Value),
     Word(String)
 }
 
@@ -237,6 +326,7 @@ pub enum DataSrc {
     Int(i64),
     String(String),
     Bool(u8),
+    Null,
 }
 
 
@@ -257,6 +347,7 @@ impl DataSrc {
             &DataSrc::Int(x) => x == 0,
             &DataSrc::String(ref x) => !x.is_empty(),
             &DataSrc::Bool(x) => x != 0,
+            &DataSrc::Null => false,
         }
     }
     /// static method to turn u8 into bool
@@ -266,3 +357,375 @@ impl DataSrc {
     }
 
 }
+
+// ============================================================================
+// Printing the AST as SQL
+// ============================================================================
+
+impl Query {
+    /// Turns the query back into SQL with uppercased keywords. The output is
+    /// not identical to the parsed query, but parses to the same AST.
+    pub fn to_sql(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &Query::Dummy => Ok(()),
+            &Query::DefStmt(ref stmt) => stmt.fmt(f),
+            &Query::ManipulationStmt(ref stmt) => stmt.fmt(f),
+        }
+    }
+}
+
+impl fmt::Display for DefStmt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &DefStmt::Create(CreateStmt::Table(ref stmt)) => stmt.fmt(f),
+            &DefStmt::Create(CreateStmt::View(ref stmt)) => stmt.fmt(f),
+            &DefStmt::Create(CreateStmt::Database(ref name)) =>
+                write!(f, "CREATE DATABASE {}", name),
+            &DefStmt::Alter(AltStmt::Table(ref stmt)) => stmt.fmt(f),
+            &DefStmt::Drop(DropStmt::Table(ref name)) => write!(f, "DROP TABLE {}", name),
+            &DefStmt::Drop(DropStmt::View(ref name)) => write!(f, "DROP VIEW {}", name),
+            &DefStmt::Drop(DropStmt::Database(ref name)) =>
+                write!(f, "DROP DATABASE {}", name),
+        }
+    }
+}
+
+impl fmt::Display for ManipulationStmt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &ManipulationStmt::Update(ref stmt) => stmt.fmt(f),
+            &ManipulationStmt::Select(ref stmt) => stmt.fmt(f),
+            &ManipulationStmt::Insert(ref stmt) => stmt.fmt(f),
+            &ManipulationStmt::Delete(ref stmt) => stmt.fmt(f),
+            &ManipulationStmt::Use(UseStmt::Database(ref name)) =>
+                write!(f, "USE DATABASE {}", name),
+            &ManipulationStmt::Describe(ref name) => write!(f, "DESCRIBE {}", name),
+        }
+    }
+}
+
+impl fmt::Display for CreateTableStmt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "CREATE TABLE {}", self.tid));
+        if !self.cols.is_empty() {
+            try!(write!(f, " ("));
+            try!(write_list(f, &self.cols));
+            try!(write!(f, ")"));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for CreateViewStmt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let replace = if self.opt { "OR REPLACE " } else { "" };
+        write!(f, "CREATE {}VIEW {} AS {}", replace, self.name, self.sel)
+    }
+}
+
+impl fmt::Display for ColumnInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "{} ", self.cid));
+        try!(match self.datatype {
+            SqlType::Int => write!(f, "INT"),
+            SqlType::Bool => write!(f, "BOOL"),
+            SqlType::Char(len) => write!(f, "CHAR({})", len),
+            SqlType::VarChar(len) => write!(f, "VARCHAR({})", len),
+            SqlType::Float => write!(f, "FLOAT"),
+            SqlType::Double => write!(f, "DOUBLE"),
+            SqlType::Date => write!(f, "DATE"),
+            SqlType::Timestamp => write!(f, "TIMESTAMP"),
+        });
+        if self.primary {
+            try!(write!(f, " PRIMARY KEY"));
+        }
+        if self.auto_increment {
+            try!(write!(f, " AUTO_INCREMENT"));
+        }
+        if self.not_null {
+            try!(write!(f, " NOT NULL"));
+        }
+        if let Some(ref comment) = self.comment {
+            try!(write!(f, " COMMENT {}", token::Value::Str(comment.clone())));
+        }
+        if let Some(ref check) = self.check {
+            try!(write!(f, " CHECK ({})", check));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for AlterTableStmt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "ALTER TABLE {} ", self.tid));
+        write_list(f, &self.ops)
+    }
+}
+
+impl fmt::Display for AlterOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &AlterOp::Add(ref col) => write!(f, "ADD {}", col),
+            &AlterOp::Drop(ref name) => write!(f, "DROP COLUMN {}", name),
+            &AlterOp::Modify(ref col) => write!(f, "MODIFY COLUMN {}", col),
+            &AlterOp::RenameTable(ref name) => write!(f, "RENAME TO {}", name),
+            &AlterOp::RenameColumn { ref from, ref to } =>
+                write!(f, "RENAME COLUMN {} TO {}", from, to),
+        }
+    }
+}
+
+impl fmt::Display for UpdateStmt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "UPDATE "));
+        try!(write_table(f, &self.tid, &self.alias));
+        try!(write!(f, " SET "));
+        try!(write_list(f, &self.set));
+        write_where(f, &self.conds)
+    }
+}
+
+impl fmt::Display for SelectStmt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "SELECT "));
+        if self.distinct {
+            try!(write!(f, "DISTINCT "));
+        }
+        try!(write_list(f, &self.target));
+        try!(write!(f, " FROM "));
+        for (i, tid) in self.tid.iter().enumerate() {
+            if i > 0 {
+                try!(write!(f, ", "));
+            }
+            try!(write_table(f, tid, &self.alias));
+        }
+        for join in &self.joins {
+            try!(write!(f, " JOIN "));
+            try!(write_table(f, &join.right, &self.alias));
+            try!(write!(f, " ON {}", join.on));
+        }
+        try!(write_where(f, &self.cond));
+        if let Some(ref group_by) = self.group_by {
+            try!(write!(f, " GROUP BY "));
+            try!(write_list(f, group_by));
+        }
+        if let Some(ref having) = self.having {
+            try!(write!(f, " HAVING {}", having));
+        }
+        if !self.order.is_empty() {
+            try!(write!(f, " ORDER BY "));
+            try!(write_list(f, &self.order));
+        }
+        if let Some(ref limit) = self.limit {
+            if let Some(count) = limit.count {
+                try!(write!(f, " LIMIT {}", count));
+            }
+            if let Some(offset) = limit.offset {
+                try!(write!(f, " OFFSET {}", offset));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref alias) = self.alias {
+            try!(write!(f, "{}.", alias));
+        }
+        try!(match self.col {
+            Col::Specified(ref name) => write!(f, "{}", name),
+            Col::Every => write!(f, "*"),
+            Col::Aggregate(ref aggregate) => write!(f, "{}", aggregate),
+            Col::Expr(ref expr) => write!(f, "{}", expr),
+        });
+        if let Some(ref rename) = self.rename {
+            try!(write!(f, " AS {}", rename));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Sort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "{}", self.col));
+        match self.order {
+            Some(Order::Asc) => write!(f, " ASC"),
+            Some(Order::Desc) => write!(f, " DESC"),
+            None => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for Aggregate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "{}(", match self.func {
+            AggregateFunc::Count => "COUNT",
+            AggregateFunc::Sum => "SUM",
+            AggregateFunc::Avg => "AVG",
+            AggregateFunc::Min => "MIN",
+            AggregateFunc::Max => "MAX",
+        }));
+        match self.arg {
+            AggregateArg::Star => write!(f, "*)"),
+            AggregateArg::Column(ref col) => write!(f, "{})", col),
+        }
+    }
+}
+
+impl fmt::Display for ColumnRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref table) = self.table {
+            try!(write!(f, "{}.", table));
+        }
+        write!(f, "{}", self.name)
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &Expr::Column(ref col) => col.fmt(f),
+            &Expr::Literal(ref lit) => lit.fmt(f),
+            &Expr::Aggregate(ref aggregate) => aggregate.fmt(f),
+            &Expr::Binary(ref lhs, op, ref rhs) => {
+                try!(write_operand(f, lhs, op, false));
+                try!(write!(f, " {} ", op));
+                write_operand(f, rhs, op, true)
+            },
+        }
+    }
+}
+
+impl fmt::Display for ArithOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &ArithOp::Add => "+",
+            &ArithOp::Sub => "-",
+            &ArithOp::Mul => "*",
+            &ArithOp::Div => "/",
+        }.fmt(f)
+    }
+}
+
+impl fmt::Display for InsertStmt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "INSERT INTO {} ", self.tid));
+        if !self.col.is_empty() {
+            try!(write!(f, "("));
+            try!(write_list(f, &self.col));
+            try!(write!(f, ") "));
+        }
+        try!(write!(f, "VALUES ("));
+        try!(write_list(f, &self.val));
+        write!(f, ")")
+    }
+}
+
+impl fmt::Display for DeleteStmt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "DELETE FROM "));
+        try!(write_table(f, &self.tid, &self.alias));
+        write_where(f, &self.cond)
+    }
+}
+
+impl fmt::Display for Conditions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &Conditions::Leaf(ref cond) => cond.fmt(f),
+            &Conditions::Compare(ref lhs, op, ref rhs) => write!(f, "{} {} {}", lhs, op, rhs),
+            &Conditions::Or(ref lhs, ref rhs) => write!(f, "{} OR {}", lhs, rhs),
+            &Conditions::And(ref lhs, ref rhs) => {
+                try!(write_and_operand(f, lhs));
+                try!(write!(f, " AND "));
+                write_and_operand(f, rhs)
+            },
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref alias) = self.aliascol {
+            try!(write!(f, "{}.", alias));
+        }
+        try!(write!(f, "{} {} ", self.col, self.op));
+        if let Some(ref alias) = self.aliasrhs {
+            try!(write!(f, "{}.", alias));
+        }
+        match self.rhs {
+            CondType::Literal(ref lit) => lit.fmt(f),
+            CondType::Word(ref word) => word.fmt(f),
+        }
+    }
+}
+
+impl fmt::Display for CompType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &CompType::Equ => "=",
+            &CompType::NEqu => "<>",
+            &CompType::GThan => ">",
+            &CompType::SThan => "<",
+            &CompType::GEThan => ">=",
+            &CompType::SEThan => "<=",
+        }.fmt(f)
+    }
+}
+
+// writes the items separated by commas
+fn write_list<T: fmt::Display>(f: &mut fmt::Formatter, items: &[T]) -> fmt::Result {
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            try!(write!(f, ", "));
+        }
+        try!(write!(f, "{}", item));
+    }
+    Ok(())
+}
+
+// writes the table name followed by its alias, if there is one
+fn write_table(f: &mut fmt::Formatter, tid: &str, aliases: &HashMap<String, String>)
+    -> fmt::Result
+{
+    try!(write!(f, "{}", tid));
+    match aliases.iter().find(|&(_, table)| table == tid) {
+        Some((alias, _)) => write!(f, " {}", alias),
+        None => Ok(()),
+    }
+}
+
+// writes one side of an `and`. `and` binds tighter, so `or` needs parentheses
+fn write_and_operand(f: &mut fmt::Formatter, cond: &Conditions) -> fmt::Result {
+    match cond {
+        &Conditions::Or(..) => write!(f, "({})", cond),
+        _ => write!(f, "{}", cond),
+    }
+}
+
+// writes an operand of `op`. The operators are left associative, so a right
+// operand of the same precedence needs parentheses, as in `a - (b - c)`.
+fn write_operand(f: &mut fmt::Formatter, expr: &Expr, op: ArithOp, right: bool)
+    -> fmt::Result
+{
+    match expr {
+        &Expr::Binary(_, inner, _) if inner.precedence() < op.precedence()
+            || (right && inner.precedence() == op.precedence()) => write!(f, "({})", expr),
+        _ => write!(f, "{}", expr),
+    }
+}
+
+// writes the where part, if there is one
+fn write_where(f: &mut fmt::Formatter, cond: &Option<Conditions>) -> fmt::Result {
+    match cond {
+        &Some(ref cond) => write!(f, " WHERE {}", cond),
+        &None => Ok(()),
+    }
+}
@@ -1,10 +1,10 @@
-use super::token::{Token, TokenSpan, Lit};
+use super::token::{Token, TokenSpan, Value, Span};
 use std::str::Chars;
-use super::Span;
 use std::iter::{Iterator};
 
 /// A lexer with its associated query, a char iterator, and
 /// positions (last, current, next)
+#[derive(Clone)]
 pub struct Lexer<'a> {
     chs: Chars<'a>,
     last: Option<char>,
@@ -21,7 +21,7 @@ impl<'a> Lexer<'a> {
     pub fn from_query<'b>(query: &'b str) -> Lexer<'b> {
         let mut lex = Lexer {
             curr: None,
-            curr_pos: None,
+            curr_pos: Some(0),
             last: None,
             last_pos: None,
             next: None,
@@ -35,26 +35,19 @@ impl<'a> Lexer<'a> {
     /// Bumper function advances to the next char
     fn bump(&mut self) {
 
-        // advance all pointers to the next char
-        self.last = self.curr;
-        self.curr = self.next;
-        self.next = self.chs.next();
-
-
         // Advance last_pos to position of current char
         self.last_pos = self.curr_pos;
 
         // Next position is current position plus the utf8 length
-        // of the next character
-        match self.next {
-            Some(c) => {
-                self.curr_pos = match self.curr_pos {
-                    Some(n) => Some(n + c.len_utf8()),
-                    None => Some(c.len_utf8()) // Start at pos 0
-                }
-            }
-            _ => {}
-        };
+        // of the current character
+        if let Some(c) = self.curr {
+            self.curr_pos = self.curr_pos.map(|n| n + c.len_utf8());
+        }
+
+        // advance all pointers to the next char
+        self.last = self.curr;
+        self.curr = self.next;
+        self.next = self.chs.next();
     }
 
     /// Double bump
@@ -105,21 +98,19 @@ impl<'a> Lexer<'a> {
 
     fn scan_lit(&mut self) -> Result<String, LexError> {
         let mut s = String::new();
+        // the literal has to be closed by the same quotation mark
+        let quote = self.curr.unwrap_or('\'');
         self.bump(); // To first char of literal
         loop {
-            if self.curr.is_some() {
-                match self.curr.unwrap_or(' ') {
-                    c @ '\'' |
-                    c @ '"' => {
-                        break
-                    }
-                    c @ _ => {
-                        s.push(c);
-                    }
-
-                }
-            } else {
-                return Err(LexError::UnclosedQuotationmark)
+            match self.curr {
+                // two quotation marks in a row are an escaped quotation mark
+                Some(c) if c == quote && self.next == Some(quote) => {
+                    s.push(c);
+                    self.bump();
+                },
+                Some(c) if c == quote => break,
+                Some(c) => s.push(c),
+                None => return Err(LexError::UnclosedQuotationmark),
             }
             self.bump();
         }
@@ -127,6 +118,38 @@ impl<'a> Lexer<'a> {
         Ok(s)
     }
 
+    /// Skips a `--` or `#` comment up to the end of the line
+    fn skip_line_comment(&mut self) {
+        while self.curr.is_some() && self.curr != Some('\n') {
+            self.bump();
+        }
+    }
+
+    /// Skips a `/* */` comment, which may contain other block comments
+    fn skip_block_comment(&mut self) -> Result<(), LexError> {
+        let mut depth = 0;
+        loop {
+            match (self.curr, self.next) {
+                (Some('/'), Some('*')) => {
+                    depth += 1;
+                    self.dbump();
+                },
+                (Some('*'), Some('/')) => {
+                    depth -= 1;
+                    self.dbump();
+                    if depth == 0 {
+                        return Ok(())
+                    }
+                },
+                (Some(_), _) => self.bump(),
+                (None, _) => return Err(LexError::UnclosedComment(Span {
+                    lo: self.span_start.unwrap(),
+                    hi: self.curr_pos.unwrap()
+                })),
+            }
+        }
+    }
+
     /// Skips all the whitespaces
     fn skip_whitespace(&mut self) {
         while is_whitespace(self.curr.unwrap_or('x')) {
@@ -134,33 +157,23 @@ impl<'a> Lexer<'a> {
         }
     }
 
-    /// Returns next token that is not a whitespace
+    /// Returns next token that is not a whitespace, skipping any number of
+    /// whitespace tokens (including none)
     pub fn next_real(&mut self) -> Result<Option<TokenSpan>, LexError> {
-        let tokspanop = try!(self.next());
-        let  wspace = match tokspanop {
-            Some(ref tokspan) => {
-                match tokspan.tok {
-                    Token::Whitespace => true,
-                    _ => false
-                }
-
+        loop {
+            match try!(self.next()) {
+                Some(TokenSpan { tok: Token::Whitespace, .. }) => continue,
+                tokspanop => return Ok(tokspanop),
             }
-            _ => false,
-        };
-
-        if wspace {
-            self.next()
-        } else {
-            Ok(tokspanop)
         }
     }
 
 }
 
-/// Checks for whitespace/line break/tab
+/// Checks for whitespace/line break/carriage return/tab
 fn is_whitespace(c: char) -> bool {
     match c {
-        ' ' | '\n' | '\t' => true,
+        ' ' | '\n' | '\r' | '\t' => true,
         _ => false
     }
 }
@@ -196,14 +209,29 @@ impl<'a> Lexer<'a> {
             // Lit Num
             '0' ... '9' => {
                 let n = self.scan_nums();
-                if let Ok(i) = n.parse::<i64>() {
-                    Token::Literal(Lit::Int(i))
-                } else {
-                    if let Ok(f) = n.parse::<f64>() {
-                        Token::Literal(Lit::Float(f))
+                let span = Span {
+                    lo: self.span_start.unwrap(),
+                    hi: self.curr_pos.unwrap()
+                };
+                match self.curr {
+                    // a name starting with a digit, the parser rejects it
+                    Some('a' ... 'z') | Some('A' ... 'Z') | Some('_') if !n.contains('.') =>
+                        Token::Word(n + &self.scan_words()),
+                    // numbers like 007 are not allowed, but 0 and 0.5 are
+                    _ if n.len() > 1 && n.starts_with('0') && !n.starts_with("0.") =>
+                        return Err(LexError::LeadingZero(span)),
+                    _ => if let Ok(i) = n.parse::<i64>() {
+                        Token::Literal(Value::Int(i))
+                    } else if !n.contains('.') {
+                        // only digits, but too big for an integer
+                        return Err(LexError::NumberOverflow(span))
                     } else {
-                        Token::Unknown
-                    }
+                        if let Ok(f) = n.parse::<f64>() {
+                            Token::Literal(Value::Float(f))
+                        } else {
+                            Token::Unknown
+                        }
+                    },
                 }
             },
 
@@ -231,11 +259,11 @@ impl<'a> Lexer<'a> {
             //     Token::Bang
             // },
 
-            // //question marks
-            // '?' => {
-            //     self.bump();
-            //     Token::QMark
-            // },
+            // question marks
+            '?' => {
+                self.bump();
+                Token::QMark
+            },
 
             // ParenOp
             '(' => {
@@ -250,9 +278,28 @@ impl<'a> Lexer<'a> {
             },
 
             // Literals
-            '\'' | '"' => {
+            '\'' => {
                 let l = try!(self.scan_lit());
-                Token::Literal(Lit::String(l))
+                Token::Literal(Value::Str(l))
+            },
+
+            // Quoted identifiers, the parser never reads them as keywords
+            '"' | '`' => {
+                let ident = try!(self.scan_lit().map_err(|_| LexError::UnclosedIdentifier(Span {
+                    lo: self.span_start.unwrap(),
+                    hi: self.curr_pos.unwrap()
+                })));
+                let span = Span {
+                    lo: self.span_start.unwrap(),
+                    hi: self.curr_pos.unwrap()
+                };
+                // names are used for files and folders of the storage
+                if ident.is_empty() || ident == "." || ident == ".." ||
+                    ident.contains(|c| c == '/' || c == '\\' || c == '\x00')
+                {
+                    return Err(LexError::InvalidIdentifier(span))
+                }
+                Token::Word(ident)
             },
 
             // Equ
@@ -297,6 +344,22 @@ impl<'a> Lexer<'a> {
                 Token::Add
             },
 
+            // Comments are treated like whitespaces
+            '-' if nexchar == '-' => {
+                self.skip_line_comment();
+                Token::Whitespace
+            },
+
+            '#' => {
+                self.skip_line_comment();
+                Token::Whitespace
+            },
+
+            '/' if nexchar == '*' => {
+                try!(self.skip_block_comment());
+                Token::Whitespace
+            },
+
             // Sub
             '-' => {
                 self.bump();
@@ -327,10 +390,14 @@ impl<'a> Lexer<'a> {
                 Token::Whitespace
             },
 
-            // Default: everything else
+            // Default: everything else is not part of the language. The lexer
+            // skips the symbol, so lexing can go on after the error
             _ => {
                 self.bump();
-                Token::Unknown
+                return Err(LexError::UnknownSymbol(Span {
+                    lo: self.span_start.unwrap(),
+                    hi: self.curr_pos.unwrap()
+                }))
             }
 
         };
@@ -348,5 +415,11 @@ impl<'a> Lexer<'a> {
 
 #[derive(PartialEq, Debug)]
 pub enum LexError {
-    UnclosedQuotationmark
+    UnclosedQuotationmark,
+    LeadingZero(Span),
+    NumberOverflow(Span),
+    UnknownSymbol(Span),
+    UnclosedComment(Span),
+    UnclosedIdentifier(Span),
+    InvalidIdentifier(Span),
 }
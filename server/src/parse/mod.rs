@@ -10,13 +10,11 @@ pub mod lex;
 pub mod parser;
 pub mod tests;
 pub use self::parser::Parser;
+pub use self::token::Span;
 
-/// Represents a substring in the query string in byte indices.
-#[derive(Debug, PartialEq)]
-pub struct Span {
-    pub lo: usize,
-    pub hi: usize,
-}
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use storage::cache::LruCache;
 
 /// Main function of this module: Takes a sql query as string and returns
 /// the parsed AST.
@@ -25,3 +23,117 @@ pub fn parse(query: &str) -> Result<ast::Query,parser::ParseError> {
     Parser::create(query).parse()
 
 }
+
+/// The parsed queries of a server by their exact text, so queries that are
+/// sent again are not parsed again. Shared by all connections.
+pub struct ParseCache {
+    queries: Mutex<LruCache<String, ast::Query>>,
+    hits: AtomicUsize,
+    parsed: AtomicUsize,
+}
+
+impl ParseCache {
+    /// Keeps at most `size` queries, 0 disables the cache
+    pub fn new(size: usize) -> ParseCache {
+        ParseCache {
+            queries: Mutex::new(LruCache::new(size)),
+            hits: AtomicUsize::new(0),
+            parsed: AtomicUsize::new(0),
+        }
+    }
+
+    /// Like `parse`, but a query is only parsed if it is not cached yet.
+    /// Queries that fail to parse are not cached.
+    pub fn parse(&self, query: &str) -> Result<ast::Query, parser::ParseError> {
+        if let Some(tree) = self.queries.lock().unwrap().get(&query.to_string()) {
+            self.hits.fetch_add(1, Ordering::SeqCst);
+            return Ok(tree.clone())
+        }
+
+        // other connections may use the cache while this query is parsed
+        self.parsed.fetch_add(1, Ordering::SeqCst);
+        let tree = try!(parse(query));
+        self.queries.lock().unwrap().insert(query.to_string(), tree.clone());
+        Ok(tree)
+    }
+
+    /// The number of queries that were taken from the cache
+    pub fn hits(&self) -> usize {
+        self.hits.load(Ordering::SeqCst)
+    }
+
+    /// The number of queries that had to be parsed
+    pub fn parsed(&self) -> usize {
+        self.parsed.load(Ordering::SeqCst)
+    }
+}
+
+/// Returns true if the error only means that the query ended too early, so
+/// it may still become valid with more input (e.g. the next line in a shell).
+pub fn is_recoverable(error: &parser::ParseError) -> bool {
+    match error {
+        &parser::ParseError::UnexpectedEoq |
+        &parser::ParseError::LexError(lex::LexError::UnclosedQuotationmark) |
+        &parser::ParseError::LexError(lex::LexError::UnclosedComment(_)) |
+        &parser::ParseError::LexError(lex::LexError::UnclosedIdentifier(_)) => true,
+        _ => false,
+    }
+}
+
+/// Parses a script of semicolon separated queries. On error the index of
+/// the failing statement is returned with the error.
+pub fn parse_script(query: &str) -> Result<Vec<ast::Query>, (usize, parser::ParseError)> {
+    Parser::create(query).parse_script()
+}
+
+/// Splits a script of semicolon separated queries into the text of each
+/// query, which is parsed to find the semicolons ending them. On error the
+/// index of the failing statement is returned with the error.
+pub fn split_script(query: &str) -> Result<Vec<&str>, (usize, parser::ParseError)> {
+    let queries = try!(Parser::create(query).parse_script_spans());
+    Ok(queries.iter().map(|&(_, ref span)| &query[span.lo..span.hi]).collect())
+}
+
+/// Returns the number of `?` placeholders in a prepared statement.
+pub fn count_params(query: &str) -> Result<usize, parser::ParseError> {
+    Ok(try!(placeholders(query)).len())
+}
+
+/// Replaces the `?` placeholders of a prepared statement with the given
+/// literals. Strings are quoted and escaped, so a parameter is always one
+/// literal and can not change the query.
+pub fn bind_params(query: &str, params: &[token::Value])
+    -> Result<String, parser::ParseError>
+{
+    let spans = try!(placeholders(query));
+    if spans.len() != params.len() {
+        return Err(parser::ParseError::ParamCountMissmatch)
+    }
+
+    let mut bound = String::new();
+    let mut pos = 0;
+    for (span, param) in spans.iter().zip(params) {
+        bound.push_str(&query[pos..span.lo]);
+        let param = param.to_string();
+        // `-?` with a negative number must not turn into a `--` comment
+        if bound.ends_with('-') && param.starts_with('-') {
+            bound.push(' ');
+        }
+        bound.push_str(&param);
+        pos = span.hi;
+    }
+    bound.push_str(&query[pos..]);
+    Ok(bound)
+}
+
+/// Returns the spans of all `?` placeholders in the query.
+fn placeholders(query: &str) -> Result<Vec<Span>, parser::ParseError> {
+    let mut lexer = lex::Lexer::from_query(query);
+    let mut spans = Vec::new();
+    while let Some(ts) = try!(lexer.next_real()) {
+        if ts.tok == token::Token::QMark {
+            spans.push(ts.span);
+        }
+    }
+    Ok(spans)
+}
@@ -3,23 +3,30 @@
 
 use std::iter::Iterator;
 use super::ast::*;
-use super::token::{TokenSpan, Lit};
+use super::token::{TokenSpan, Value, Span};
 use super::lex::Lexer;
 use std::mem::swap;
 use super::token::Token;
-use super::Span;
 use super::super::storage::SqlType;
 use std::collections::HashMap;
 use super::lex;
+use std::error;
+use std::fmt;
 
 // ===========================================================================
 // Parser public functions
 // ===========================================================================
 
 
+/// Names of tables and columns are limited to this number of chars
+pub const MAX_IDENTIFIER_LENGTH: usize = 64;
+
 // the parser needs a Lexer that iterates through the query
+#[derive(Clone)]
 pub struct Parser<'a> {
     lexiter: Lexer<'a>,
+    // the query, used to show the text of invalid tokens in errors
+    query: &'a str,
 
     last: Option<TokenSpan>,
     // the current token given by the lexer
@@ -33,7 +40,7 @@ impl<'a> Parser<'a> {
     /// Constructs a Parser for the given query.
     pub fn create(query: &'a str) -> Parser<'a> {
         let l = Lexer::from_query(query);
-        let mut p = Parser { lexiter: l, last: None, curr: None, peek: None };
+        let mut p = Parser { lexiter: l, query: query, last: None, curr: None, peek: None };
         // Sets initial position of lexer and curr/peek
         p.bump();
         p.bump();
@@ -42,7 +49,41 @@ impl<'a> Parser<'a> {
 
     /// Parses the given query into an AST
     pub fn parse(&mut self) -> Result<Query, ParseError> {
-        // deletes Whitespaces in the beginning of Query
+        let query = try!(self.parse_statement());
+        self.return_query_ast(query)
+    }
+
+    /// Parses a script of queries separated by semicolons. Empty statements
+    /// are skipped. On error the index of the failing statement is returned
+    /// together with the error.
+    pub fn parse_script(&mut self) -> Result<Vec<Query>, (usize, ParseError)> {
+        let queries = try!(self.parse_script_spans());
+        Ok(queries.into_iter().map(|(query, _)| query).collect())
+    }
+
+    /// Like `parse_script`, but every query is returned with its span in the
+    /// script, which does not include the semicolon.
+    pub fn parse_script_spans(&mut self) -> Result<Vec<(Query, Span)>, (usize, ParseError)> {
+        let mut queries = Vec::new();
+        loop {
+            while self.expect_token(&[Token::Semi]).is_ok() {
+                try!(self.bump().map_err(|e| (queries.len(), e)));
+            }
+            let lo = match self.curr {
+                Some(ref token) => token.span.lo,
+                None => break,
+            };
+            let index = queries.len();
+            let query = try!(self.parse_statement().map_err(|e| (index, e)));
+            let hi = try!(self.curr_span().map_err(|e| (index, e))).hi;
+            try!(self.end_of_statement().map_err(|e| (index, e)));
+            queries.push((query, Span { lo: lo, hi: hi }));
+        }
+        Ok(queries)
+    }
+
+    // Parses a single statement, curr is set to its last token afterwards
+    fn parse_statement(&mut self) -> Result<Query, ParseError> {
 
         // first token is checked if it's a keyword using expect_keyword()
         let keywords = &[Keyword::Create, Keyword::Drop, Keyword::Alter,
@@ -57,49 +98,49 @@ impl<'a> Parser<'a> {
             // Create-Query
             Keyword::Create => {
                 let query = Query::DefStmt(DefStmt::Create(try!(self.parse_create_stmt())));
-                Ok(try!(self.return_query_ast(query)))
+                Ok(query)
             },
             // Alter-Query
             Keyword::Alter => {
                 let query = Query::DefStmt(DefStmt::Alter(
                     try!(self.parse_alt_stmt())
                     ));
-                Ok(try!(self.return_query_ast(query)))
+                Ok(query)
             },
             // Drop-Query
             Keyword::Drop => {
                 let query = Query::DefStmt(DefStmt::Drop(
                     try!(self.parse_drop_stmt())
                     ));
-                Ok(try!(self.return_query_ast(query)))
+                Ok(query)
             },
             // Use-Query
             Keyword::Use => {
                 let query = Query::ManipulationStmt(ManipulationStmt::Use(
                     try!(self.parse_use_stmt())
                     ));
-                Ok(try!(self.return_query_ast(query)))
+                Ok(query)
             }
             // Insert-Query
             Keyword::Insert => {
                 let query = Query::ManipulationStmt(ManipulationStmt::Insert(
                     try!(self.parse_insert_stmt())
                     ));
-                Ok(try!(self.return_query_ast(query)))
+                Ok(query)
             }
             //Update-Query
             Keyword::Update => {
                 let query = Query::ManipulationStmt(ManipulationStmt::Update(
                     try!(self.parse_update_stmt())
                     ));
-                Ok(try!(self.return_query_ast(query)))
+                Ok(query)
             },
             // Delete-Query
             Keyword::Delete => {
                 let query = Query::ManipulationStmt(ManipulationStmt::Delete(
                     try!(self.parse_delete_stmt())
                     ));
-                Ok(try!(self.return_query_ast(query)))
+                Ok(query)
             }
             //Describe-Query
             Keyword::Describe => {
@@ -107,14 +148,14 @@ impl<'a> Parser<'a> {
                 let query = Query::ManipulationStmt(ManipulationStmt::Describe(
                     try!(self.expect_word(false))
                     ));
-                Ok(try!(self.return_query_ast(query)))
+                Ok(query)
             }
             //Select-Query
             Keyword::Select => {
                 let query = Query::ManipulationStmt(ManipulationStmt::Select(
                     try!(self.parse_select_stmt())
                     ));
-                Ok(try!(self.return_query_ast(query)))
+                Ok(query)
             }
 
             // Unknown Error
@@ -143,8 +184,14 @@ impl<'a> Parser<'a> {
             view_check = true;
         }
 
-        match try!(self.expect_keyword(&[Keyword::Table, Keyword::Database, Keyword::View,
-            Keyword::Or])) {
+        // `or replace` is only allowed for views
+        let expected = if view_check {
+            vec![Keyword::View]
+        } else {
+            vec![Keyword::Table, Keyword::Database, Keyword::View]
+        };
+
+        match try!(self.expect_keyword(&expected)) {
             // Create the table subtree
             Keyword::Table => Ok(CreateStmt::Table(try!(self.parse_create_table_stmt()))),
             // Create Database subtree
@@ -153,19 +200,7 @@ impl<'a> Parser<'a> {
                 Ok(CreateStmt::Database(try!(self.expect_word(false))))
             },
             // Create View subtree
-            Keyword::View => {
-                try!(self.bump());
-                let name = try!(self.expect_word(false));
-                try!(self.bump());
-                try!(self.expect_keyword(&[Keyword::As]));
-                try!(self.bump());
-                try!(self.expect_keyword(&[Keyword::Select]));
-                Ok(CreateStmt::View(CreateViewStmt {
-                    name: name,
-                    opt: view_check,
-                    sel: try!(self.parse_select_stmt()),
-                }))
-            },
+            Keyword::View => Ok(CreateStmt::View(try!(self.parse_create_view_stmt(view_check)))),
             _ => Err(ParseError::UnknownError),
         }
     }
@@ -181,17 +216,32 @@ impl<'a> Parser<'a> {
             tid: try!(self.expect_word(false)),
             cols: Vec::<ColumnInfo>::new()
         };
-        try!(self.bump());
         // if there is a ParenOp token.....
-        if self.curr.is_none() {
+        if self.peek.is_none() || self.check_next_token(&[Token::Semi]) {
             return Ok(table_info)
         }
+        try!(self.bump());
         try!(self.expect_token(&[Token::ParenOp]));
         // ...call parse_create_column_vec to generate the column vector subtree
         table_info.cols = try!(self.parse_create_column_vec());
         Ok(table_info)
     }
 
+    // Parses the tokens for the create view subtree
+    fn parse_create_view_stmt(&mut self, replace: bool) -> Result<CreateViewStmt, ParseError> {
+        try!(self.bump());
+        let name = try!(self.expect_word(false));
+        try!(self.bump());
+        try!(self.expect_keyword(&[Keyword::As]));
+        try!(self.bump());
+        try!(self.expect_keyword(&[Keyword::Select]));
+        Ok(CreateViewStmt {
+            name: name,
+            opt: replace,
+            sel: try!(self.parse_select_stmt()),
+        })
+    }
+
     // Parses the tokens for the column vector subtree
     fn parse_create_column_vec(&mut self) -> Result<Vec<ColumnInfo>, ParseError> {
         // Convention: Every method must use bump to
@@ -208,7 +258,17 @@ impl<'a> Parser<'a> {
             // Check if there is a Comma seperating two columns or a ParenCl
             // ending the vectorparsing
             match try!(self.expect_token(&[Token::Comma, Token::ParenCl])) {
-                Token::Comma => try!(self.bump()),
+                Token::Comma => {
+                    try!(self.bump());
+                    // a comma has to be followed by another column
+                    if self.expect_token(&[Token::ParenCl]).is_ok() {
+                        let span = match self.last {
+                            Some(ref token) => Span { lo: token.span.lo, hi: token.span.hi },
+                            None => return Err(ParseError::UnknownError),
+                        };
+                        return Err(ParseError::TrailingComma(span));
+                    }
+                },
                 _ => (),
             };
         }
@@ -229,10 +289,18 @@ impl<'a> Parser<'a> {
     // Parses table to modify and subsequent operations
     fn parse_alter_table_stmt(&mut self) -> Result<AlterTableStmt, ParseError> {
         try!(self.bump());
-        let alt_table_stmt = AlterTableStmt {
+        let mut alt_table_stmt = AlterTableStmt {
             tid: try!(self.expect_word(false)),
-            op: try!(self.parse_alter_op())
+            ops: vec![try!(self.parse_alter_op())]
         };
+        // further operations are separated by commas
+        while self.check_next_token(&[Token::Comma]) {
+            try!(self.bump());
+            if self.peek.is_none() || self.check_next_token(&[Token::Semi]) {
+                return Err(ParseError::TrailingComma(try!(self.curr_span())));
+            }
+            alt_table_stmt.ops.push(try!(self.parse_alter_op()));
+        }
         Ok(alt_table_stmt)
     }
 
@@ -240,7 +308,9 @@ impl<'a> Parser<'a> {
     // datatype if necessary
     fn parse_alter_op(&mut self) -> Result<AlterOp, ParseError> {
         try!(self.bump());
-        match try!(self.expect_keyword(&[Keyword::Add, Keyword::Drop, Keyword::Modify])) {
+        match try!(self.expect_keyword(&[Keyword::Add, Keyword::Drop, Keyword::Modify,
+            Keyword::Rename]))
+        {
             Keyword::Add => {
                 try!(self.bump());
                 Ok(AlterOp::Add(try!(self.expect_column_info())))
@@ -257,6 +327,24 @@ impl<'a> Parser<'a> {
                 try!(self.bump());
                 Ok(AlterOp::Modify(try!(self.expect_column_info())))
             },
+            // `rename to t2` renames the table, `rename column a to b` a column
+            Keyword::Rename => {
+                try!(self.bump());
+                match try!(self.expect_keyword(&[Keyword::To, Keyword::Column])) {
+                    Keyword::To => {
+                        try!(self.bump());
+                        Ok(AlterOp::RenameTable(try!(self.expect_word(false))))
+                    },
+                    _ => {
+                        try!(self.bump());
+                        let from = try!(self.expect_word(true));
+                        try!(self.bump());
+                        try!(self.expect_keyword(&[Keyword::To]));
+                        try!(self.bump());
+                        Ok(AlterOp::RenameColumn { from: from, to: try!(self.expect_word(true)) })
+                    },
+                }
+            },
             _ => Err(ParseError::UnknownError),
         }
     }
@@ -352,8 +440,8 @@ impl<'a> Parser<'a> {
     }
 
     // Parses i.val of parse_insert_stmt
-    fn parse_insert_stmt_value(&mut self) -> Result<Vec<Lit>, ParseError> {
-        let mut res_vec = Vec::<Lit>::new();
+    fn parse_insert_stmt_value(&mut self) -> Result<Vec<Value>, ParseError> {
+        let mut res_vec = Vec::<Value>::new();
         match try!(self.expect_keyword(&[Keyword::Values])) {
             Keyword::Values => (),
             _ => return Err(ParseError::UnknownError),
@@ -396,32 +484,31 @@ impl<'a> Parser<'a> {
         while !done
         {
             try!(self.bump());
-            //parse optional alias
-            let mut alias = None;
-            if self.check_next_token(&[Token::Dot]) {
-                alias = Some(try!(self.expect_word(false)));
-                try!(self.bump());
-                try!(self.bump());
-            };
-            let column = try!(self.expect_word(true));
+            // `set where ...` or a trailing comma: no assignment given
+            if self.expect_keyword(&[Keyword::Where]).is_ok() {
+                return Err(ParseError::MissingAssignment(try!(self.curr_span())));
+            }
+            let column = try!(self.parse_column_ref());
             try!(self.bump());
             try!(self.expect_token(&[Token::Equ]));
             try!(self.bump());
             let value = try!(self.expect_literal());
-            try!(self.bump());
             setvec.push(Condition {
-                aliascol: alias,
-                col: column,
+                aliascol: column.table,
+                col: column.name,
                 op: CompType::Equ,
                 aliasrhs: None,
                 rhs: CondType::Literal(value)
             } );
-            if !self.expect_token(&[Token::Comma]).is_ok() {
+            if self.check_next_token(&[Token::Comma]) {
+                try!(self.bump());
+            } else {
                 done = true;
             }
         }
         Ok(UpdateStmt { tid: tableid, alias: aliasmap, set: setvec, conds:
-                if self.expect_keyword(&[Keyword::Where]).is_ok() {
+                if self.check_next_keyword(&[Keyword::Where]) {
+                    try!(self.bump());
                     Some(try!(self.parse_where_part()))
                 } else {
                     None
@@ -437,19 +524,17 @@ impl<'a> Parser<'a> {
         try!(self.bump());
         let tableid = try!(self.expect_word(false));
         let mut aliasmap = HashMap::new();
-        if !self.check_next_keyword(&[Keyword::Where]) {
+        // optional table alias
+        if self.peek.is_some() && !self.check_next_keyword(&[Keyword::Where])
+        && !self.check_next_token(&[Token::Semi]) {
             try!(self.bump());
-            match self.expect_word(false) {
-                Err(ParseError::UnexpectedEoq) => (),
-                Err(err) => return Err(err),
-                Ok(s) => { aliasmap.insert(s.clone(), tableid.clone()); () },
-            }
+            aliasmap.insert(try!(self.expect_word(false)), tableid.clone());
+        }
+        let mut conditiontree = None;
+        if self.check_next_keyword(&[Keyword::Where]) {
+            try!(self.bump());
+            conditiontree = Some(try!(self.parse_where_part()));
         }
-        try!(self.bump());
-        let conditiontree = match self.expect_keyword(&[Keyword::Where]) {
-            Ok(Keyword::Where) => Some(try!(self.parse_where_part())),
-            _ => None,
-        };
         Ok(DeleteStmt { tid: tableid, alias: aliasmap, cond: conditiontree } )
     }
 
@@ -457,29 +542,45 @@ impl<'a> Parser<'a> {
     fn parse_select_stmt(&mut self) -> Result<SelectStmt, ParseError>{
         let mut targetvec = Vec::new();
         let mut done = false;
+        // optional distinct keyword
+        let distinct = self.check_next_keyword(&[Keyword::Distinct]);
+        if distinct {
+            try!(self.bump());
+        }
         // parsing optional targets, at least one
         while !done
         {
             // optional table alias
             try!(self.bump());
-            let mut targetalias = None;
-            if self.check_next_token(&[Token::Dot]) {
-                targetalias = Some(try!(self.expect_word(false)));
-                try!(self.bump());
-                try!(self.bump());
-            };
-            // required target column
-            let targetcol = match self.expect_token(&[Token::Star]) {
-                Err(err) => Col::Specified(try!(self.expect_word(true))),
-                Ok(Token::Star) => Col::Every,
-                _ => return Err(ParseError::UnknownError) ,
+            // at least one target is required in front of the from keyword
+            if self.expect_keyword(&[Keyword::From]).is_ok() {
+                return Err(ParseError::MissingTarget(try!(self.curr_span())));
+            }
+            let targetalias = try!(self.parse_table_prefix());
+            let (targetalias, targetcol) = if self.expect_token(&[Token::Star]).is_ok() {
+                (targetalias, Col::Every)
+            } else {
+                // required target column, which may be named like a keyword
+                let first = match targetalias {
+                    Some(table) => Expr::Column(ColumnRef {
+                        table: Some(table),
+                        name: try!(self.expect_word(true)),
+                    }),
+                    None => try!(self.parse_leading_operand()),
                 };
+                match try!(self.parse_expr_rest(first)) {
+                    Expr::Column(col) => (col.table, Col::Specified(col.name)),
+                    Expr::Aggregate(aggregate) => (None, Col::Aggregate(aggregate)),
+                    expr => (None, Col::Expr(expr)),
+                }
+            };
             try!(self.bump());
             // optional target column rename
             let mut targetrename = None;
             if self.expect_keyword(&[Keyword::As]).is_ok() {
                 try!(self.bump());
-                targetrename = Some(try!(self.expect_word(true)));
+                // a keyword here means the alias is missing, e.g. `select a as from`
+                targetrename = Some(try!(self.expect_word(false)));
                 try!(self.bump());
             }
             targetvec.push(Target { alias: targetalias, col: targetcol, rename: targetrename} );
@@ -492,26 +593,32 @@ impl<'a> Parser<'a> {
         try!(self.expect_keyword(&[Keyword::From]));
         let mut tidvec = Vec::new();
         let mut aliasmap = HashMap::new();
+        let mut joinvec = Vec::new();
         done = false;
         // parsing optional tables
         while !done
         {
             try!(self.bump());
-            let tableid = try!(self.expect_word(false));
-            if !self.check_next_keyword(&[Keyword::Where, Keyword::Limit,
-                Keyword::Group, Keyword::Order])
-            && !self.check_next_token(&[Token::Comma]) {
+            let tableid = try!(self.parse_select_table(&mut aliasmap));
+            // optional joins: [inner] join <table> [alias] on <conditions>
+            let mut left = tableid.clone();
+            while self.check_next_keyword(&[Keyword::Join, Keyword::Inner]) {
                 try!(self.bump());
-                match self.expect_word(false) {
-                    Err(ParseError::UnexpectedEoq) => (),
-                    Err(err) => return Err(err),
-                    Ok(s) => { aliasmap.insert(s.clone(), tableid.clone()); () },
+                if self.expect_keyword(&[Keyword::Inner]).is_ok() {
+                    try!(self.bump());
+                    try!(self.expect_keyword(&[Keyword::Join]));
                 }
+                try!(self.bump());
+                let right = try!(self.parse_select_table(&mut aliasmap));
+                try!(self.bump());
+                try!(self.expect_keyword(&[Keyword::On]));
+                let on = try!(self.parse_where_part());
+                joinvec.push(Join { left: left, right: right.clone(), on: on });
+                left = right;
             }
             tidvec.push(tableid);
             if !self.check_next_token(&[Token::Comma]) {
                 done = true;
-                try!(self.bump());
             } else {
                 try!(self.bump());
             }
@@ -519,29 +626,40 @@ impl<'a> Parser<'a> {
         let mut conditions = None;
         let mut order_vec = Vec::new();
         // optional where statement
-        if self.expect_keyword(&[Keyword::Where]).is_ok() {
+        if self.check_next_keyword(&[Keyword::Where]) {
+            try!(self.bump());
             conditions = Some(try!(self.parse_where_part()));
         }
-        if self.expect_keyword(&[Keyword::Group]).is_ok() {
+        let mut group_by = None;
+        if self.check_next_keyword(&[Keyword::Group]) {
+            try!(self.bump());
             try!(self.bump());
             try!(self.expect_keyword(&[Keyword::By]));
+            let mut group_vec = Vec::new();
+            loop {
+                try!(self.bump());
+                group_vec.push(try!(self.parse_column_ref()));
+                if !self.check_next_token(&[Token::Comma]) {
+                    break;
+                }
+                try!(self.bump());
+            }
+            group_by = Some(group_vec);
+        }
+        let mut having = None;
+        if self.check_next_keyword(&[Keyword::Having]) {
             try!(self.bump());
-            return Err(ParseError::DebugError("GroupBy part needs implementation!".to_string()));
+            having = Some(try!(self.parse_where_part()));
         }
-        if self.expect_keyword(&[Keyword::Order]).is_ok() {
+        if self.check_next_keyword(&[Keyword::Order]) {
+            try!(self.bump());
             try!(self.bump());
             try!(self.expect_keyword(&[Keyword::By]));
             let mut done = false;
             while !done
             {
                 try!(self.bump());
-                let mut o_alias = None;
-                if self.check_next_token(&[Token::Dot]) {
-                    o_alias = Some(try!(self.expect_word(false)));
-                    try!(self.bump());
-                    try!(self.bump());
-                };
-                let o_col = try!(self.expect_word(true));
+                let o_col = try!(self.parse_column_ref());
                 let mut o_order = Some(Order::Asc);
                 if self.check_next_keyword(&[Keyword::Asc]) {
                     try!(self.bump());
@@ -550,7 +668,6 @@ impl<'a> Parser<'a> {
                     try!(self.bump());
                 }
                 order_vec.push(Sort {
-                    alias: o_alias,
                     col: o_col,
                     order: o_order,
                 });
@@ -560,45 +677,215 @@ impl<'a> Parser<'a> {
                     try!(self.bump());
                 }
             }
-            if self.check_next_keyword(&[Keyword::Limit]) {
-                try!(self.bump());
-            }
         }
         let mut limit = None;
-        if self.expect_keyword(&[Keyword::Limit]).is_ok() {
+        if self.check_next_keyword(&[Keyword::Limit]) {
             try!(self.bump());
-            let tmp = match try!(self.expect_number()) {
-                Lit::Int(i) => i ,
-                _ => return Err(ParseError::LimitError) ,
-            };
+            try!(self.bump());
+            let tmp = try!(self.expect_limit_number());
             if self.check_next_token(&[Token::Comma]) {
                 try!(self.bump());
                 try!(self.bump());
-                let count = match try!(self.expect_number()) {
-                    Lit::Int(i) => i ,
-                     _ => return Err(ParseError::LimitError) ,
-                };
+                let count = try!(self.expect_limit_number());
                 limit = Some(Limit { count: Some(count), offset: Some(tmp) } ) ;
             } else {
                 limit = Some(Limit { count: Some(tmp) , offset: None} );
             };
         }
+        // optional offset, also allowed without a limit
+        if self.check_next_keyword(&[Keyword::Offset]) {
+            try!(self.bump());
+            let span = try!(self.curr_span());
+            try!(self.bump());
+            let offset = try!(self.expect_limit_number());
+            limit = match limit {
+                // `limit 30,3 offset 5` has two offsets
+                Some(Limit { offset: Some(_), .. }) => return Err(ParseError::LimitError(span)),
+                Some(Limit { count, .. }) => Some(Limit { count: count, offset: Some(offset) }),
+                None => Some(Limit { count: None, offset: Some(offset) }),
+            };
+        }
         Ok(SelectStmt {
             target: targetvec,
             tid: tidvec,
             alias: aliasmap,
+            joins: joinvec,
             cond: conditions,
+            group_by: group_by,
+            having: having,
+            distinct: distinct,
             spec_op: None,
             order : order_vec,
             limit: limit,
         })
     }
 
+    // parses a table of the from list with its optional alias
+    fn parse_select_table(&mut self, aliasmap: &mut HashMap<String, String>)
+        -> Result<String, ParseError>
+    {
+        let tableid = try!(self.expect_word(false));
+        if self.peek.is_some() && !self.check_next_keyword(&[Keyword::Where, Keyword::Limit,
+            Keyword::Group, Keyword::Having, Keyword::Order, Keyword::Offset, Keyword::Join,
+            Keyword::Inner, Keyword::On])
+        && !self.check_next_token(&[Token::Comma, Token::Semi]) {
+            try!(self.bump());
+            aliasmap.insert(try!(self.expect_word(false)), tableid.clone());
+        }
+        Ok(tableid)
+    }
 
 // ============================================================================
 // Utility Functions
 // ============================================================================
 
+    // returns the aggregate function, if curr is the name of one followed by
+    // a parenthesis. The names are no keywords, so `count` remains a column.
+    fn aggregate_func(&self) -> Option<AggregateFunc> {
+        let name = match self.curr {
+            Some(ref token) if !self.is_quoted(token) => match token.tok {
+                Token::Word(ref s) => s.to_lowercase(),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        if !self.check_next_token(&[Token::ParenOp]) {
+            return None
+        }
+        match &name[..] {
+            "count" => Some(AggregateFunc::Count),
+            "sum" => Some(AggregateFunc::Sum),
+            "avg" => Some(AggregateFunc::Avg),
+            "min" => Some(AggregateFunc::Min),
+            "max" => Some(AggregateFunc::Max),
+            _ => None,
+        }
+    }
+
+    // parses the parenthesized argument of an aggregate function, curr is
+    // set to the closing parenthesis
+    fn parse_aggregate(&mut self, func: AggregateFunc) -> Result<Aggregate, ParseError> {
+        try!(self.bump());
+        try!(self.bump());
+        let arg = if self.expect_token(&[Token::Star]).is_ok() {
+            if func != AggregateFunc::Count {
+                return Err(ParseError::InvalidAggregate(try!(self.curr_span())))
+            }
+            AggregateArg::Star
+        } else {
+            AggregateArg::Column(try!(self.parse_column_ref()))
+        };
+        try!(self.bump());
+        try!(self.expect_token(&[Token::ParenCl]).map_err(|e| match e {
+            ParseError::WrongToken { span, .. } => ParseError::MissingParenthesis(span),
+            _ => e,
+        }));
+        Ok(Aggregate { func: func, arg: arg })
+    }
+
+    // parses a column with an optional table in front of it, `name` or
+    // `table.name`. curr is set to the column name.
+    fn parse_column_ref(&mut self) -> Result<ColumnRef, ParseError> {
+        let table = try!(self.parse_table_prefix());
+        Ok(ColumnRef { table: table, name: try!(self.expect_word(true)) })
+    }
+
+    // parses the optional `table.` in front of a column, curr is set to the
+    // token after the dot. Columns may be named like keywords, but one that
+    // follows a column, as in `select t. from t`, means the column is missing.
+    fn parse_table_prefix(&mut self) -> Result<Option<String>, ParseError> {
+        if !self.check_next_token(&[Token::Dot]) {
+            return Ok(None)
+        }
+        let table = try!(self.expect_word(false));
+        try!(self.bump());
+        try!(self.bump());
+        if self.expect_keyword(&[Keyword::From, Keyword::Where, Keyword::As, Keyword::And,
+            Keyword::Or, Keyword::Order, Keyword::Group, Keyword::Having, Keyword::Limit,
+            Keyword::Offset, Keyword::Join, Keyword::Inner, Keyword::On]).is_ok()
+        {
+            let span = try!(self.curr_span());
+            return Err(ParseError::NotAWord { found: self.token_text(span.lo, span.hi),
+                span: span })
+        }
+        Ok(Some(table))
+    }
+
+    // parses an arithmetic expression, `*` and `/` bind tighter than `+` and
+    // `-`. curr is set to the last token of the expression.
+    fn parse_expr(&mut self) -> Result<Expr, ParseError> {
+        let first = try!(self.parse_expr_primary());
+        self.parse_expr_rest(first)
+    }
+
+    // parses the sums following the already parsed operand `first`
+    fn parse_expr_rest(&mut self, first: Expr) -> Result<Expr, ParseError> {
+        let mut expr = try!(self.parse_expr_factors(first));
+        while self.check_next_token(&[Token::Add, Token::Sub]) {
+            try!(self.bump());
+            let op = match try!(self.expect_token(&[Token::Add, Token::Sub])) {
+                Token::Add => ArithOp::Add,
+                _ => ArithOp::Sub,
+            };
+            try!(self.bump());
+            let operand = try!(self.parse_expr_primary());
+            let rhs = try!(self.parse_expr_factors(operand));
+            expr = Expr::Binary(Box::new(expr), op, Box::new(rhs));
+        }
+        Ok(expr)
+    }
+
+    // parses the products following the already parsed operand `first`
+    fn parse_expr_factors(&mut self, first: Expr) -> Result<Expr, ParseError> {
+        let mut expr = first;
+        while self.check_next_token(&[Token::Star, Token::Div]) {
+            try!(self.bump());
+            let op = match try!(self.expect_token(&[Token::Star, Token::Div])) {
+                Token::Star => ArithOp::Mul,
+                _ => ArithOp::Div,
+            };
+            try!(self.bump());
+            let rhs = try!(self.parse_expr_primary());
+            expr = Expr::Binary(Box::new(expr), op, Box::new(rhs));
+        }
+        Ok(expr)
+    }
+
+    // parses the first operand of an expression, where a word is always a
+    // column, which may be named like a keyword, or an aggregate function
+    fn parse_leading_operand(&mut self) -> Result<Expr, ParseError> {
+        if let Some(func) = self.aggregate_func() {
+            return Ok(Expr::Aggregate(try!(self.parse_aggregate(func))))
+        }
+        match self.curr {
+            Some(TokenSpan { tok: Token::Word(_), .. }) =>
+                Ok(Expr::Column(try!(self.parse_column_ref()))),
+            _ => self.parse_expr_primary(),
+        }
+    }
+
+    // parses a column, a literal, an aggregate function or an expression in
+    // parentheses. Keywords like `true` are literals, so a missing operand as
+    // in `price * from` is reported as missing literal.
+    fn parse_expr_primary(&mut self) -> Result<Expr, ParseError> {
+        if let Some(func) = self.aggregate_func() {
+            Ok(Expr::Aggregate(try!(self.parse_aggregate(func))))
+        } else if self.expect_token(&[Token::ParenOp]).is_ok() {
+            try!(self.bump());
+            let expr = try!(self.parse_expr());
+            try!(self.bump());
+            try!(self.expect_token(&[Token::ParenCl]).map_err(|e| match e {
+                ParseError::WrongToken { span, .. } => ParseError::MissingParenthesis(span),
+                _ => e,
+            }));
+            Ok(expr)
+        } else if self.expect_word(false).is_ok() {
+            Ok(Expr::Column(try!(self.parse_column_ref())))
+        } else {
+            Ok(Expr::Literal(try!(self.expect_literal())))
+        }
+    }
+
     // sets next position for the lexer
     fn bump(&mut self) -> Result<(),ParseError> {
         swap(&mut self.last, &mut self.curr);  //  last = curr
@@ -608,52 +895,75 @@ impl<'a> Parser<'a> {
     }
     // checks, if query is ended correctly. if yes -> returns query as ast
     fn return_query_ast(&mut self, query: Query) -> Result<Query, ParseError> {
-        try!(self.bump());
+        try!(self.end_of_statement());
+        // a single trailing semicolon is allowed
+        if self.curr.is_some() {
+            try!(self.bump());
+        }
         if self.curr.is_none() {
             Ok(query)
         } else {
-            Err(ParseError::InvalidEoq)
+            Err(ParseError::TrailingTokens(try!(self.curr_span())))
         }
     }
-    // parses the where part into Conditions type
+    // checks, if the statement is followed by a semicolon or the end of query
+    fn end_of_statement(&mut self) -> Result<(), ParseError> {
+        try!(self.bump());
+        if self.curr.is_none() || self.expect_token(&[Token::Semi]).is_ok() {
+            Ok(())
+        } else {
+            Err(ParseError::TrailingTokens(try!(self.curr_span())))
+        }
+    }
+    // parses the where part into Conditions type, `and` binds tighter than `or`.
+    // Like the other parse methods, curr is set to the last token of the part.
     fn parse_where_part(&mut self) -> Result<Conditions, ParseError> {
-        let mut cond;
-        if self.check_next_token(&[Token::ParenOp]) {
+        let mut cond = try!(self.parse_where_and());
+        if self.check_next_keyword(&[Keyword::Or]) {
             try!(self.bump());
-            cond = try!(self.parse_where_part());
-            try!(self.expect_token(&[Token::ParenCl]).map_err(|e| match e {
-                ParseError::WrongToken(span) => ParseError::MissingParenthesis(span),
-                _ => e,
-            }));
-            if self.check_next_keyword(&[Keyword::Or,Keyword::And]) {
-                try!(self.bump());
-                if self.expect_keyword(&[Keyword::Or]).is_ok() {
-                    cond = Conditions::Or(
-                        Box::new(cond),Box::new(try!(self.parse_where_part())));
-                } else if self.expect_keyword(&[Keyword::And]).is_ok(){
-                    cond = Conditions::And(
-                        Box::new(cond),Box::new(try!(self.parse_where_part())));
-                };
+            cond = Conditions::Or(Box::new(cond), Box::new(try!(self.parse_where_part())));
+        }
+        Ok(cond)
+    }
+
+    // parses a chain of conditions connected by `and`
+    fn parse_where_and(&mut self) -> Result<Conditions, ParseError> {
+        let mut cond = try!(self.parse_where_primary());
+        while self.check_next_keyword(&[Keyword::And]) {
+            try!(self.bump());
+            cond = Conditions::And(Box::new(cond), Box::new(try!(self.parse_where_primary())));
+        }
+        Ok(cond)
+    }
+
+    // parses a single condition or a group of conditions in parentheses. A
+    // parenthesis may start an expression as well, as in `(a + b) > 1`, so
+    // the condition is tried if the group fails.
+    fn parse_where_primary(&mut self) -> Result<Conditions, ParseError> {
+        if self.check_next_token(&[Token::ParenOp]) {
+            let start = self.clone();
+            match self.parse_where_group() {
+                Ok(cond) => Ok(cond),
+                Err(err) => {
+                    *self = start;
+                    // the group is what was meant, if neither parses
+                    self.parse_condition().map_err(|_| err)
+                },
             }
         } else {
-            cond = Conditions::Leaf(try!(self.parse_condition()));
-            try!(self.bump());
-            while self.expect_keyword(&[Keyword::And, Keyword::Or]).is_ok() {
-                if self.expect_keyword(&[Keyword::Or]).is_ok() {
-                    cond = Conditions::Or(Box::new(cond),Box::new(try!(self.parse_where_part())));
-                } else {
-                    if self.check_next_token(&[Token::ParenOp]) {
-                        cond = Conditions::And(
-                            Box::new(cond),
-                            Box::new(try!(self.parse_where_part())));
-                    } else {
-                        cond = Conditions::And(Box::new(cond),
-                            Box::new(Conditions::Leaf(try!(self.parse_condition()))));
-                        try!(self.bump());
-                    };
-                };
-            };
+            self.parse_condition()
         }
+    }
+
+    // parses a group of conditions in parentheses
+    fn parse_where_group(&mut self) -> Result<Conditions, ParseError> {
+        try!(self.bump());
+        let cond = try!(self.parse_where_part());
+        try!(self.bump());
+        try!(self.expect_token(&[Token::ParenCl]).map_err(|e| match e {
+            ParseError::WrongToken { span, .. } => ParseError::MissingParenthesis(span),
+            _ => e,
+        }));
         Ok(cond)
     }
 
@@ -670,7 +980,7 @@ impl<'a> Parser<'a> {
              _ => return false,
         };
         let possiblekeyword = match tokenspan.tok {
-            Token::Word(ref s) => s,
+            Token::Word(ref s) if !self.is_quoted(&tokenspan) => s,
             _ => return false,
         };
         match keyword_from_string(possiblekeyword) {
@@ -678,16 +988,12 @@ impl<'a> Parser<'a> {
             None => false
         }
     }
-    // aprses a single condition
-    fn parse_condition(&mut self) -> Result<Condition, ParseError> {
+    // parses a single condition, a comparison of two expressions. A column
+    // compared with a column or a literal is a leaf, anything else a compare.
+    fn parse_condition(&mut self) -> Result<Conditions, ParseError> {
         try!(self.bump());
-        let mut alias = None;
-        if self.check_next_token(&[Token::Dot]) {
-            alias = Some(try!(self.expect_word(false)));
-            try!(self.bump());
-            try!(self.bump());
-        };
-        let columnname = try!(self.expect_word(true));
+        let first = try!(self.parse_leading_operand());
+        let lhs = try!(self.parse_expr_rest(first));
         try!(self.bump());
         let operation = match try!(self.expect_token(&[Token::Equ, Token::GThan,
         Token::SThan, Token::GEThan,
@@ -702,25 +1008,23 @@ impl<'a> Parser<'a> {
 
         };
         try!(self.bump());
-        let mut rhsalias = None;
-        let rhs = match self.expect_word(false) {
-            Ok(s) => {
-                if self.check_next_token(&[Token::Dot]) {
-                    rhsalias = Some(s);
-                    try!(self.bump());
-                    try!(self.bump());
-                }
-                CondType::Word(try!(self.expect_word(true)))
-            },
-            _ => CondType::Literal(try!(self.expect_literal())),
+        let rhs = try!(self.parse_expr());
+        let column = match lhs {
+            Expr::Column(column) => column,
+            lhs => return Ok(Conditions::Compare(lhs, operation, rhs)),
         };
-        Ok(Condition {
-            aliascol: alias,
-            col: columnname,
+        let (rhsalias, rhs) = match rhs {
+            Expr::Column(col) => (col.table, CondType::Word(col.name)),
+            Expr::Literal(lit) => (None, CondType::Literal(lit)),
+            rhs => return Ok(Conditions::Compare(Expr::Column(column), operation, rhs)),
+        };
+        Ok(Conditions::Leaf(Condition {
+            aliascol: column.table,
+            col: column.name,
             op: operation,
             aliasrhs: rhsalias,
             rhs: rhs,
-        })
+        }))
     }
     // Utility function to parse metadata of columns
     fn expect_column_info(&mut self) -> Result<ColumnInfo, ParseError> {
@@ -731,35 +1035,63 @@ impl<'a> Parser<'a> {
         let mut auto_increment = false;
         let mut not_null = false;
         let mut comment = None;
+        let mut check = None;
 
-        while self.peek.is_some() && !self.check_next_token(&[Token::ParenCl, Token::Comma]) {
-            if self.check_next_keyword(&[Keyword::Primary]) {
-                try!(self.bump());
-                try!(self.expect_keyword(&[Keyword::Primary]));
-                try!(self.bump());
-                try!(self.expect_keyword(&[Keyword::Key]));
-                colprimary = true;
-            } else if self.check_next_keyword(&[Keyword::AutoIncrement]) {
-                try!(self.bump());
-                try!(self.expect_keyword(&[Keyword::AutoIncrement]));
-                auto_increment = true;
-            } else if self.check_next_keyword(&[Keyword::Not]) {
-                try!(self.bump());
-                try!(self.expect_keyword(&[Keyword::Not]));
-                try!(self.bump());
-                try!(self.expect_keyword(&[Keyword::Null]));
-                not_null = true;
-            } else if self.check_next_keyword(&[Keyword::Comment]) {
-                try!(self.bump());
-                try!(self.expect_keyword(&[Keyword::Comment]));
-                try!(self.bump());
-                let tmp = try!(self.expect_literal());
-                comment = Some(match tmp {
-                    Lit::String(s) => s,
-                    _ => return Err(ParseError::CommentIsNoString),
-                })
-            } else {
-                break;
+        let constraints = &[Keyword::Primary, Keyword::AutoIncrement, Keyword::Not,
+            Keyword::Comment, Keyword::Check];
+        while self.check_next_keyword(constraints) {
+            try!(self.bump());
+            let start = try!(self.curr_span());
+            let duplicate = match try!(self.expect_keyword(constraints)) {
+                Keyword::Primary => {
+                    try!(self.bump());
+                    try!(self.expect_keyword(&[Keyword::Key]));
+                    let duplicate = colprimary;
+                    colprimary = true;
+                    duplicate
+                },
+                Keyword::AutoIncrement => {
+                    let duplicate = auto_increment;
+                    auto_increment = true;
+                    duplicate
+                },
+                Keyword::Not => {
+                    try!(self.bump());
+                    try!(self.expect_keyword(&[Keyword::Null]));
+                    let duplicate = not_null;
+                    not_null = true;
+                    duplicate
+                },
+                Keyword::Comment => {
+                    try!(self.bump());
+                    let tmp = try!(self.expect_literal());
+                    let duplicate = comment.is_some();
+                    comment = Some(match tmp {
+                        Value::Str(s) => s,
+                        _ => return Err(ParseError::CommentIsNoString),
+                    });
+                    duplicate
+                },
+                // the condition is parsed like a where clause
+                Keyword::Check => {
+                    try!(self.bump());
+                    try!(self.expect_token(&[Token::ParenOp]));
+                    let cond = try!(self.parse_where_part());
+                    try!(self.bump());
+                    try!(self.expect_token(&[Token::ParenCl]).map_err(|e| match e {
+                        ParseError::WrongToken { span, .. } => ParseError::MissingParenthesis(span),
+                        _ => e,
+                    }));
+                    let duplicate = check.is_some();
+                    check = Some(cond);
+                    duplicate
+                },
+                _ => return Err(ParseError::UnknownError),
+            };
+            // e.g. `id int primary key primary key`
+            if duplicate {
+                let end = try!(self.curr_span());
+                return Err(ParseError::DuplicateConstraint(Span::merge(&start, &end)));
             }
         }
 
@@ -770,6 +1102,7 @@ impl<'a> Parser<'a> {
             auto_increment: auto_increment,
             not_null: not_null,
             comment: comment,
+            check: check,
         })
     }
     // checks if the current token is a datatype.
@@ -801,30 +1134,21 @@ impl<'a> Parser<'a> {
             "int" => SqlType::Int,
             "bool" => SqlType::Bool,
             "boolean" => SqlType::Bool,
+            "float" => SqlType::Float,
+            "double" => SqlType::Double,
+            "date" => SqlType::Date,
+            "timestamp" => SqlType::Timestamp,
             // checks if char is written in correct sql syntax
             "char" => {
-                try!(self.bump());
-                try!(self.expect_token(&[Token::ParenOp]));
-                try!(self.bump());
-                let length_lit = try!(self.expect_number());
-                try!(self.bump());
-                try!(self.expect_token(&[Token::ParenCl]));
-
-                let length = match length_lit {
-                    Lit::Int(i) => {
-                        if 0 <= i && i <= ( u8::max_value() as i64)  {
-                            i as u8
-                        }else {
-                            return Err(ParseError::DatatypeMissmatch(
-                                Span { lo: span_lo , hi: span_hi }
-                            ))
-                        }
-                    },
-                    _ => return Err(ParseError::DatatypeMissmatch(
-                                Span { lo: span_lo , hi: span_hi }
-                                ))
-                };
-                SqlType::Char(length)
+                let length = try!(self.expect_datatype_length(0, u8::max_value() as i64,
+                    Span { lo: span_lo , hi: span_hi }));
+                SqlType::Char(length as u8)
+            },
+            // the length of a varchar must be greater than zero
+            "varchar" => {
+                let length = try!(self.expect_datatype_length(1, u16::max_value() as i64,
+                    Span { lo: span_lo , hi: span_hi }));
+                SqlType::VarChar(length as u16)
             },
             _ => return Err(ParseError::NotADatatype(
              Span { lo: span_lo , hi: span_hi }
@@ -832,11 +1156,30 @@ impl<'a> Parser<'a> {
         };
         Ok((found_datatype))
     }
+    // parses the (x) part of a datatype like char(x) and checks if x is
+    // between min and max. `span` is the span of the datatype name.
+    fn expect_datatype_length(&mut self, min: i64, max: i64, span: Span)
+    -> Result<i64, ParseError>
+    {
+        try!(self.bump());
+        try!(self.expect_token(&[Token::ParenOp]));
+        try!(self.bump());
+        let length_lit = try!(self.expect_number());
+        try!(self.bump());
+        try!(self.expect_token(&[Token::ParenCl]));
+
+        match length_lit {
+            Value::Int(i) if min <= i && i <= max => Ok(i),
+            // the error covers the whole datatype, e.g. `char(300)`
+            _ => Err(ParseError::DatatypeMissmatch(Span::merge(&span, &try!(self.curr_span())))),
+        }
+    }
     // checks if the current token is a word
     fn expect_word(&self,allowkeyword: bool) -> Result<String, ParseError> {
         let mut found_word;
         let mut span_lo;
         let mut span_hi;
+        let quoted;
         {
             // checks if token non or some
             let token = match self.curr {
@@ -849,20 +1192,61 @@ impl<'a> Parser<'a> {
             // checks whether token is a word
             found_word = match token.tok {
                 Token::Word(ref s) => s,
-                _ => return Err(ParseError::NotAWord(
-                 Span { lo: span_lo , hi: span_hi }
-                 ))
+                _ => return Err(ParseError::NotAWord {
+                    span: Span { lo: span_lo , hi: span_hi },
+                    found: self.token_text(span_lo, span_hi),
+                })
             };
+            quoted = self.is_quoted(token);
+        }
+        // quoted names may start with a digit
+        if found_word.is_empty() || found_word.chars().count() > MAX_IDENTIFIER_LENGTH ||
+            (!quoted && found_word.starts_with(|c: char| c.is_digit(10)))
+        {
+            return Err(ParseError::InvalidIdentifier(Span { lo: span_lo, hi: span_hi }))
         }
-        if keyword_from_string(found_word).is_some() && !allowkeyword {
-            Err(ParseError::ReservedKeyword(Span { lo: span_lo , hi: span_hi }))
+        if keyword_from_string(found_word).is_some() && !allowkeyword && !quoted {
+            Err(ParseError::ReservedKeyword {
+                span: Span { lo: span_lo , hi: span_hi },
+                found: found_word.to_string(),
+            })
         } else {
             Ok(found_word.to_string())
         }
     }
 
     // checks if the current token is a word
-    fn expect_literal(&self) -> Result<Lit, ParseError> {
+    fn expect_literal(&mut self) -> Result<Value, ParseError> {
+        // a negative number is lexed as `-` followed by the number
+        if self.expect_token(&[Token::Sub]).is_ok() {
+            let negated = match self.peek {
+                Some(ref ts) => match ts.tok {
+                    Token::Literal(Value::Int(i)) => Some(Value::Int(-i)),
+                    Token::Literal(Value::Float(f)) => Some(Value::Float(-f)),
+                    _ => None,
+                },
+                None => None,
+            };
+            if let Some(lit) = negated {
+                try!(self.bump());
+                return Ok(lit)
+            }
+        }
+        // typed literals like date '2024-01-31' are a keyword and a string
+        if let Ok(keyword) = self.expect_keyword(&[Keyword::Date, Keyword::Timestamp]) {
+            let lo = try!(self.curr_span()).lo;
+            try!(self.bump());
+            let span = try!(self.curr_span());
+            let text = match self.curr {
+                Some(TokenSpan { tok: Token::Literal(Value::Str(ref s)), .. }) => s.clone(),
+                _ => return Err(ParseError::NotALiteral(span)),
+            };
+            let lit = match keyword {
+                Keyword::Date => Value::date(&text),
+                _ => Value::timestamp(&text),
+            };
+            return lit.ok_or(ParseError::InvalidDate(Span { lo: lo, hi: span.hi }))
+        }
         let mut found_lit;
         let mut span_lo;
         let mut span_hi;
@@ -877,17 +1261,12 @@ impl<'a> Parser<'a> {
             span_hi = token.span.hi;
             // checks whether token is a word
             found_lit = match token.tok {
-                Token::Word(ref s) => {
-                    if s.to_lowercase() == "true" {
-
-                        Lit::Bool(1)
-                    } else if s.to_lowercase() == "false" {
-                        Lit::Bool(0)
-                    } else {
-                        return  Err(ParseError::NotALiteral(
-                         Span { lo: span_lo , hi: span_hi } ))
-                    }
-                }
+                Token::Word(ref s) if !self.is_quoted(token) => match keyword_from_string(s) {
+                    Some(Keyword::True) => Value::Bool(true),
+                    Some(Keyword::False) => Value::Bool(false),
+                    Some(Keyword::Null) => Value::Null,
+                    _ => return Err(ParseError::NotALiteral(Span { lo: span_lo , hi: span_hi })),
+                },
                 Token::Literal(ref s) => s.clone(),
                 _ => return Err(ParseError::NotALiteral(
                  Span { lo: span_lo , hi: span_hi }
@@ -897,7 +1276,7 @@ impl<'a> Parser<'a> {
         Ok(found_lit)
     }
     // checks if the current token is a number
-    fn expect_number(&self) -> Result<Lit, ParseError> {
+    fn expect_number(&self) -> Result<Value, ParseError> {
         let mut found_num;
         let mut span_lo;
         let mut span_hi;
@@ -912,13 +1291,39 @@ impl<'a> Parser<'a> {
             span_hi = token.span.hi;
             // checks whether token is a valid number
             found_num = match token.tok {
-                Token::Literal(Lit::Int(s)) => Lit::Int(s),
-                Token::Literal(Lit::Float(s)) => Lit::Float(s),
+                Token::Literal(Value::Int(s)) => Value::Int(s),
+                Token::Literal(Value::Float(s)) => Value::Float(s),
                 _ => return Err(ParseError::NotANumber(Span { lo: span_lo , hi: span_hi } ))
             };
         }
         Ok(found_num)
     }
+    // matches current token against a non negative integer, used by limit and offset
+    fn expect_limit_number(&self) -> Result<u64, ParseError> {
+        let span = try!(self.curr_span());
+        match try!(self.expect_number()) {
+            Value::Int(i) if i >= 0 => Ok(i as u64),
+            _ => Err(ParseError::LimitError(span)),
+        }
+    }
+    // returns the span of the current token
+    fn curr_span(&self) -> Result<Span, ParseError> {
+        match self.curr {
+            Some(ref token) => Ok(token.span.clone()),
+            None => Err(ParseError::UnexpectedEoq),
+        }
+    }
+    // checks if the token is a quoted name, which is never a keyword
+    fn is_quoted(&self, token: &TokenSpan) -> bool {
+        match self.query[token.span.lo..].chars().next() {
+            Some('"') | Some('`') => true,
+            _ => false,
+        }
+    }
+    // returns the text of the query between the given byte positions
+    fn token_text(&self, lo: usize, hi: usize) -> String {
+        self.query[lo..hi].to_string()
+    }
     // checks if current token is an expected token
     fn expect_token(&self,expected_tokens: &[Token])
     -> Result<Token, ParseError>
@@ -932,7 +1337,10 @@ impl<'a> Parser<'a> {
             if expected_tokens.contains(&(token.tok)) {
                 Ok(token.tok.clone())
             } else {
-                Err(ParseError::WrongToken(Span { lo: token.span.lo, hi: token.span.hi } ))
+                Err(ParseError::WrongToken {
+                    span: token.span.clone(),
+                    found: self.token_text(token.span.lo, token.span.hi),
+                })
             }
     }
     // matches current token against any keyword and checks if it is one of
@@ -954,20 +1362,29 @@ impl<'a> Parser<'a> {
             span_hi = token.span.hi;
             // checks whether token is a word
             let word = match token.tok {
-                Token::Word(ref s) => s,
-                _ => return Err(ParseError::NotAKeyword(Span { lo: span_lo , hi: span_hi } ))
+                Token::Word(ref s) if !self.is_quoted(token) => s,
+                _ => return Err(ParseError::NotAKeyword {
+                    span: Span { lo: span_lo , hi: span_hi },
+                    found: self.token_text(span_lo, span_hi),
+                })
             };
             // checks if word is a keyword
             found_keyword = match keyword_from_string(&word){
                 Some(keyword) => keyword,
-                None => return Err(ParseError::NotAKeyword(Span { lo: span_lo , hi: span_hi } )),
+                None => return Err(ParseError::NotAKeyword {
+                    span: Span { lo: span_lo , hi: span_hi },
+                    found: word.to_string(),
+                }),
             };
         }
         // checks if keyword is expected keyword
         if expected_keywords.contains(&found_keyword) {
             Ok(found_keyword)
         } else {
-            Err(ParseError::WrongKeyword(Span { lo: span_lo , hi: span_hi } ))
+            Err(ParseError::WrongKeyword {
+                span: Span { lo: span_lo , hi: span_hi },
+                found: self.token_text(span_lo, span_hi),
+            })
         }
     }
 }
@@ -985,6 +1402,8 @@ fn keyword_from_string(string: &str) -> Option<Keyword> {
                 "insert" => Some(Keyword::Insert),
                 "delete" => Some(Keyword::Delete),
                 "modify" => Some(Keyword::Modify),
+                "rename" => Some(Keyword::Rename),
+                "to" => Some(Keyword::To),
                 "add" => Some(Keyword::Add),
                 "column" => Some(Keyword::Column),
                 "database" => Some(Keyword::Database),
@@ -1012,6 +1431,16 @@ fn keyword_from_string(string: &str) -> Option<Keyword> {
                 "not" => Some(Keyword::Not),
                 "null" => Some(Keyword::Null),
                 "comment" => Some(Keyword::Comment),
+                "check" => Some(Keyword::Check),
+                "distinct" => Some(Keyword::Distinct),
+                "offset" => Some(Keyword::Offset),
+                "true" => Some(Keyword::True),
+                "false" => Some(Keyword::False),
+                "date" => Some(Keyword::Date),
+                "timestamp" => Some(Keyword::Timestamp),
+                "join" => Some(Keyword::Join),
+                "inner" => Some(Keyword::Inner),
+                "on" => Some(Keyword::On),
                 _ => None,
             }
 }
@@ -1051,6 +1480,8 @@ pub enum Keyword {
     Limit,
     Modify,
     Add,
+    Rename,
+    To,
     Into,
     Values,
     And,
@@ -1066,6 +1497,16 @@ pub enum Keyword {
     Not,
     Null,
     Comment,
+    Check,
+    Distinct,
+    Offset,
+    True,
+    False,
+    Date,
+    Timestamp,
+    Join,
+    Inner,
+    On,
 }
 
 #[derive(Debug, PartialEq)]
@@ -1077,27 +1518,166 @@ pub enum ParseError {
     // Syntax errors:
     //End of file,
     UnexpectedEoq,
-    InvalidEoq,
+    // something other than a semicolon after the end of the statement
+    TrailingTokens(Span),
     //Token errors
-    WrongKeyword(Span),
-    WrongToken(Span),
+    WrongKeyword { span: Span, found: String },
+    WrongToken { span: Span, found: String },
     DatatypeMissmatch(Span),
-    NotAKeyword(Span),
+    NotAKeyword { span: Span, found: String },
     NotAToken(Span),
-    NotAWord(Span),
+    NotAWord { span: Span, found: String },
     NotADatatype(Span),
     NotANumber(Span),
     NotALiteral(Span),
     ColumnCountMissmatch,
+    ParamCountMissmatch,
     MissingParenthesis(Span),
-    LimitError,
-    ReservedKeyword(Span),
+    LimitError(Span),
+    MissingTarget(Span),
+    MissingAssignment(Span),
+    TrailingComma(Span),
+    DuplicateConstraint(Span),
+    ReservedKeyword { span: Span, found: String },
+    // empty, too long or starting with a digit
+    InvalidIdentifier(Span),
+    // the string of a date or timestamp literal
+    InvalidDate(Span),
+    // `*` as argument of another aggregate than count
+    InvalidAggregate(Span),
     CommentIsNoString,
     //Used for debugging
     DebugError(String)
 // TODO: introduce good errors and think more about it
 }
 
+impl ParseError {
+    /// Returns the span of the query this error refers to, if any
+    pub fn span(&self) -> Option<&Span> {
+        match self {
+            &ParseError::WrongKeyword { ref span, .. } |
+            &ParseError::WrongToken { ref span, .. } |
+            &ParseError::DatatypeMissmatch(ref span) |
+            &ParseError::NotAKeyword { ref span, .. } |
+            &ParseError::NotAToken(ref span) |
+            &ParseError::NotAWord { ref span, .. } |
+            &ParseError::NotADatatype(ref span) |
+            &ParseError::NotANumber(ref span) |
+            &ParseError::NotALiteral(ref span) |
+            &ParseError::MissingParenthesis(ref span) |
+            &ParseError::MissingTarget(ref span) |
+            &ParseError::MissingAssignment(ref span) |
+            &ParseError::TrailingComma(ref span) |
+            &ParseError::TrailingTokens(ref span) |
+            &ParseError::DuplicateConstraint(ref span) |
+            &ParseError::ReservedKeyword { ref span, .. } |
+            &ParseError::LimitError(ref span) |
+            &ParseError::InvalidIdentifier(ref span) |
+            &ParseError::InvalidDate(ref span) |
+            &ParseError::InvalidAggregate(ref span) |
+            &ParseError::LexError(lex::LexError::LeadingZero(ref span)) |
+            &ParseError::LexError(lex::LexError::NumberOverflow(ref span)) |
+            &ParseError::LexError(lex::LexError::UnknownSymbol(ref span)) |
+            &ParseError::LexError(lex::LexError::UnclosedComment(ref span)) |
+            &ParseError::LexError(lex::LexError::UnclosedIdentifier(ref span)) |
+            &ParseError::LexError(lex::LexError::InvalidIdentifier(ref span)) => Some(span),
+            _ => None,
+        }
+    }
+
+    /// Translates the start of the error span into a 1-based line and column
+    /// of the given query. Columns are counted in chars, a `\r` before a line
+    /// break is not counted.
+    pub fn location(&self, query: &str) -> Option<(usize, usize)> {
+        let span = match self.span() {
+            Some(span) => span,
+            None => return None,
+        };
+        let mut line = 1;
+        let mut col = 1;
+        for (pos, c) in query.char_indices() {
+            if pos >= span.lo {
+                break;
+            }
+            match c {
+                '\n' => {
+                    line += 1;
+                    col = 1;
+                },
+                '\r' => {},
+                _ => col += 1,
+            }
+        }
+        Some((line, col))
+    }
+}
+
+/// Implement display for a readable description of the ParseError including
+/// the position in the query
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let description = error::Error::description(self);
+        match self {
+            &ParseError::WrongKeyword { ref span, ref found } |
+            &ParseError::WrongToken { ref span, ref found } |
+            &ParseError::NotAKeyword { ref span, ref found } |
+            &ParseError::NotAWord { ref span, ref found } |
+            &ParseError::ReservedKeyword { ref span, ref found } =>
+                write!(f, "{} `{}` at position {}", description, found, span.lo),
+            &ParseError::DebugError(ref msg) => msg.fmt(f),
+            _ => match self.span() {
+                Some(span) => write!(f, "{} at position {}", description, span.lo),
+                None => description.fmt(f),
+            },
+        }
+    }
+}
+
+/// Implement description for the ParseError enum
+impl error::Error for ParseError {
+    fn description(&self) -> &str {
+        match self {
+            &ParseError::LexError(lex::LexError::UnclosedQuotationmark) =>
+                "unclosed quotation mark",
+            &ParseError::LexError(lex::LexError::LeadingZero(_)) =>
+                "number with leading zero",
+            &ParseError::LexError(lex::LexError::NumberOverflow(_)) => "number is too large",
+            &ParseError::LexError(lex::LexError::UnknownSymbol(_)) => "unknown symbol",
+            &ParseError::LexError(lex::LexError::UnclosedComment(_)) => "unclosed comment",
+            &ParseError::LexError(lex::LexError::UnclosedIdentifier(_)) =>
+                "unclosed quoted name",
+            &ParseError::LexError(lex::LexError::InvalidIdentifier(_)) => "invalid quoted name",
+            &ParseError::UnknownError => "unknown error",
+            &ParseError::EmptyQueryError => "empty query",
+            &ParseError::UnexpectedEoq => "unexpected end of query",
+            &ParseError::TrailingTokens(_) => "expected end of query",
+            &ParseError::WrongKeyword { .. } => "unexpected keyword",
+            &ParseError::WrongToken { .. } => "unexpected token",
+            &ParseError::DatatypeMissmatch(_) => "invalid length of datatype",
+            &ParseError::NotAKeyword { .. } => "expected a keyword, found",
+            &ParseError::NotAToken(_) => "expected a token",
+            &ParseError::NotAWord { .. } => "expected a name, found",
+            &ParseError::NotADatatype(_) => "unknown datatype",
+            &ParseError::NotANumber(_) => "expected a number",
+            &ParseError::NotALiteral(_) => "expected a literal",
+            &ParseError::ColumnCountMissmatch => "number of columns and values differs",
+            &ParseError::ParamCountMissmatch => "number of placeholders and parameters differs",
+            &ParseError::MissingParenthesis(_) => "missing closing parenthesis",
+            &ParseError::LimitError(_) => "limit and offset must be non negative integers",
+            &ParseError::MissingTarget(_) => "missing select target",
+            &ParseError::MissingAssignment(_) => "missing assignment",
+            &ParseError::TrailingComma(_) => "trailing comma",
+            &ParseError::DuplicateConstraint(_) => "constraint is given twice",
+            &ParseError::ReservedKeyword { .. } => "reserved keyword used as name:",
+            &ParseError::InvalidIdentifier(_) => "invalid name",
+            &ParseError::InvalidDate(_) => "invalid date",
+            &ParseError::InvalidAggregate(_) => "only count accepts `*`",
+            &ParseError::CommentIsNoString => "comment must be a string",
+            &ParseError::DebugError(_) => "debug error",
+        }
+    }
+}
+
 impl From<lex::LexError> for ParseError {
     fn from(error: lex::LexError) -> ParseError {
         ParseError::LexError(error)
@@ -1,41 +1,26 @@
-use super::Span;
-use parse::ast::*;
-use storage::SqlType;
-/// A token with it's associated Span in the source code
-#[derive(Debug)]
-pub struct TokenSpan {
-    pub tok: Token,
-    pub span: Span,
-}
+use std::cmp::{min, max};
+
+pub use value::Value;
 
+/// Represents a substring in the query string in byte indices.
 #[derive(Debug, Clone, PartialEq)]
-pub enum Lit {
-	String(String),
-	Int(i64),
-    Float(f64),
-    Bool(u8),
+pub struct Span {
+    pub lo: usize,
+    pub hi: usize,
 }
 
-impl Lit {
-    pub fn into_DataSrc(&self) -> DataSrc {
-        match self {
-            &Lit::String(ref s) => DataSrc::String(s.clone()),
-            &Lit::Int(ref i) => DataSrc::Int(i.clone()),
-            &Lit::Float(ref f) => DataSrc::String(f.to_string()),
-            &Lit::Bool(ref b) => DataSrc::Bool(b.clone()),
-        }
-    }
-
-    pub fn sqltype(&self) -> SqlType {
-        match self {
-            &Lit::String(_) => SqlType::Char(0),
-            &Lit::Int(_) => SqlType::Int,
-            &Lit::Float(_) => SqlType::Char(0),
-            &Lit::Bool(_) => SqlType::Bool,
-        }
+impl Span {
+    /// Returns the smallest span covering both spans
+    pub fn merge(a: &Span, b: &Span) -> Span {
+        Span { lo: min(a.lo, b.lo), hi: max(a.hi, b.hi) }
     }
+}
 
-
+/// A token with it's associated Span in the source code
+#[derive(Debug, Clone)]
+pub struct TokenSpan {
+    pub tok: Token,
+    pub span: Span,
 }
 
 /// A token: Everything the lexer can produce
@@ -45,13 +30,14 @@ pub enum Token {
     Word(String),
 
     // detects literals
-    Literal(Lit),
+    Literal(Value),
 
     Semi,
     Dot,
     Comma,
     // Bang,
-    // QMark,
+    // placeholder of a prepared statement
+    QMark,
 
     // delimiter (,),',"
     ParenOp,
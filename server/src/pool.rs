@@ -0,0 +1,159 @@
+//! A fixed-size pool of worker threads.
+//!
+//! Jobs are sent through a bounded channel that all workers pull from, so a
+//! flood of jobs blocks the submitter instead of spawning more threads.
+//!
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{sync_channel, SyncSender, Receiver, TrySendError};
+use std::thread::{self, JoinHandle};
+
+/// A pool of worker threads that process jobs of type `T` with the same
+/// handler function.
+pub struct Pool<T: Send + 'static> {
+    sender: Option<SyncSender<T>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl<T: Send + 'static> Pool<T> {
+    /// Starts `size` workers that call `handler` for every submitted job.
+    /// At most `size` jobs wait in the queue at the same time.
+    ///
+    /// # Panics
+    /// If `size` is 0.
+    pub fn new<F>(size: usize, handler: F) -> Pool<T>
+        where F: Fn(T) + Send + Sync + 'static
+    {
+        assert!(size > 0, "a pool needs at least one worker");
+
+        let (sender, receiver) = sync_channel(size);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let handler = Arc::new(handler);
+
+        let workers = (0..size).map(|_| {
+            let receiver = receiver.clone();
+            let handler = handler.clone();
+            thread::spawn(move || work(receiver, handler))
+        }).collect();
+
+        Pool { sender: Some(sender), workers: workers }
+    }
+
+    /// Queues a job, blocks while the queue is full.
+    pub fn submit(&self, job: T) {
+        if let Some(ref sender) = self.sender {
+            // Only fails if all workers are gone, which means they panicked
+            if sender.send(job).is_err() {
+                error!("All workers of the pool have stopped");
+            }
+        }
+    }
+
+    /// Queues a job unless the queue is full, the job is returned then.
+    pub fn try_submit(&self, job: T) -> Result<(), T> {
+        match self.sender {
+            Some(ref sender) => match sender.try_send(job) {
+                Ok(()) => Ok(()),
+                Err(TrySendError::Full(job)) => Err(job),
+                Err(TrySendError::Disconnected(job)) => {
+                    error!("All workers of the pool have stopped");
+                    Err(job)
+                }
+            },
+            None => Err(job)
+        }
+    }
+}
+
+/// Processes jobs until the sending end of the channel is closed. A job that
+/// panics is logged, the worker continues with the next one.
+fn work<T, F: Fn(T)>(receiver: Arc<Mutex<Receiver<T>>>, handler: Arc<F>) {
+    loop {
+        // The lock is released before the job is handled
+        let job = match receiver.lock() {
+            Ok(r) => r.recv(),
+            Err(_) => return
+        };
+        match job {
+            Ok(job) => {
+                if panic::catch_unwind(AssertUnwindSafe(|| handler(job))).is_err() {
+                    error!("A job of the pool panicked");
+                }
+            },
+            Err(_) => return
+        }
+    }
+}
+
+impl<T: Send + 'static> Drop for Pool<T> {
+    /// Closes the queue and waits until the workers have finished all
+    /// remaining jobs.
+    fn drop(&mut self) {
+        self.sender.take();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[test]
+fn test_more_jobs_than_workers() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    let done = Arc::new(AtomicUsize::new(0));
+    {
+        let done = done.clone();
+        let pool = Pool::new(2, move |n: usize| {
+            thread::sleep(Duration::from_millis(5));
+            done.fetch_add(n, Ordering::SeqCst);
+        });
+        for n in 1..21 {
+            pool.submit(n);
+        }
+        // dropping the pool waits for all jobs
+    }
+    assert_eq!(done.load(Ordering::SeqCst), (1..21).fold(0, |a, b| a + b));
+}
+
+#[test]
+fn test_try_submit_full_queue() {
+    use std::sync::mpsc::channel;
+
+    // the worker blocks on its first job, one more job fits into the queue
+    let (release, blocked) = channel::<()>();
+    let blocked = Mutex::new(blocked);
+    let (started, wait_started) = channel();
+    let started = Mutex::new(started);
+    let pool = Pool::new(1, move |n: usize| {
+        if n == 1 {
+            started.lock().unwrap().send(()).unwrap();
+            blocked.lock().unwrap().recv().unwrap();
+        }
+    });
+    assert_eq!(pool.try_submit(1), Ok(()));
+    wait_started.recv().unwrap();
+    assert_eq!(pool.try_submit(2), Ok(()));
+    assert_eq!(pool.try_submit(3), Err(3));
+    release.send(()).unwrap();
+}
+
+#[test]
+fn test_worker_survives_panic() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let done = Arc::new(AtomicUsize::new(0));
+    {
+        let done = done.clone();
+        let pool = Pool::new(1, move |n: usize| {
+            if n == 0 {
+                panic!("job failed");
+            }
+            done.fetch_add(n, Ordering::SeqCst);
+        });
+        pool.submit(0);
+        pool.submit(1);
+        pool.submit(2);
+    }
+    assert_eq!(done.load(Ordering::SeqCst), 3);
+}
@@ -5,7 +5,7 @@
 //!
 
 use super::parse::ast::*;
-use super::parse::token::Lit;
+use super::value::Value;
 use super::storage::{Database, Column, Table, Rows, ResultSet, Engine, EngineID, Error};
 use super::storage::types::SqlType;
 use super::storage;
@@ -15,26 +15,59 @@ use std::io::{Write, Read, Seek};
 use std::fs::File;
 use std::io::Cursor;
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use byteorder::{BigEndian, WriteBytesExt, ReadBytesExt};
 
 pub struct Executor<'a> {
     pub user: &'a mut auth::User,
+    // set from another thread to stop the execution
+    cancel: Arc<AtomicBool>,
 }
 
 
 
+/// Result of an executed query: the rows of e.g. a `select`, or the number
+/// of rows changed by `insert`, `update` and `delete`.
+#[derive(Debug)]
+pub enum ExecutionResult {
+    Rows(ResultSet),
+    Affected(u64),
+}
+
     pub fn execute_from_ast<'a>(query: Query, user: &'a mut auth::User)
-        -> Result<ResultSet, ExecutionError> {
+        -> Result<ExecutionResult, ExecutionError> {
+
+        execute_from_ast_cancellable(query, user, Arc::new(AtomicBool::new(false)))
+    }
+
+    /// Like `execute_from_ast`, but stops with `ExecutionError::Cancelled`
+    /// as soon as `cancel` is set, e.g. by the thread of another connection.
+    pub fn execute_from_ast_cancellable<'a>(query: Query, user: &'a mut auth::User,
+        cancel: Arc<AtomicBool>) -> Result<ExecutionResult, ExecutionError> {
 
         let mut executor = Executor::new(user);
+        executor.cancel = cancel;
 
         let res = match query {
+            Query::ManipulationStmt(ManipulationStmt::Insert(stmt)) => {
+                try!(executor.execute_insert_stmt(stmt));
+                return Ok(ExecutionResult::Affected(1))
+            },
+            Query::ManipulationStmt(ManipulationStmt::Delete(stmt)) => {
+                let count = try!(executor.execute_delete_stmt(stmt));
+                return Ok(ExecutionResult::Affected(count))
+            },
+            Query::ManipulationStmt(ManipulationStmt::Update(stmt)) => {
+                let count = try!(executor.execute_update_stmt(stmt));
+                return Ok(ExecutionResult::Affected(count))
+            },
             Query::ManipulationStmt(stmt) => executor.execute_manipulation_stmt(stmt),
             Query::DefStmt(stmt) => executor.execute_def_stmt(stmt),
             _ => return Err(ExecutionError::ParseError(ParseError::UnknownError)),
 
         };
-        Ok(try!(try!(res).to_result_set()))
+        Ok(ExecutionResult::Rows(try!(try!(res).to_result_set())))
     }
 
 
@@ -44,7 +77,15 @@ impl<'a> Executor<'a> {
 
 
     pub fn new(user: &'a mut auth::User) -> Executor<'a> {
-        Executor { user: user }
+        Executor { user: user, cancel: Arc::new(AtomicBool::new(false)) }
+    }
+
+    /// Returns `ExecutionError::Cancelled` if the execution was cancelled.
+    fn check_cancelled(&self) -> Result<(), ExecutionError> {
+        if self.cancel.load(Ordering::SeqCst) {
+            return Err(ExecutionError::Cancelled)
+        }
+        Ok(())
     }
 
 
@@ -57,7 +98,10 @@ impl<'a> Executor<'a> {
             ManipulationStmt::Insert(stmt) => self.execute_insert_stmt(stmt),
             ManipulationStmt::Describe(stmt) => self.execute_describe_stmt(stmt),
             ManipulationStmt::Select(stmt) => self.execute_select_stmt(stmt),
-            ManipulationStmt::Delete(stmt) => self.execute_delete_stmt(stmt),
+            ManipulationStmt::Delete(stmt) => {
+                try!(self.execute_delete_stmt(stmt));
+                Ok(generate_rows_dummy())
+            },
             _ => Err(ExecutionError::DebugError("Feature not implemented yet!".into())),
         }
 
@@ -80,7 +124,8 @@ impl<'a> Executor<'a> {
 
         match query {
             UseStmt::Database(querybase) => {
-                self.user._currentDatabase = Some(try!(Database::load(&querybase)));
+                self.user._currentDatabase = Some(try!(Database::load_in(&self.user.data_dir,
+                    &querybase)));
                 Ok(generate_rows_dummy())
             }
         }
@@ -110,7 +155,8 @@ impl<'a> Executor<'a> {
             for column in table.columns() {
                 info!("inserting at {:?}", writevec.len());
                 info!("This is the insertvalue: {:?}", insertvalues[index] );
-                column.sql_type.encode_into(&mut writevec,&insertvalues[index]);
+                try!(check_null(column, &insertvalues[index]));
+                try!(column.encode_into(&mut writevec,&insertvalues[index]));
                 index += 1;
             }
         }
@@ -126,6 +172,19 @@ fn execute_select_stmt(&mut self, mut stmt: SelectStmt)
     {
         let mut masterrow: Rows<Cursor<Vec<u8>>>;
 
+        if stmt.group_by.is_some() || stmt.having.is_some() {
+            return Err(ExecutionError::DebugError("Not implemented: GROUP BY and HAVING".into()))
+        }
+
+        // an inner join is the cross product filtered by its on-condition
+        for join in stmt.joins.drain(..) {
+            stmt.tid.push(join.right);
+            stmt.cond = Some(match stmt.cond.take() {
+                Some(cond) => Conditions::And(Box::new(join.on), Box::new(cond)),
+                None => join.on,
+            });
+        }
+
         let mut left = try!(self.get_rows(&stmt.tid[0]));
 
         let mut name_column_map = HashMap::<String, HashMap<String, usize>>::new();
@@ -157,6 +216,7 @@ fn execute_select_stmt(&mut self, mut stmt: SelectStmt)
             left = tmp;
         }
         masterrow = left;
+        try!(self.check_cancelled());
 
         // compute conditions
         let mut whereresult = if stmt.cond.is_some() {
@@ -233,7 +293,11 @@ fn execute_select_stmt(&mut self, mut stmt: SelectStmt)
                     indextargets.push((append,column.unwrap().clone()));
 
 
-                }
+                },
+                Col::Aggregate(_) => return Err(ExecutionError::DebugError(
+                    "Not implemented: aggregate functions".into())),
+                Col::Expr(_) => return Err(ExecutionError::DebugError(
+                    "Not implemented: arithmetic expressions".into())),
             }
         }
 
@@ -258,7 +322,10 @@ fn execute_select_stmt(&mut self, mut stmt: SelectStmt)
         let mut limitcount = (false,0);
         if stmt.limit.is_some() {
             let limit = stmt.limit.unwrap();
-            limitcount = (true,limit.count.unwrap().clone());
+            // a limit may only consist of an offset
+            if let Some(count) = limit.count {
+                limitcount = (true, count);
+            }
             if limit.offset.is_some() {
                 for i in 0..limit.offset.unwrap() {
                     let mut skiprow =  Vec::<u8>::new();
@@ -286,7 +353,9 @@ fn execute_select_stmt(&mut self, mut stmt: SelectStmt)
                 toinsert.extend(try!(whereresult.get_value(&originalrow,index.1)).into_iter());
             }
             resultrows.add_row(&toinsert);
-            limitcount.1 -=1;
+            if limitcount.0 {
+                limitcount.1 -= 1;
+            }
 
         }
 
@@ -369,6 +438,21 @@ fn execute_select_stmt(&mut self, mut stmt: SelectStmt)
 
             // TODO: SO MUCH REDUNDANT CODE!!!!!!!11111
             //       remove whenever there is time.
+            // a literal in front of a column is the same as behind it
+            &Conditions::Compare(Expr::Literal(ref lit), op, Expr::Column(ref col)) => {
+                let cond = Conditions::Leaf(Condition {
+                    aliascol: col.table.clone(),
+                    col: col.name.clone(),
+                    op: op.flip(),
+                    aliasrhs: None,
+                    rhs: CondType::Literal(lit.clone()),
+                });
+                self.execute_where(tableset, infos, &cond, negate, wheretype)
+            },
+
+            &Conditions::Compare(..) => Err(ExecutionError::DebugError(
+                "Not implemented: arithmetic expressions".into())),
+
             &Conditions::Leaf(ref c) => {
                 let tablename = if c.aliascol.is_some() {
                     match infos.0.get(&c.clone().aliascol.unwrap()) {
@@ -428,13 +512,24 @@ fn execute_select_stmt(&mut self, mut stmt: SelectStmt)
 
                     },
 
+                    CondType::Literal(Value::Null) => {
+                        return Err(ExecutionError::DebugError("Not implemented:
+                        Compare a column with null".into()))
+                    },
+
                     CondType::Literal(ref lit) => {
                         // Error handling: if wrong compare type is giving => Missmatch error
                         match tableset.columns[index].sql_type {
-                            SqlType::Char(c) => if lit.sqltype() != SqlType::Char(0) {
-                                return Err(ExecutionError::CompareDatatypeMissmatch)
+                            SqlType::Char(_) | SqlType::VarChar(_) =>
+                                if lit.as_sql_type() != SqlType::Char(0) {
+                                    return Err(ExecutionError::CompareDatatypeMissmatch)
+                                },
+                            // integers fit into float columns, too
+                            SqlType::Float | SqlType::Double => match lit.as_sql_type() {
+                                SqlType::Double | SqlType::Int => {},
+                                _ => return Err(ExecutionError::CompareDatatypeMissmatch)
                             },
-                            _ => if tableset.columns[index].sql_type.clone() != lit.sqltype() {
+                            _ => if tableset.columns[index].sql_type.clone() != lit.as_sql_type() {
                                  return Err(ExecutionError::CompareDatatypeMissmatch)
                             }
                         }
@@ -463,9 +558,11 @@ fn execute_select_stmt(&mut self, mut stmt: SelectStmt)
 
     }
 
+    /// Deletes the matching rows and returns how many were deleted.
     fn execute_delete_stmt(&mut self, mut query: DeleteStmt)
-        -> Result<Rows<Cursor<Vec<u8>>>, ExecutionError> {
+        -> Result<u64, ExecutionError> {
 
+        let before = try!(self.count_rows(&query.tid));
         let mut table = try!(self.get_rows(&query.tid));
         let mut name_column_map = HashMap::<String, HashMap<String, usize>>::new();
         let mut column_index_map = HashMap::<String, usize>::new();
@@ -483,17 +580,103 @@ fn execute_select_stmt(&mut self, mut stmt: SelectStmt)
         if query.cond.is_some() {
             try!(self.execute_where(table,
                     (&query.alias, &column_tablename_map, &name_column_map),
-                    &query.cond.unwrap(), false, Where::Delete(query.tid)));
+                    &query.cond.unwrap(), false, Where::Delete(query.tid.clone())));
         } else {
             let mut engine = try!(self.get_engine(&query.tid));
             // Todo: Storage: enable full table reset!!
             try!(engine.reset());
         }
 
-        Ok(generate_rows_dummy())
+        let after = try!(self.count_rows(&query.tid));
+        Ok(before.saturating_sub(after))
     }
 
 
+    /// Sets the columns of the matching rows and returns how many were
+    /// changed. The rows are deleted and inserted again with the new values.
+    fn execute_update_stmt(&mut self, mut query: UpdateStmt)
+        -> Result<u64, ExecutionError> {
+
+        let mut table = try!(self.get_rows(&query.tid));
+        let mut name_column_map = HashMap::<String, HashMap<String, usize>>::new();
+        let mut column_index_map = HashMap::<String, usize>::new();
+        let mut column_tablename_map = HashMap::<String, String>::new();
+        let mut columnindex: usize = 0;
+        for column in table.columns.clone() {
+            column_tablename_map.insert(column.name.clone(), query.tid.clone());
+            column_index_map.insert(column.name.into(), columnindex);
+            columnindex += 1;
+        }
+
+        // the new values, encoded like the rows
+        let mut values = Vec::new();
+        for set in &query.set {
+            let index = match column_index_map.get(&set.col) {
+                Some(index) => *index,
+                None => return Err(ExecutionError::UnknownColumn)
+            };
+            let lit = match set.rhs {
+                CondType::Literal(ref lit) => lit,
+                CondType::Word(_) => return Err(ExecutionError::DebugError("Not implemented:
+                Set a column to the value of another column".into()))
+            };
+            try!(check_null(&table.columns[index], lit));
+            match table.columns[index].sql_type {
+                // null fits every column which allows it, see check_null
+                _ if *lit == Value::Null => {},
+                SqlType::Char(_) | SqlType::VarChar(_) =>
+                    if lit.as_sql_type() != SqlType::Char(0) {
+                        return Err(ExecutionError::CompareDatatypeMissmatch)
+                    },
+                // integers fit into float columns, too
+                SqlType::Float | SqlType::Double => match lit.as_sql_type() {
+                    SqlType::Double | SqlType::Int => {},
+                    _ => return Err(ExecutionError::CompareDatatypeMissmatch)
+                },
+                _ => if table.columns[index].sql_type.clone() != lit.as_sql_type() {
+                     return Err(ExecutionError::CompareDatatypeMissmatch)
+                }
+            }
+            let mut data = Vec::<u8>::new();
+            try!(table.columns[index].encode_into(&mut data, lit));
+            values.push((index, data));
+        }
+
+        name_column_map.insert(query.tid.clone(), column_index_map);
+        query.alias.insert(query.tid.clone(), query.tid.clone());
+
+        let mut matching = match query.conds {
+            Some(ref conds) => {
+                let matching = try!(self.execute_where(try!(table.full_scan()),
+                    (&query.alias, &column_tablename_map, &name_column_map),
+                    conds, false, Where::Select));
+                try!(self.execute_where(table,
+                    (&query.alias, &column_tablename_map, &name_column_map),
+                    conds, false, Where::Delete(query.tid.clone())));
+                matching
+            },
+            None => {
+                let matching = try!(table.full_scan());
+                try!(try!(self.get_engine(&query.tid)).reset());
+                matching
+            }
+        };
+
+        let mut engine = try!(self.get_engine(&query.tid));
+        try!(matching.reset_pos());
+        let mut count = 0;
+        let mut row = Vec::<u8>::new();
+        while matching.next_row(&mut row).is_ok() {
+            for &(index, ref data) in &values {
+                matching.set_value(&mut row, data, index);
+            }
+            try!(engine.insert_row(&row));
+            row.clear();
+            count += 1;
+        }
+        Ok(count)
+    }
+
     fn execute_describe_stmt(&mut self, query: String)
         -> Result<Rows<Cursor<Vec<u8>>>, ExecutionError>
     {
@@ -510,7 +693,8 @@ fn execute_select_stmt(&mut self, mut stmt: SelectStmt)
     {
         match query {
             CreateStmt::Database(s) => {
-                self.user._currentDatabase = Some(try!(Database::create(&s)));
+                self.user._currentDatabase =
+                    Some(try!(Database::create_in(&self.user.data_dir, &s)));
                 Ok(generate_rows_dummy())
             },
             CreateStmt::Table(stmt) => self.execute_create_table_stmt(stmt),
@@ -521,11 +705,14 @@ fn execute_select_stmt(&mut self, mut stmt: SelectStmt)
     fn execute_create_table_stmt(&mut self, query: CreateTableStmt)
          -> Result<Rows<Cursor<Vec<u8>>>, ExecutionError> {
         let base = try!(self.get_own_database());
+        for col in &query.cols {
+            try!(check_unsupported(col));
+        }
         let tmp_vec : Vec<_> = query.cols.into_iter().map(|c| Column {
             name: c.cid,
             sql_type: c.datatype,
-            allow_null: false,
-            description: "this is a column".to_string(),
+            allow_null: !c.not_null,
+            description: c.comment.unwrap_or("".into()),
              is_primary_key: c.primary,
         }).collect();
         let table = try!(base.create_table(&query.tid, tmp_vec, EngineID::FlatFile));
@@ -545,7 +732,7 @@ fn execute_select_stmt(&mut self, mut stmt: SelectStmt)
                 Ok(generate_rows_dummy())
             },
             DropStmt::Database(s) => {
-                let base = try!(Database::load(&s));
+                let base = try!(Database::load_in(&self.user.data_dir, &s));
                 try!(base.delete());
                 let mut baseinuse = false;
                 match self.user._currentDatabase {
@@ -581,11 +768,29 @@ fn execute_select_stmt(&mut self, mut stmt: SelectStmt)
         -> Result<Rows<Cursor<Vec<u8>>>, ExecutionError>
     {
         let table = try!(self.get_table(&stmt.tid));
-        match stmt.op {
+        // operations are applied in the given order, the ones after a rename
+        // apply to the table with its new name
+        let mut tid = stmt.tid;
+        for op in stmt.ops {
+            let renamed = match op {
+                AlterOp::RenameTable(ref name) => Some(name.clone()),
+                _ => None,
+            };
+            try!(self.execute_alter_op(&tid, op));
+            if let Some(name) = renamed {
+                tid = name;
+            }
+        }
+        Ok(generate_rows_dummy())
+    }
+
+    fn execute_alter_op(&mut self, tid: &str, op: AlterOp) -> Result<(), ExecutionError> {
+        match op {
             AlterOp::Add(columninfo) => {
-                let mut table = try!(self.get_table(&stmt.tid));
+                try!(check_unsupported(&columninfo));
+                let mut table = try!(self.get_table(tid));
                 // Todo: no fullscan necessary!
-                let mut rows = try!(self.get_rows(&stmt.tid));
+                let mut rows = try!(self.get_rows(tid));
                 if !try!(rows.is_empty()) {
                     return Err(ExecutionError::TableNotEmpty)
                 }
@@ -603,21 +808,22 @@ fn execute_select_stmt(&mut self, mut stmt: SelectStmt)
                                  columninfo.primary
                                  );
                 try!(table.save());
-                Ok(generate_rows_dummy())
+                Ok(())
             },
             AlterOp::Drop(column) => {
-                let mut table = try!(self.get_table(&stmt.tid));
+                let mut table = try!(self.get_table(tid));
                 // Todo: no fullscan necessary!
-                let mut rows = try!(self.get_rows(&stmt.tid));
+                let mut rows = try!(self.get_rows(tid));
                 if !try!(rows.is_empty()) {
                     return Err(ExecutionError::TableNotEmpty)
                 }
                 table.remove_column(&column);
                 try!(table.save());
-                Ok(generate_rows_dummy())
+                Ok(())
             },
             AlterOp::Modify(columninfo) => {
-                let mut table = try!(self.get_table(&stmt.tid));
+                try!(check_unsupported(&columninfo));
+                let mut table = try!(self.get_table(tid));
                 {
                 let columns = &mut table.meta_data.columns;
                 let comment = if columninfo.comment.is_some() {
@@ -640,7 +846,18 @@ fn execute_select_stmt(&mut self, mut stmt: SelectStmt)
                 }
                 //println!("{:?}",table);
                 try!(table.save());
-                Ok(generate_rows_dummy())
+                Ok(())
+            },
+            AlterOp::RenameTable(name) => {
+                let mut table = try!(self.get_table(tid));
+                try!(table.rename(&name));
+                Ok(())
+            },
+            AlterOp::RenameColumn { from, to } => {
+                let mut table = try!(self.get_table(tid));
+                try!(table.rename_column(&from, &to));
+                try!(table.save());
+                Ok(())
             },
         }
 
@@ -671,11 +888,22 @@ fn execute_select_stmt(&mut self, mut stmt: SelectStmt)
         Ok(rows)
     }
 
+    /// Returns the number of rows (not marked as deleted) in the table.
+    fn count_rows(&self, table: &str) -> Result<u64, ExecutionError> {
+        let mut rows = try!(self.get_rows(table));
+        let mut count = 0;
+        while rows.next_row(&mut Vec::<u8>::new()).is_ok() {
+            count += 1;
+        }
+        Ok(count)
+    }
+
     fn merge_rows(&self, mut left: Rows<Cursor<Vec<u8>>>, mut right: Rows<Cursor<Vec<u8>>>)
         -> Result<Rows<Cursor<Vec<u8>>>, ExecutionError>
     {
         try!(right.reset_pos());
         loop {
+            try!(self.check_cancelled());
             try!(left.reset_pos());
             let mut valid = true;
 
@@ -736,6 +964,7 @@ fn execute_select_stmt(&mut self, mut stmt: SelectStmt)
 
 
         loop {
+            try!(self.check_cancelled());
             let mut insertingrow = Vec::<u8>::new();
             let outerres = left.next_row(&mut insertingrow);
 
@@ -792,6 +1021,8 @@ pub enum ExecutionError {
     UnknownColumn,
     CompareDatatypeMissmatch,
     TableNotEmpty,
+    Cancelled,
+    NullValue(String),
 }
 
 impl From<ParseError> for ExecutionError {
@@ -806,6 +1037,23 @@ impl From<storage::Error> for ExecutionError {
     }
 }
 
+/// Tables can not store `CHECK` constraints yet, so they are rejected
+/// instead of being ignored
+fn check_unsupported(column: &ColumnInfo) -> Result<(), ExecutionError> {
+    match column.check {
+        Some(_) => Err(ExecutionError::DebugError("Not implemented: CHECK constraints".into())),
+        None => Ok(()),
+    }
+}
+
+/// Rejects null for a column which doesn't allow it
+fn check_null(column: &Column, lit: &Value) -> Result<(), ExecutionError> {
+    match lit {
+        &Value::Null if !column.allow_null => Err(ExecutionError::NullValue(column.name.clone())),
+        _ => Ok(()),
+    }
+}
+
 #[derive(PartialEq, Clone)]
 pub enum Where {
     Select,
@@ -0,0 +1,171 @@
+//! Caches for results of read-only queries
+//!
+//! Writes go through the tables directly, so every write has to evict the
+//! cached results of the tables it changes.
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::ResultSet;
+
+/// A map holding at most `capacity` entries. If it is full, an insert
+/// removes the entry that was used least recently.
+pub struct LruCache<K, V> {
+    capacity: usize,
+    /// The values with the tick of their last use
+    entries: HashMap<K, (V, u64)>,
+    tick: u64,
+}
+
+impl<K: Hash + Eq + Clone, V> LruCache<K, V> {
+    /// A cache with a capacity of 0 stores nothing
+    pub fn new(capacity: usize) -> LruCache<K, V> {
+        LruCache { capacity: capacity, entries: HashMap::new(), tick: 0 }
+    }
+
+    /// Returns the value of `key` and marks it as used
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        self.tick += 1;
+        let tick = self.tick;
+        self.entries.get_mut(key).map(|entry| {
+            entry.1 = tick;
+            &entry.0
+        })
+    }
+
+    pub fn insert(&mut self, key: K, value: V) {
+        if self.capacity == 0 {
+            return
+        }
+        if !self.entries.contains_key(&key) && self.entries.len() >= self.capacity {
+            self.evict();
+        }
+        self.tick += 1;
+        self.entries.insert(key, (value, self.tick));
+    }
+
+    /// Removes all entries for which `f` returns false
+    pub fn retain<F: FnMut(&K, &V) -> bool>(&mut self, mut f: F) {
+        self.entries.retain(|key, entry| f(key, &entry.0));
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Removes the least recently used entry
+    fn evict(&mut self) {
+        // a linear search, caches are small compared to the tables
+        let oldest = match self.entries.iter().min_by_key(|&(_, entry)| entry.1) {
+            Some((key, _)) => key.clone(),
+            None => return,
+        };
+        self.entries.remove(&oldest);
+    }
+}
+
+/// A cached result with the tables it was read from
+struct CachedResult {
+    tables: Vec<String>,
+    result: ResultSet,
+}
+
+/// Results of selects by database and normalized query text, shared by all
+/// connections of a server. The size is the number of results, regardless
+/// of how many rows they have.
+pub struct QueryCache {
+    entries: Mutex<LruCache<(String, String), CachedResult>>,
+    /// Incremented by every eviction of a table or database
+    generation: AtomicUsize,
+    hits: AtomicUsize,
+}
+
+impl QueryCache {
+    pub fn new(size: usize) -> QueryCache {
+        QueryCache {
+            entries: Mutex::new(LruCache::new(size)),
+            generation: AtomicUsize::new(0),
+            hits: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns a copy of the cached result of `query` in `database`
+    pub fn get(&self, database: &str, query: &str) -> Option<ResultSet> {
+        let key = (database.to_string(), query.to_string());
+        let result = self.entries.lock().unwrap().get(&key).map(|e| e.result.clone());
+        if result.is_some() {
+            self.hits.fetch_add(1, Ordering::SeqCst);
+        }
+        result
+    }
+
+    /// Has to be read before the query is executed and passed to `insert`
+    pub fn generation(&self) -> usize {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    /// Caches the result of `query`, which read `tables`. Nothing is cached
+    /// if one of the tables was written since `generation` was read, as the
+    /// result may be outdated already.
+    pub fn insert(&self, database: &str, query: &str, tables: Vec<String>, result: ResultSet,
+        generation: usize)
+    {
+        let mut entries = self.entries.lock().unwrap();
+        if self.generation() != generation {
+            return
+        }
+        let key = (database.to_string(), query.to_string());
+        entries.insert(key, CachedResult { tables: tables, result: result });
+    }
+
+    /// Removes the results read from `table`, called after it was written
+    pub fn evict_table(&self, database: &str, table: &str) {
+        let mut entries = self.entries.lock().unwrap();
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        entries.retain(|key, entry| {
+            key.0 != database || !entry.tables.iter().any(|t| t == table)
+        });
+    }
+
+    /// Removes all results of `database`, e.g. after it was dropped
+    pub fn evict_database(&self, database: &str) {
+        let mut entries = self.entries.lock().unwrap();
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        entries.retain(|key, _| key.0 != database);
+    }
+
+    /// The number of queries that were answered from the cache
+    pub fn hits(&self) -> usize {
+        self.hits.load(Ordering::SeqCst)
+    }
+
+    /// The number of cached results
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+}
+
+#[test]
+fn test_lru_eviction() {
+    let mut cache = LruCache::new(2);
+    cache.insert(1, "a");
+    cache.insert(2, "b");
+
+    // 1 was used after 2, so 2 is evicted
+    assert_eq!(cache.get(&1), Some(&"a"));
+    cache.insert(3, "c");
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache.get(&2), None);
+    assert_eq!(cache.get(&1), Some(&"a"));
+    assert_eq!(cache.get(&3), Some(&"c"));
+
+    // replacing a value evicts nothing
+    cache.insert(3, "d");
+    assert_eq!(cache.get(&1), Some(&"a"));
+    assert_eq!(cache.get(&3), Some(&"d"));
+
+    let mut disabled = LruCache::new(0);
+    disabled.insert(1, "a");
+    assert_eq!(disabled.get(&1), None);
+}
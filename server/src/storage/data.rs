@@ -251,7 +251,7 @@ impl<B: Write + Read + Seek> Rows <B> {
     }
 
     /// Sets value of column_index' column to new_value.
-    fn set_value(&self, row_data: &mut[u8], new_value: &[u8], column_index: usize)
+    pub fn set_value(&self, row_data: &mut[u8], new_value: &[u8], column_index: usize)
     {
         // start index of column
         let s = self.column_offsets[column_index] as usize;
@@ -338,29 +338,39 @@ impl<B: Write + Read + Seek> Rows <B> {
         // do lookups
         info!("doing lookups to search for matches ....");
         {
-            let mut it = pks.iter();
-            let first = match it.next() {
-                Some(x) => x,
-                None => return Err(Error::FoundNoPrimaryKey),
-            };
-
-            let val = try!(self.get_value(row_data, *first));
-            let mut look = try!(self.lookup(*first, (&val, None), CompType::Equ));
-
-            loop {
-                match it.next() {
-                    Some(x) => {
-                        let value = try!(self.get_value(row_data, *x));
-                        look = try!(look.lookup(*x, (&value, None), CompType::Equ));
-                        if try!(look.is_empty()) {
-                            break;
-                        }
+            if pks.is_empty() {
+                return Err(Error::FoundNoPrimaryKey);
+            }
+            // null equals no other value, so a key holding null is never a duplicate
+            let mut keys = Vec::new();
+            for &index in &pks {
+                let cell = try!(self.get_value(row_data, index));
+                match self.get_column(index).value_of(&cell) {
+                    Some(value) => keys.push((index, value.to_vec())),
+                    None => {
+                        keys.clear();
+                        break;
                     },
-                    None => break,
-                };
+                }
             }
-            if !try!(look.is_empty()) {
-                return Err(Error::PrimaryKeyValueExists);
+            let mut it = keys.iter();
+            if let Some(&(first, ref val)) = it.next() {
+                let mut look = try!(self.lookup(first, (val, None), CompType::Equ));
+
+                loop {
+                    match it.next() {
+                        Some(&(x, ref value)) => {
+                            look = try!(look.lookup(x, (value, None), CompType::Equ));
+                            if try!(look.is_empty()) {
+                                break;
+                            }
+                        },
+                        None => break,
+                    };
+                }
+                if !try!(look.is_empty()) {
+                    return Err(Error::PrimaryKeyValueExists);
+                }
             }
         }
         try!(self.set_pos(SeekFrom::End(0)));
@@ -528,7 +538,15 @@ impl<B: Write + Read + Seek> Rows <B> {
                 Ok(_) => {
                     let col = self.get_column(column_index);
 
-                    let row_value: &Vec<u8> = &try!(self.get_value(&row, column_index));
+                    let row_cell = try!(self.get_value(&row, column_index));
+                    // a comparison with null is never true
+                    let row_value = match col.value_of(&row_cell) {
+                        Some(row_value) => row_value,
+                        None => {
+                            row.clear();
+                            continue;
+                        },
+                    };
                     if value.1.is_none() {
                         let cmp_result = try!(col.sql_type.cmp(row_value, value.0, comp));
 
@@ -540,7 +558,14 @@ impl<B: Write + Read + Seek> Rows <B> {
                         }
                     } else {
                         let cmpindex = value.1.unwrap();
-                        let cmp_value: &Vec<u8> = &try!(self.get_value(&row, cmpindex));
+                        let cmp_cell = try!(self.get_value(&row, cmpindex));
+                        let cmp_value = match self.get_column(cmpindex).value_of(&cmp_cell) {
+                            Some(cmp_value) => cmp_value,
+                            None => {
+                                row.clear();
+                                continue;
+                            },
+                        };
                         let cmp_result = try!(col.sql_type.cmp(row_value, cmp_value, comp));
                         if cmp_result {
                             false
@@ -641,7 +666,7 @@ impl RowHeader{
 }
 
 /// Encodable and decodable representation of a Rows object
-#[derive(Debug, RustcEncodable, RustcDecodable)]
+#[derive(Debug, Clone, RustcEncodable, RustcDecodable)]
 pub struct ResultSet {
     pub data: Vec<u8>,
     pub columns: Vec<Column>,
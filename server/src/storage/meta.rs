@@ -1,7 +1,9 @@
 use std::mem;
+use std::io;
 use std::io::prelude::*;
 use std::fs;
-use std::fs::{OpenOptions, create_dir, remove_dir_all};
+use std::fs::{OpenOptions, File, create_dir, remove_dir_all};
+use std::path::PathBuf;
 
 use byteorder::{WriteBytesExt, ReadBytesExt, BigEndian};
 
@@ -18,7 +20,11 @@ use super::EngineID;
 
 /// constants
 const MAGIC_NUMBER: u64 = 0x49616D4372616E43;
-const VERSION_NO: u8 = 1;
+/// Version 2 stores a null flag in the cells of columns which allow null
+const VERSION_NO: u8 = 2;
+/// Upper bound for the metadata of a table, so a corrupt file can not make
+/// the server allocate arbitrary amounts of memory
+const MAX_META_SIZE: u64 = 1024 * 1024;
 
 
 
@@ -43,34 +49,73 @@ impl DataType {
 #[derive(Debug)]
 pub struct Database {
     pub name: String,
+    path: String,
 }
 
 impl Database {
-    /// Starts the process of creating a new Database
+    /// Starts the process of creating a new Database in the working directory
     /// Returns database or on fail Error
     pub fn create(name: &str) -> Result<Database, Error> {
-        let d = Database{ name: name.to_string() };
+        Database::create_in(".", name)
+    }
+
+    /// Creates a new Database as a subdirectory of `dir`
+    /// Returns database or on fail Error
+    pub fn create_in(dir: &str, name: &str) -> Result<Database, Error> {
+        if !is_valid_name(name) {
+            return Err(Error::InvalidDataBaseName)
+        }
+        let d = Database{ name: name.to_string(), path: Database::get_path(dir, name) };
         try!(d.save());
         info!("created new database {:?}", d);
         Ok(d)
     }
 
-    /// Loads already existing Database
+    /// Loads already existing Database from the working directory
     /// returns DataBase Error when database does not exist else the loaded DB
     pub fn load(name: &str) -> Result<Database, Error> {
-        if try!(fs::metadata(name)).is_dir() {
+        Database::load_in(".", name)
+    }
+
+    /// Loads already existing Database from the subdirectory `name` of `dir`
+    /// returns DataBase Error when database does not exist else the loaded DB
+    pub fn load_in(dir: &str, name: &str) -> Result<Database, Error> {
+        let path = Database::get_path(dir, name);
+        if is_valid_name(name) && fs::metadata(&path).map(|m| m.is_dir()).unwrap_or(false) {
             info!("loaded Database {:?}", name.to_string());
-            Ok(Database{ name: name.to_string() })
+            Ok(Database{ name: name.to_string(), path: path })
         } else {
             warn!("could not load database {:?}", name.to_string());
             return Err(Error::LoadDataBase)
         }
     }
 
+    /// Returns the names of all databases in `dir`, sorted
+    pub fn names_in(dir: &str) -> Result<Vec<String>, Error> {
+        let mut names = Vec::new();
+        for entry in try!(fs::read_dir(dir)) {
+            let path = try!(entry).path();
+            if path.is_dir() {
+                if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
+                    if is_valid_name(name) {
+                        names.push(name.to_string());
+                    }
+                }
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// Returns the path of the database folder
+    fn get_path(dir: &str, name: &str) -> String {
+        format!("{}/{}", dir, name)
+    }
+
     /// Creates a folder for the database
     fn save(&self) -> Result<(), Error> {
         info!("trying to create dir!");
-        try!(create_dir(&self.name));
+        try!(create_dir(&self.path));
         info!("created dir");
         Ok(())
     }
@@ -78,7 +123,7 @@ impl Database {
     /// do not use RANDOM!!
     pub fn delete(&self) -> Result<(), Error> {
         info!("deleting Database and all its tables");
-        try!(remove_dir_all(&self.name));
+        try!(remove_dir_all(&self.path));
         Ok(())
     }
     /// Creates a new table in the DB folder
@@ -97,8 +142,76 @@ impl Database {
     pub fn load_table(&self, name: &str) -> Result<Table, Error> {
         Table::load(&self, name)
     }
+
+    /// Returns the names of all tables in the database folder, sorted
+    pub fn table_names(&self) -> Result<Vec<String>, Error> {
+        let mut names = Vec::new();
+        for entry in try!(fs::read_dir(&self.path)) {
+            let path = try!(entry).path();
+            if path.extension().and_then(|e| e.to_str()) == Some("tbl") {
+                if let Some(name) = path.file_stem().and_then(|n| n.to_str()) {
+                    names.push(name.to_string());
+                }
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// Reads the files of all tables into memory, so they can be put back
+    /// with `Snapshot::restore`
+    pub fn snapshot(&self) -> Result<Snapshot, Error> {
+        let mut files = Vec::new();
+        for entry in try!(fs::read_dir(&self.path)) {
+            let path = try!(entry).path();
+            if path.is_file() {
+                let mut content = Vec::new();
+                try!(try!(File::open(&path)).read_to_end(&mut content));
+                files.push((path, content));
+            }
+        }
+        Ok(Snapshot { database: self.name.clone(), path: self.path.clone(), files: files })
+    }
 }
 
+/// The table files of a database at one point in time
+#[derive(Debug)]
+pub struct Snapshot {
+    database: String,
+    path: String,
+    files: Vec<(PathBuf, Vec<u8>)>,
+}
+
+impl Snapshot {
+    /// Name of the database the snapshot was taken of
+    pub fn database(&self) -> &str {
+        &self.database
+    }
+
+    /// Writes the files back and removes the ones created since the
+    /// snapshot was taken, e.g. by `create table`
+    pub fn restore(&self) -> Result<(), Error> {
+        // the database may have been dropped in the meantime
+        try!(fs::create_dir_all(&self.path));
+        for entry in try!(fs::read_dir(&self.path)) {
+            let path = try!(entry).path();
+            if path.is_file() && !self.files.iter().any(|&(ref p, _)| *p == path) {
+                try!(fs::remove_file(&path));
+            }
+        }
+        for &(ref path, ref content) in &self.files {
+            try!(try!(File::create(path)).write_all(content));
+        }
+        Ok(())
+    }
+}
+
+
+/// Database names may only contain letters, digits and underscores, so they
+/// cannot point outside of the data directory
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
 
 //---------------------------------------------------------------
 // TableMetaData
@@ -152,11 +265,13 @@ impl<'a> Table<'a> {
     {
         // TODO: Read the .tbl file from disk and parse it
 
-        let path_to_table = Table::get_path(&database.name, name, "tbl");
+        let path_to_table = Table::get_path(&database.path, name, "tbl");
         info!("getting path and opening file: {:?}", path_to_table);
-        let mut file = try!(OpenOptions::new()
-            .read(true)
-            .open(path_to_table));
+        let mut file = match OpenOptions::new().read(true).open(path_to_table) {
+            Ok(file) => file,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Err(Error::UnknownTable),
+            Err(e) => return Err(e.into()),
+        };
         info!("reading file: {:?}", file);
         let ma_nmbr = try!(file.read_uint::<BigEndian>(mem::size_of_val(&MAGIC_NUMBER)));
 
@@ -165,7 +280,14 @@ impl<'a> Table<'a> {
             info!("Magic Number not correct");
             return Err(Error::WrongMagicNmbr)
         }
-        let meta_data: TableMetaData = try!(decode_from(&mut file, SizeLimit::Infinite));
+        let mut meta_data: TableMetaData =
+            try!(decode_from(&mut file, SizeLimit::Bounded(MAX_META_SIZE)));
+        // the rows of older tables have no null flags, they could not hold null
+        if meta_data.version_nmbr < 2 {
+            for column in &mut meta_data.columns {
+                column.allow_null = false;
+            }
+        }
         info!("getting meta data{:?}", meta_data);
 
         let table = Table::new(database, name, meta_data.columns, meta_data.engine_id);
@@ -260,6 +382,38 @@ impl<'a> Table<'a> {
         Ok(())
     }
 
+    /// Renames the table together with its files
+    /// Returns Error::TableExists if the database has a table `name` already
+    pub fn rename(&mut self, name: &str) -> Result<(), Error> {
+        let metadata_path = Self::get_path(&self.database.path, name, "tbl");
+        if fs::metadata(&metadata_path).is_ok() {
+            return Err(Error::TableExists)
+        }
+        try!(fs::rename(self.get_table_metadata_path(), metadata_path));
+        try!(fs::rename(self.get_table_data_path(),
+            Self::get_path(&self.database.path, name, "dat")));
+        info!("Table {:?} was renamed to {:?}", self.name, name);
+        self.name = name.to_string();
+        Ok(())
+    }
+
+    /// Renames a column, the rows stay the same
+    /// Returns Error::InvalidColumn if the table has no column `from` and
+    /// Error::AddColumn if it has a column `to` already
+    pub fn rename_column(&mut self, from: &str, to: &str) -> Result<(), Error> {
+        if self.meta_data.columns.iter().any(|x| x.name == to) {
+            warn!("Column {:?} already exists", to);
+            return Err(Error::AddColumn)
+        }
+        match self.meta_data.columns.iter_mut().find(|x| x.name == from) {
+            Some(column) => {
+                column.name = to.to_string();
+                Ok(())
+            },
+            None => Err(Error::InvalidColumn),
+        }
+    }
+
     /// Creates an engine for Table
     /// Returns Box<Engine>
     pub fn create_engine(self) -> Box<Engine + 'a> {
@@ -279,12 +433,12 @@ impl<'a> Table<'a> {
 
     /// Returns the path for the metadata files
     fn get_table_metadata_path(&self) -> String {
-        Self::get_path(&self.database.name, &self.name, "tbl")
+        Self::get_path(&self.database.path, &self.name, "tbl")
     }
 
     /// Returns the path for the data files
     pub fn get_table_data_path(&self) -> String {
-        Self::get_path(&self.database.name, &self.name, "dat")
+        Self::get_path(&self.database.path, &self.name, "dat")
     }
 
     /// Returns the path of the table
@@ -5,6 +5,7 @@ mod engine;
 mod meta;
 pub mod types;
 pub mod bstar;
+pub mod cache;
 
 mod data;
 
@@ -12,8 +13,10 @@ use bincode::rustc_serialize::{EncodingError, DecodingError};
 
 pub use self::meta::Table;
 pub use self::meta::Database;
+pub use self::meta::Snapshot;
 pub use self::data::Rows;
 pub use self::data::ResultSet;
+pub use self::cache::QueryCache;
 pub use self::types::Column;
 pub use self::types::SqlType;
 pub use self::engine::FlatFile;
@@ -63,6 +66,9 @@ pub enum Error {
     PrimaryKeyValueExists,
     FoundNoPrimaryKey,
     PrimaryKeyNotAllowed,
+    InvalidDataBaseName,
+    UnknownTable,
+    TableExists,
 }
 
 impl From<NulError> for Error {
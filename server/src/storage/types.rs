@@ -1,5 +1,5 @@
 use super::Error;
-use super::super::parse::token::Lit;
+use super::super::value::Value;
 use super::super::parse::ast::CompType;
 
 use byteorder::{BigEndian, WriteBytesExt, ReadBytesExt};
@@ -14,6 +14,13 @@ pub enum SqlType {
     Int,
     Bool,
     Char(u8),
+    VarChar(u16),
+    Float,
+    Double,
+    /// Stored as days since 1970-01-01
+    Date,
+    /// Stored as seconds since 1970-01-01 00:00:00 UTC
+    Timestamp,
 }
 
 
@@ -25,24 +32,45 @@ impl SqlType {
             &SqlType::Int => 4 as u32,
             &SqlType::Bool => 1 as u32,
             &SqlType::Char(len) => (len) as u32,
+            &SqlType::VarChar(len) => (len) as u32,
+            &SqlType::Float => 4 as u32,
+            &SqlType::Double => 8 as u32,
+            &SqlType::Date => 4 as u32,
+            &SqlType::Timestamp => 8 as u32,
         }
     }
 
-    /// Decodes the data in buf according to SqlType into a Lit enum.
-    pub fn decode_from<R: Read>(&self, mut buf: &mut R) -> Result<Lit, Error> {
+    /// Decodes the data in buf according to SqlType into a Value.
+    pub fn decode_from<R: Read>(&self, mut buf: &mut R) -> Result<Value, Error> {
         match self {
             &SqlType::Int => {
                 let i = try!(buf.read_i32::<BigEndian>());
-                Ok(Lit::Int(i as i64))
+                Ok(Value::Int(i as i64))
             },
             &SqlType::Bool => {
                 let b = try!(buf.read_u8());
-                Ok(Lit::Bool(b))
+                Ok(Value::Bool(b != 0))
             },
-            &SqlType::Char(_) => {
+            &SqlType::Char(_) | &SqlType::VarChar(_) => {
                 let mut s = String::new();
                 try!(buf.read_to_string(&mut s));
-                Ok(Lit::String(s))
+                Ok(Value::Str(s))
+            },
+            &SqlType::Float => {
+                let f = try!(buf.read_f32::<BigEndian>());
+                Ok(Value::Float(f as f64))
+            },
+            &SqlType::Double => {
+                let f = try!(buf.read_f64::<BigEndian>());
+                Ok(Value::Float(f))
+            },
+            &SqlType::Date => {
+                let d = try!(buf.read_i32::<BigEndian>());
+                Ok(Value::Date(d as i64))
+            },
+            &SqlType::Timestamp => {
+                let t = try!(buf.read_i64::<BigEndian>());
+                Ok(Value::Timestamp(t))
             },
         }
     }
@@ -50,18 +78,18 @@ impl SqlType {
 
     /// Writes data to buf
     /// Returns the bytes written.
-    /// Returns Error::InvalidType if type of Lit does not match expected
+    /// Returns Error::InvalidType if type of Value does not match expected
     /// type.
     /// Returns byteorder::Error, if data could not be written to buf.
-    /// Lit: contains data to write to buf
+    /// data: contains data to write to buf
     /// buf: target of write operation.
-    pub fn encode_into<W: Write>(&self, mut buf: &mut W, data: &Lit)
+    pub fn encode_into<W: Write>(&self, mut buf: &mut W, data: &Value)
     -> Result<u32, Error>
     {
         match self {
             &SqlType::Int => {
                 match data {
-                    &Lit::Int(a) => {
+                    &Value::Int(a) => {
                         if a > i32::max_value() as i64 {
                             Err(Error::InvalidType)
                         }
@@ -77,7 +105,7 @@ impl SqlType {
             },
             &SqlType::Bool => {
                 match data {
-                    &Lit::Bool(a) => {
+                    &Value::Bool(a) => {
                         try!(buf.write_u8(a as u8));
                         Ok(self.size())
                     }
@@ -86,10 +114,10 @@ impl SqlType {
                     }
                 }
             },
-            &SqlType::Char(len) => {
+            &SqlType::Char(_) | &SqlType::VarChar(_) => {
                 match data {
-                    &Lit::String(ref a) => {
-                        let str_as_bytes = Self::to_nul_terminated_bytes(&a, len as u32);
+                    &Value::Str(ref a) => {
+                        let str_as_bytes = Self::to_nul_terminated_bytes(&a, self.size());
                         try!(buf.write_all(&str_as_bytes));
                         Ok(self.size())
                     }
@@ -98,6 +126,39 @@ impl SqlType {
                     }
                 }
             },
+            // integers are converted, `3` is as good as `3.0`
+            &SqlType::Float => {
+                match data {
+                    &Value::Float(a) => try!(buf.write_f32::<BigEndian>(a as f32)),
+                    &Value::Int(a) => try!(buf.write_f32::<BigEndian>(a as f32)),
+                    _ => return Err(Error::InvalidType)
+                }
+                Ok(self.size())
+            },
+            &SqlType::Double => {
+                match data {
+                    &Value::Float(a) => try!(buf.write_f64::<BigEndian>(a)),
+                    &Value::Int(a) => try!(buf.write_f64::<BigEndian>(a as f64)),
+                    _ => return Err(Error::InvalidType)
+                }
+                Ok(self.size())
+            },
+            &SqlType::Date => {
+                match data {
+                    &Value::Date(a) if a >= i32::min_value() as i64 &&
+                        a <= i32::max_value() as i64 =>
+                        try!(buf.write_i32::<BigEndian>(a as i32)),
+                    _ => return Err(Error::InvalidType)
+                }
+                Ok(self.size())
+            },
+            &SqlType::Timestamp => {
+                match data {
+                    &Value::Timestamp(a) => try!(buf.write_i64::<BigEndian>(a)),
+                    _ => return Err(Error::InvalidType)
+                }
+                Ok(self.size())
+            },
         }
     }
 
@@ -124,7 +185,8 @@ impl SqlType {
     {
         info!("checking Compare type: {:?}", comp);
         match self {
-            &SqlType::Int => {
+            // dates are stored like integers
+            &SqlType::Int | &SqlType::Date => {
                 match comp {
                     CompType::Equ => {
                         self.equal_for_int_with_value(val, val2)
@@ -161,7 +223,7 @@ impl SqlType {
                 }
             },
 
-            &SqlType::Char(_) => {
+            &SqlType::Char(_) | &SqlType::VarChar(_) => {
                 match comp {
                     CompType::Equ => {
                         self.compare_byte_for_equal(val, val2)
@@ -183,6 +245,14 @@ impl SqlType {
                     },
                 }
             },
+
+            &SqlType::Float | &SqlType::Double => {
+                self.compare_as_float(val, val2, comp)
+            },
+
+            &SqlType::Timestamp => {
+                self.compare_as_i64(val, val2, comp)
+            },
         }
     }
     /// fn compares slices of u8 byte for byte and returns if both values are equal
@@ -240,6 +310,42 @@ impl SqlType {
         info!("start comparing bool");
         Ok(val == val2)
     }
+    /// converts both values to floats of the size of the type and compares
+    /// them, floats can not be compared byte for byte
+    fn compare_as_float(&self, val: &[u8], val2: &[u8], comp: CompType)
+    -> Result<bool, Error>
+    {
+        let (f1, f2) = match self {
+            &SqlType::Float => (try!(f32::from_sql(val)) as f64, try!(f32::from_sql(val2)) as f64),
+            _ => (try!(f64::from_sql(val)), try!(f64::from_sql(val2))),
+        };
+        info!("start comparing floats");
+        Ok(match comp {
+            CompType::Equ => f1 == f2,
+            CompType::NEqu => f1 != f2,
+            CompType::GThan => f1 > f2,
+            CompType::SThan => f1 < f2,
+            CompType::GEThan => f1 >= f2,
+            CompType::SEThan => f1 <= f2,
+        })
+    }
+
+    /// converts both values to i64 and compares them (needs 8 bytes)
+    fn compare_as_i64(&self, val: &[u8], val2: &[u8], comp: CompType)
+    -> Result<bool, Error>
+    {
+        let (i1, i2) = (try!(i64::from_sql(val)), try!(i64::from_sql(val2)));
+        info!("start comparing i64");
+        Ok(match comp {
+            CompType::Equ => i1 == i2,
+            CompType::NEqu => i1 != i2,
+            CompType::GThan => i1 > i2,
+            CompType::SThan => i1 < i2,
+            CompType::GEThan => i1 >= i2,
+            CompType::SEThan => i1 <= i2,
+        })
+    }
+
     /// converts value to i32 and compares if equal (needs 4 bytes)
     /// returns boolean if successful returns Error if not
     fn equal_for_int_with_value(&self, val: &[u8], val2: &[u8])
@@ -381,11 +487,51 @@ impl Column {
         &self.name
     }
 
+    /// Returns the size of a cell of this column. A cell of a column which
+    /// allows null starts with a flag byte, which is `NULL_FLAG` for null.
     pub fn get_size(&self) -> u32 {
-        self.sql_type.size() as u32
+        self.sql_type.size() as u32 + if self.allow_null { 1 } else { 0 }
+    }
+
+    /// Returns the value of a cell without the null flag, None if the cell
+    /// holds null
+    pub fn value_of<'a>(&self, cell: &'a [u8]) -> Option<&'a [u8]> {
+        if !self.allow_null {
+            Some(cell)
+        } else if cell.first() == Some(&NULL_FLAG) {
+            None
+        } else {
+            cell.get(1..)
+        }
+    }
+
+    /// Writes a cell of this column to buf, the value of a null cell is
+    /// filled with zeros.
+    /// Returns the bytes written.
+    /// Returns Error::InvalidType if data does not fit into the column,
+    /// null included.
+    pub fn encode_into<W: Write>(&self, mut buf: &mut W, data: &Value)
+    -> Result<u32, Error>
+    {
+        match (data, self.allow_null) {
+            (&Value::Null, false) => Err(Error::InvalidType),
+            (&Value::Null, true) => {
+                try!(buf.write_u8(NULL_FLAG));
+                try!(buf.write_all(&vec![0; self.sql_type.size() as usize]));
+                Ok(self.get_size())
+            },
+            (_, true) => {
+                try!(buf.write_u8(0));
+                Ok(try!(self.sql_type.encode_into(buf, data)) + 1)
+            },
+            (_, false) => self.sql_type.encode_into(buf, data),
+        }
     }
 }
 
+/// First byte of a null cell in a column which allows null
+pub const NULL_FLAG: u8 = 1;
+
 //---------------------------------------------------------------
 // FromSql
 //---------------------------------------------------------------
@@ -401,6 +547,13 @@ impl FromSql for i32 {
     }
 }
 
+impl FromSql for i64 {
+    fn from_sql(mut data: &[u8]) -> Result<Self, Error> {
+        let i = try!(data.read_i64::<BigEndian>());
+        Ok(i)
+    }
+}
+
 impl FromSql for u16 {
     fn from_sql(mut data: &[u8]) -> Result<Self, Error> {
         let u = try!(data.read_u16::<BigEndian>());
@@ -415,6 +568,20 @@ impl FromSql for u8 {
     }
 }
 
+impl FromSql for f32 {
+    fn from_sql(mut data: &[u8]) -> Result<Self, Error> {
+        let f = try!(data.read_f32::<BigEndian>());
+        Ok(f)
+    }
+}
+
+impl FromSql for f64 {
+    fn from_sql(mut data: &[u8]) -> Result<Self, Error> {
+        let f = try!(data.read_f64::<BigEndian>());
+        Ok(f)
+    }
+}
+
 impl FromSql for String {
     fn from_sql(data: &[u8]) -> Result<Self, Error> {
         let cstr = try!(CString::new(data));
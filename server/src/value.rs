@@ -0,0 +1,194 @@
+//! A value of a query: parsed as literal and sent as parameter of a
+//! prepared statement.
+use parse::ast::DataSrc;
+use storage::SqlType;
+use std::fmt;
+
+#[derive(RustcEncodable, RustcDecodable, Debug, Clone, PartialEq)]
+pub enum Value {
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Bool(bool),
+    /// Days since 1970-01-01
+    Date(i64),
+    /// Seconds since 1970-01-01 00:00:00 UTC
+    Timestamp(i64),
+    Null,
+}
+
+impl Value {
+    pub fn into_DataSrc(&self) -> DataSrc {
+        match self {
+            &Value::Str(ref s) => DataSrc::String(s.clone()),
+            &Value::Int(i) => DataSrc::Int(i),
+            &Value::Float(f) => DataSrc::String(f.to_string()),
+            &Value::Bool(b) => DataSrc::Bool(b as u8),
+            &Value::Date(d) => DataSrc::Int(d),
+            &Value::Timestamp(t) => DataSrc::Int(t),
+            &Value::Null => DataSrc::Null,
+        }
+    }
+
+    /// The type of a column the value can be stored in. Strings fit into
+    /// every `Char` and `VarChar` column, so their length is not known here.
+    pub fn as_sql_type(&self) -> SqlType {
+        match self {
+            &Value::Str(_) => SqlType::Char(0),
+            &Value::Int(_) => SqlType::Int,
+            &Value::Float(_) => SqlType::Double,
+            &Value::Bool(_) => SqlType::Bool,
+            &Value::Date(_) => SqlType::Date,
+            &Value::Timestamp(_) => SqlType::Timestamp,
+            // null fits every type, callers have to check for it first
+            &Value::Null => SqlType::Char(0),
+        }
+    }
+
+    /// Parses a date like `2024-01-31`, returns None if it is no valid date.
+    pub fn date(s: &str) -> Option<Value> {
+        parse_date(s).map(Value::Date)
+    }
+
+    /// Parses a timestamp like `2024-01-31 10:00:00`, returns None if it is
+    /// no valid date and time.
+    pub fn timestamp(s: &str) -> Option<Value> {
+        let mut parts = s.splitn(2, ' ');
+        let days = match parts.next().and_then(parse_date) {
+            Some(days) => days,
+            None => return None,
+        };
+        let time: Vec<_> = match parts.next() {
+            Some(time) => time.split(':').collect(),
+            None => return None,
+        };
+        if time.len() != 3 {
+            return None
+        }
+        match (parse_digits(time[0], 2), parse_digits(time[1], 2), parse_digits(time[2], 2)) {
+            (Some(h), Some(m), Some(s)) if h < 24 && m < 60 && s < 60 =>
+                Some(Value::Timestamp(days * 86400 + h * 3600 + m * 60 + s)),
+            _ => None,
+        }
+    }
+}
+
+/// Formats days since 1970-01-01 as `YYYY-MM-DD`
+pub fn format_date(days: i64) -> String {
+    // see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = (if z >= 0 { z } else { z - 146096 }) / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Formats seconds since 1970-01-01 00:00:00 as `YYYY-MM-DD hh:mm:ss`
+pub fn format_timestamp(secs: i64) -> String {
+    let (days, secs) = (div_floor(secs, 86400), secs - div_floor(secs, 86400) * 86400);
+    format!("{} {:02}:{:02}:{:02}", format_date(days), secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+fn div_floor(a: i64, b: i64) -> i64 {
+    if a >= 0 { a / b } else { (a - b + 1) / b }
+}
+
+/// Parses exactly `len` ASCII digits
+fn parse_digits(s: &str, len: usize) -> Option<i64> {
+    if s.len() != len || !s.bytes().all(|b| b'0' <= b && b <= b'9') {
+        return None
+    }
+    s.parse().ok()
+}
+
+/// Parses `YYYY-MM-DD` into days since 1970-01-01
+fn parse_date(s: &str) -> Option<i64> {
+    let parts: Vec<_> = s.split('-').collect();
+    if parts.len() != 3 {
+        return None
+    }
+    let (year, month, day) =
+        match (parse_digits(parts[0], 4), parse_digits(parts[1], 2), parse_digits(parts[2], 2)) {
+            (Some(y), Some(m), Some(d)) => (y, m, d),
+            _ => return None,
+        };
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let month_len = match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        1 ... 12 => 31,
+        _ => return None,
+    };
+    if day < 1 || day > month_len {
+        return None
+    }
+    // see http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = (if y >= 0 { y } else { y - 399 }) / 400;
+    let yoe = y - era * 400;
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    Some(era * 146097 + doe - 719468)
+}
+
+/// Prints the value as it would be written in a query
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            // quotation marks are escaped by doubling them
+            &Value::Str(ref s) => write!(f, "'{}'", s.replace("'", "''")),
+            &Value::Int(i) => i.fmt(f),
+            // the lexer knows no exponents, so the float is written out and
+            // keeps a dot, so the literal stays a float
+            &Value::Float(v) => {
+                let s = v.to_string();
+                if s.contains('.') || !v.is_finite() {
+                    write!(f, "{}", s)
+                } else {
+                    write!(f, "{}.0", s)
+                }
+            },
+            &Value::Bool(false) => write!(f, "false"),
+            &Value::Bool(true) => write!(f, "true"),
+            &Value::Date(d) => write!(f, "DATE '{}'", format_date(d)),
+            &Value::Timestamp(t) => write!(f, "TIMESTAMP '{}'", format_timestamp(t)),
+            &Value::Null => write!(f, "NULL"),
+        }
+    }
+}
+
+#[test]
+fn test_as_sql_type() {
+    assert_eq!(Value::Int(-3).as_sql_type(), SqlType::Int);
+    assert_eq!(Value::Bool(true).as_sql_type(), SqlType::Bool);
+    assert_eq!(Value::Str("abc".into()).as_sql_type(), SqlType::Char(0));
+    assert_eq!(Value::Float(0.5).as_sql_type(), SqlType::Double);
+    assert_eq!(Value::Null.as_sql_type(), SqlType::Char(0));
+    assert_eq!(Value::Date(0).as_sql_type(), SqlType::Date);
+}
+
+#[test]
+fn test_dates() {
+    assert_eq!(Value::date("1970-01-01"), Some(Value::Date(0)));
+    assert_eq!(Value::date("2024-01-31"), Some(Value::Date(19753)));
+    assert_eq!(Value::date("1969-12-31"), Some(Value::Date(-1)));
+    assert_eq!(Value::date("2024-02-29"), Some(Value::Date(19782)));
+    assert_eq!(Value::timestamp("2024-01-31 10:00:00"),
+        Some(Value::Timestamp(19753 * 86400 + 36000)));
+    for days in &[-719468, -1, 0, 19753, 19782, 2932896] {
+        assert_eq!(Value::date(&format_date(*days)), Some(Value::Date(*days)));
+    }
+    assert_eq!(format_timestamp(-1), "1969-12-31 23:59:59");
+
+    for invalid in &["2024-13-40", "2023-02-29", "2024-1-31", "24-01-31", "2024-01-31 10:00"] {
+        assert_eq!(Value::date(invalid), None);
+    }
+    assert_eq!(Value::timestamp("2024-01-31"), None);
+    assert_eq!(Value::timestamp("2024-01-31 24:00:00"), None);
+}
@@ -0,0 +1,134 @@
+//! Connects to a TLS server with a self-signed certificate, which is
+//! created for every test run.
+//!
+#![cfg(feature = "tls")]
+
+extern crate bincode;
+extern crate openssl;
+extern crate rand;
+extern crate server;
+
+use std::env;
+use std::fs::{self, File};
+use std::io::Write;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bincode::SizeLimit;
+use bincode::rustc_serialize::decode_from;
+use openssl::asn1::Asn1Time;
+use openssl::bn::{BigNum, MsbOption};
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::rsa::Rsa;
+use openssl::x509::{X509, X509NameBuilder};
+use openssl::x509::extension::{BasicConstraints, SubjectAlternativeName};
+use server::{Config, Server};
+use server::net::stream::{self, Certificate};
+use server::net::types::{ClientErrMsg, PkgType};
+
+/// Creates a self-signed certificate for "localhost" and its PKCS #8 key, both
+/// PEM encoded
+fn self_signed() -> (Vec<u8>, Vec<u8>) {
+    let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+    let mut name = X509NameBuilder::new().unwrap();
+    name.append_entry_by_text("CN", "localhost").unwrap();
+    let name = name.build();
+
+    let mut cert = X509::builder().unwrap();
+    cert.set_version(2).unwrap();
+    let mut serial = BigNum::new().unwrap();
+    serial.rand(64, MsbOption::MAYBE_ZERO, false).unwrap();
+    cert.set_serial_number(&serial.to_asn1_integer().unwrap()).unwrap();
+    cert.set_subject_name(&name).unwrap();
+    cert.set_issuer_name(&name).unwrap();
+    cert.set_pubkey(&key).unwrap();
+    cert.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+    cert.set_not_after(&Asn1Time::days_from_now(1).unwrap()).unwrap();
+    cert.append_extension(BasicConstraints::new().critical().ca().build().unwrap()).unwrap();
+    let san = SubjectAlternativeName::new().dns("localhost")
+        .build(&cert.x509v3_context(None, None)).unwrap();
+    cert.append_extension(san).unwrap();
+    cert.sign(&key, MessageDigest::sha256()).unwrap();
+
+    (cert.build().to_pem().unwrap(), key.private_key_to_pem_pkcs8().unwrap())
+}
+
+/// A running TLS server, shut down when it goes out of scope
+struct TestServer {
+    server: Arc<Server>,
+    runner: Option<thread::JoinHandle<()>>,
+    addr: SocketAddr,
+    cert: Vec<u8>,
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.server.shutdown();
+        if let Some(runner) = self.runner.take() {
+            let _ = runner.join();
+        }
+    }
+}
+
+fn start_server() -> TestServer {
+    let (cert, key) = self_signed();
+    let dir = env::temp_dir().join(format!("uosql-tls-{}", rand::random::<u32>()));
+    fs::create_dir_all(&dir).unwrap();
+    let write = |name: &str, pem: &[u8]| {
+        let path = dir.join(name);
+        File::create(&path).unwrap().write_all(pem).unwrap();
+        path.to_string_lossy().into_owned()
+    };
+
+    let config = Config {
+        max_connections: 10,
+        worker_threads: 2,
+        shutdown_timeout: 1,
+        log_queries: false,
+        tls_cert: Some(write("cert.pem", &cert)),
+        tls_key: Some(write("key.pem", &key)),
+        query_cache_size: 0,
+        parse_cache_size: 0,
+        .. Config::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 0, "data".into())
+    };
+    // the files are only read here
+    let server = Arc::new(Server::bind(config).unwrap());
+    fs::remove_dir_all(&dir).unwrap();
+
+    let addr = server.local_addr().unwrap();
+    let runner = {
+        let server = server.clone();
+        thread::spawn(move || server.run())
+    };
+    TestServer { server: server, runner: Some(runner), addr: addr, cert: cert }
+}
+
+#[test]
+fn test_tls_greeting() {
+    let server = start_server();
+    let cert = Certificate::from_pem(&server.cert).unwrap();
+
+    let tcp = TcpStream::connect(server.addr).unwrap();
+    let mut tls = stream::tls_connect(tcp, "localhost", &[cert]).unwrap();
+    let pkg: PkgType = decode_from(&mut tls, SizeLimit::Bounded(1024)).unwrap();
+    assert!(pkg == PkgType::Greet);
+}
+
+#[test]
+fn test_plaintext_client_rejected() {
+    let server = start_server();
+
+    // a plaintext client waits for the greeting and gets an error instead,
+    // long before the handshake would time out
+    let mut tcp = TcpStream::connect(server.addr).unwrap();
+    tcp.set_read_timeout(Some(Duration::from_secs(stream::TLS_HANDSHAKE_TIMEOUT * 3))).unwrap();
+    let start = Instant::now();
+    let pkg: PkgType = decode_from(&mut tcp, SizeLimit::Bounded(1024)).unwrap();
+    assert!(pkg == PkgType::Error);
+    let err: ClientErrMsg = decode_from(&mut tcp, SizeLimit::Bounded(1024)).unwrap();
+    assert_eq!(err.msg, "the server only accepts TLS connections");
+    assert!(start.elapsed() < Duration::from_secs(stream::TLS_HANDSHAKE_TIMEOUT));
+}
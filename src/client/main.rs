@@ -24,7 +24,7 @@ use std::error::Error;
 use std::io::{self, stdout, Write, Read};
 use std::str::FromStr;
 use uosql::logger;
-use uosql::Connection;
+use uosql::{Connection, QueryResult};
 use uosql::types::{DataSet};
 use server::storage::SqlType;
 use docopt::Docopt;
@@ -140,6 +140,34 @@ fn main() {
                 uosql::Error::Server(_) => {
                     error!("{}", e.description());
                     return
+                },
+                uosql::Error::Timeout => {
+                    error!("{}", e.description());
+                    return
+                },
+                uosql::Error::UnknownHost => {
+                    error!("{}", e.description());
+                    return
+                },
+                uosql::Error::ProtocolMismatch { .. } => {
+                    error!("{}", e);
+                    return
+                },
+                uosql::Error::ParamCount { .. } => {
+                    error!("{}", e);
+                    return
+                },
+                uosql::Error::Cancelled => {
+                    error!("{}", e.description());
+                    return
+                },
+                uosql::Error::Poisoned => {
+                    error!("{}, reconnect and try again", e.description());
+                    return
+                },
+                uosql::Error::Parse(_) => {
+                    error!("{}", e);
+                    return
                 }
             }
         }
@@ -402,10 +430,13 @@ fn process_input(input: &str, conn: &mut Connection, history: &Vec<String>) -> b
         },
         _ => { // Queries
             match conn.execute(input.into()) {
-                Ok(mut data) => {
+                Ok(QueryResult::Rows(mut data)) => {
                     // show data belonging to executed query
                     display(&mut data);
                 },
+                Ok(QueryResult::Affected(n)) => {
+                    println!("{} row(s) affected", n);
+                },
                 Err(e) => {
                     match e {
                         uosql::Error::Io(_) => {
@@ -516,10 +547,13 @@ fn execute_sql(mut f: File, conn: &mut Connection) -> bool {
 
         println!("\n Query given was: {}", i);
         match conn.execute(i.into()) {
-            Ok(mut data) => {
-            // show data belonging to executed query
+            Ok(QueryResult::Rows(mut data)) => {
+                // show data belonging to executed query
                 display(&mut data);
             },
+            Ok(QueryResult::Affected(n)) => {
+                println!("{} row(s) affected", n);
+            },
             Err(e) => {
                 match e {
                     uosql::Error::Io(_) => {
@@ -665,6 +699,15 @@ fn display_data(table: &mut DataSet) {
             },
             SqlType::Char(size) => {
                 cols.push(max(size as usize, table.get_col_name(i).unwrap().len()));
+            },
+            SqlType::VarChar(size) => {
+                cols.push(max(size as usize, table.get_col_name(i).unwrap().len()));
+            },
+            SqlType::Float | SqlType::Double => {
+                cols.push(max(12, table.get_col_name(i).unwrap().len()));
+            },
+            SqlType::Date | SqlType::Timestamp => {
+                cols.push(max(19, table.get_col_name(i).unwrap().len()));
             }
         }
     }
@@ -703,9 +746,18 @@ fn display_data(table: &mut DataSet) {
                                     print!("| {1: ^0$} ", min(30, cols[i]), val),
                                 None => print!("| {1: ^0$} ", min(30, cols[i]), "none"),
                             },
-                        SqlType::Char(_) =>
+                        SqlType::Char(_) | SqlType::VarChar(_) =>
+                            print!("| {1: ^0$} ", min(30, cols[i]),
+                                    table.next_char_by_idx(i).unwrap_or("none".into())),
+                        SqlType::Float | SqlType::Double =>
+                            match table.next_float_by_idx(i) {
+                                Some(val) =>
+                                    print!("| {1: ^0$} ", min(30, cols[i]), val),
+                                None => print!("| {1: ^0$} ", min(30, cols[i]), "none"),
+                            },
+                        SqlType::Date | SqlType::Timestamp =>
                             print!("| {1: ^0$} ", min(30, cols[i]),
-                                    table.next_char_by_idx(i).unwrap_or("none".into()))
+                                    table.next_datetime_by_idx(i).unwrap_or("none".into())),
                     }
                 },
                 None => continue
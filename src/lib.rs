@@ -1,20 +1,71 @@
+//! Client library to connect to an uosql server and send queries.
+//!
+//! `Connection::execute` returns the rows of a query as a `DataSet`, which
+//! can be iterated row by row, or the number of rows changed by an insert,
+//! update or delete:
+//!
+//! ```no_run
+//! # use uosql::{Connection, Error, QueryResult};
+//! # fn run() -> Result<(), Error> {
+//! let mut conn = try!(Connection::connect("127.0.0.1".into(), 4242,
+//!     "admin".into(), "pw".into()));
+//! match try!(conn.execute("select id, name from users".into())) {
+//!     QueryResult::Rows(data) => for row in data {
+//!         println!("{:?} {:?}", row.get_i64(0), row.get_str(1));
+//!     },
+//!     QueryResult::Affected(n) => println!("{} rows changed", n),
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
 #[macro_use]
 extern crate server;
 extern crate bincode;
+extern crate rustc_serialize;
 
-use std::net::{Ipv4Addr, AddrParseError, TcpStream};
+use std::net::{Ipv4Addr, AddrParseError, TcpStream, SocketAddr, SocketAddrV4,
+    ToSocketAddrs};
 use std::str::FromStr;
-use std::io::{self, Write};
+use std::io::{self, Write, Read};
+use std::fs::File;
+use std::path::Path;
 use std::fmt;
+use std::time::{Duration, Instant};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
 pub use server::net::types;
 pub use server::logger;
+#[cfg(feature = "tls")]
+pub use server::net::stream::Certificate;
+use server::net::Stream;
+use server::auth;
+use server::parse::ast::{Query, ManipulationStmt};
+use server::parse::parser::ParseError;
 use server::storage::ResultSet;
 use bincode::SizeLimit;
 use bincode::rustc_serialize::{EncodingError, DecodingError,
     decode_from, encode_into};
+use rustc_serialize::Decodable;
 use types::*;
 
-const PROTOCOL_VERSION : u8 = 1;
+mod pool;
+pub use pool::{ConnectionPool, PooledConnection};
+#[cfg(test)]
+mod test_server;
+
+/// The client speaks the protocol of the server crate it is built with
+const PROTOCOL_VERSION: u8 = server::net::PROTOCOL_VERSION;
+const DEFAULT_ADDRESS: &'static str = "127.0.0.1";
+const DEFAULT_PORT: u16 = 4242;
+/// Limit of messages sent and received, unless changed by
+/// `Connection::with_max_message_size`.
+pub const DEFAULT_MAX_MESSAGE_SIZE: u64 = 64 * 1024 * 1024;
+/// Limit of package headers and other small messages
+const HEADER_SIZE: u64 = 1024;
 
 /// Client specific Error definition.
 #[derive(Debug)]
@@ -26,12 +77,39 @@ pub enum Error {
     Decode(DecodingError),
     Auth,
     Server(ClientErrMsg),
+    Timeout,
+    UnknownHost,
+    ProtocolMismatch { client: u8, server: u8 },
+    ParamCount { expected: usize, given: usize },
+    Cancelled,
+    Poisoned,
+    /// A query could not be parsed by the client, e.g. in `execute_file`
+    Parse(ParseError),
 }
 
 /// Implement display for description of Error
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        std::error::Error::description(self).fmt(f)
+        match self {
+            &Error::ProtocolMismatch { client, server } =>
+                write!(f, "{} (client: {}, server: {})",
+                    std::error::Error::description(self), client, server),
+            &Error::ParamCount { expected, given } =>
+                write!(f, "{} (expected: {}, given: {})",
+                    std::error::Error::description(self), expected, given),
+            // name the cause, e.g. "IO error occured: connection refused"
+            &Error::AddrParse(ref e) =>
+                write!(f, "{}: {}", std::error::Error::description(self), e),
+            &Error::Io(ref e) =>
+                write!(f, "{}: {}", std::error::Error::description(self), e),
+            &Error::Encode(ref e) =>
+                write!(f, "{}: {}", std::error::Error::description(self), e),
+            &Error::Decode(ref e) =>
+                write!(f, "{}: {}", std::error::Error::description(self), e),
+            &Error::Parse(ref e) =>
+                write!(f, "{}: {}", std::error::Error::description(self), e),
+            _ => std::error::Error::description(self).fmt(f)
+        }
     }
 }
 
@@ -42,17 +120,76 @@ impl std::error::Error for Error {
             &Error::AddrParse(_) => "wrong IPv4 address format",
             &Error::Io(_) => "IO error occured",
             &Error::UnexpectedPkg => "received unexpected package",
+            &Error::Encode(EncodingError::SizeLimit) =>
+                "could not send package: exceeds maximum message size",
             &Error::Encode(_) => "could not encode/ send package",
+            &Error::Decode(DecodingError::SizeLimit) =>
+                "could not receive package: exceeds maximum message size",
             &Error::Decode(_) => "could not decode/ receive package",
             &Error::Auth => "could not authenticate user",
             &Error::Server(ref e) => { &e.msg }
+            &Error::Timeout => "connection timed out",
+            &Error::UnknownHost => "could not resolve host name",
+            &Error::ProtocolMismatch { .. } => "protocol version mismatch",
+            &Error::ParamCount { .. } => "wrong number of parameters",
+            &Error::Cancelled => "query was cancelled",
+            &Error::Poisoned => "connection is out of sync with the server",
+            &Error::Parse(_) => "could not parse query",
+        }
+    }
+
+    /// The wrapped error of `AddrParse`, `Io`, `Encode`, `Decode` and `Parse`.
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            &Error::AddrParse(ref e) => Some(e),
+            &Error::Io(ref e) => Some(e),
+            &Error::Encode(ref e) => Some(e),
+            &Error::Decode(ref e) => Some(e),
+            &Error::Parse(ref e) => Some(e),
+            _ => None
+        }
+    }
+}
+
+impl Error {
+    /// The kind of error the server reported, to tell e.g. an unknown table
+    /// from a syntax error. None for errors of the client or connection.
+    pub fn error_code(&self) -> Option<ErrorCode> {
+        match self {
+            &Error::Server(ref e) => Some(e.code()),
+            &Error::Auth => Some(ErrorCode::Auth),
+            &Error::Cancelled => Some(ErrorCode::Cancelled),
+            _ => None
+        }
+    }
+
+    /// Check whether reading from or writing to the server failed, e.g.
+    /// because the connection was closed or is poisoned.
+    fn is_connection_error(&self) -> bool {
+        match self {
+            &Error::Io(_) => true,
+            &Error::Poisoned => true,
+            &Error::Encode(EncodingError::IoError(_)) => true,
+            &Error::Decode(DecodingError::IoError(_)) => true,
+            _ => false
         }
     }
 }
 
+/// Check whether an io::Error was caused by a read/ write or connect timeout
+fn is_timeout(err: &io::Error) -> bool {
+    match err.kind() {
+        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => true,
+        _ => false
+    }
+}
+
 /// Implement the conversion from io::Error to Connection-Error
 impl From<io::Error> for Error {
     fn from(err: io::Error) -> Error {
+        if is_timeout(&err) {
+            return Error::Timeout
+        }
         Error::Io(err)
     }
 }
@@ -67,14 +204,20 @@ impl From<AddrParseError> for Error {
 /// Implement the conversion from EncodingError to NetworkError
 impl  From<EncodingError> for Error {
     fn from(err: EncodingError) -> Error {
-        Error::Encode(err)
+        match err {
+            EncodingError::IoError(ref e) if is_timeout(e) => Error::Timeout,
+            _ => Error::Encode(err)
+        }
     }
 }
 
 /// Implement the conversion from DecodingError to NetworkError
 impl From<DecodingError> for Error {
     fn from(err: DecodingError) -> Error {
-        Error::Decode(err)
+        match err {
+            DecodingError::IoError(ref e) if is_timeout(e) => Error::Timeout,
+            _ => Error::Decode(err)
+        }
     }
 }
 
@@ -85,110 +228,928 @@ impl From<ClientErrMsg> for Error {
     }
 }
 
-/// Stores TCPConnection with a server. Contains IP, Port, Login data and
-/// greeting from server.
+/// Result of `Connection::execute`: the rows of a query like `select`, or the
+/// number of rows changed by `insert`, `update` and `delete`.
+pub enum QueryResult {
+    Rows(DataSet),
+    Affected(u64),
+}
+
+/// Returned by the callback of `Connection::execute_many`: whether it wants
+/// the next row.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ControlFlow {
+    Continue,
+    Break,
+}
+
+/// Handle to cancel the running query of a connection, created by
+/// `Connection::cancel_token`.
+///
+/// A connection blocks its thread while it waits for the result of a query,
+/// so the token is meant to be moved or cloned to another thread. `cancel`
+/// opens a second connection to the same server and asks it to stop the
+/// query, which then fails with `Error::Cancelled`. The second connection
+/// does not log in, it proves with the secret key from the greeting that
+/// the query is ours.
+#[derive(Clone)]
+pub struct CancelToken {
+    options: ConnectionBuilder,
+    connection_id: u64,
+    cancel_key: u64,
+}
+
+impl CancelToken {
+    /// Cancel the query that is currently running on the connection. Does
+    /// nothing if no query is running.
+    pub fn cancel(&self) -> Result<(), Error> {
+        let (mut stream, _) = try!(Connection::open(&self.options));
+        try!(send_cmd(&mut stream, Command::Cancel(self.connection_id, self.cancel_key),
+            HEADER_SIZE));
+        receive(&mut stream, PkgType::Ok, HEADER_SIZE)
+    }
+}
+
+/// Rows of a streamed result, created by `Connection::execute_stream`. Only
+/// the rows of the current batch are held in memory.
+pub struct RowStream<'a> {
+    tcp: MutexGuard<'a, Channel>,
+    max_size: u64,
+    batch: Rows,
+    columns: Rc<Vec<Column>>,
+    done: bool,
+}
+
+impl<'a> RowStream<'a> {
+    /// Return the columns of the result.
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    /// Return the number of received rows that were not returned yet.
+    pub fn buffered(&self) -> usize {
+        self.batch.remaining()
+    }
+
+    /// Receive the next batch, returns false at the end of the stream.
+    fn next_batch(&mut self) -> Result<bool, Error> {
+        let expected = [PkgType::RowBatch, PkgType::StreamEnd];
+        match try!(self.tcp.receive_any(&expected, self.max_size)) {
+            PkgType::RowBatch => {
+                let data: Vec<u8> = try!(self.tcp.decode(self.max_size));
+                self.batch = Rows::from_batch(&data, self.columns.clone());
+                Ok(true)
+            },
+            _ => Ok(false)
+        }
+    }
+}
+
+impl<'a> Iterator for RowStream<'a> {
+    type Item = Result<Row, Error>;
+
+    fn next(&mut self) -> Option<Result<Row, Error>> {
+        loop {
+            if let Some(row) = self.batch.next() {
+                return Some(Ok(row))
+            }
+            if self.done {
+                return None
+            }
+            match self.next_batch() {
+                Ok(true) => {},
+                Ok(false) => {
+                    self.done = true;
+                    return None
+                },
+                Err(e) => {
+                    // the rest of the stream can not be read anymore
+                    self.done = true;
+                    return Some(Err(e))
+                }
+            }
+        }
+    }
+}
+
+impl<'a> Drop for RowStream<'a> {
+    /// Skip the remaining batches, so the connection can be used again.
+    fn drop(&mut self) {
+        while !self.done {
+            match self.next_batch() {
+                Ok(true) => {},
+                _ => self.done = true
+            }
+        }
+    }
+}
+
+/// Handle of a statement prepared on the server with `Connection::prepare`.
+/// Only valid for the connection that prepared it.
+pub struct PreparedStatement {
+    id: u64,
+    param_count: usize,
+}
+
+impl PreparedStatement {
+    /// Execute the statement with the given parameters bound to its `?`
+    /// placeholders. Nothing is sent if the number of parameters is wrong.
+    pub fn execute(&self, conn: &mut Connection, params: &[Value])
+        -> Result<QueryResult, Error>
+    {
+        if params.len() != self.param_count {
+            return Err(Error::ParamCount { expected: self.param_count,
+                given: params.len() })
+        }
+        let cmd = Command::ExecutePrepared(self.id, params.to_vec());
+        conn.execute_cmd(cmd)
+    }
+
+    /// Return number of parameters the statement expects.
+    pub fn get_param_count(&self) -> usize {
+        self.param_count
+    }
+}
+
+/// Collects the options for a new connection. Unset options keep their
+/// defaults: address 127.0.0.1, port 4242, empty login, no timeout and the
+/// server has to speak the same protocol version.
+///
+/// ```no_run
+/// # use uosql::{ConnectionBuilder, Error};
+/// # use std::time::Duration;
+/// # fn run() -> Result<(), Error> {
+/// let mut conn = try!(ConnectionBuilder::new()
+///     .address("localhost")
+///     .port(4242)
+///     .user("admin")
+///     .password("pw")
+///     .timeout(Duration::from_secs(5))
+///     .connect());
+/// try!(conn.ping());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ConnectionBuilder {
+    address: String,
+    port: u16,
+    user: String,
+    password: String,
+    timeout: Option<Duration>,
+    allow_version_mismatch: bool,
+    compression: bool,
+    heartbeat: Option<Duration>,
+    auto_reconnect: bool,
+    #[cfg(feature = "tls")]
+    tls_roots: Option<Vec<Certificate>>,
+}
+
+impl ConnectionBuilder {
+    pub fn new() -> ConnectionBuilder {
+        ConnectionBuilder {
+            address: DEFAULT_ADDRESS.into(),
+            port: DEFAULT_PORT,
+            user: String::new(),
+            password: String::new(),
+            timeout: None,
+            allow_version_mismatch: false,
+            compression: false,
+            heartbeat: None,
+            auto_reconnect: false,
+            #[cfg(feature = "tls")]
+            tls_roots: None,
+        }
+    }
+
+    /// IPv4 address or host name of the server.
+    pub fn address(mut self, address: &str) -> ConnectionBuilder {
+        self.address = address.into();
+        self
+    }
+
+    pub fn port(mut self, port: u16) -> ConnectionBuilder {
+        self.port = port;
+        self
+    }
+
+    pub fn user(mut self, user: &str) -> ConnectionBuilder {
+        self.user = user.into();
+        self
+    }
+
+    pub fn password(mut self, password: &str) -> ConnectionBuilder {
+        self.password = password.into();
+        self
+    }
+
+    /// Timeout for connecting and every later read/ write.
+    pub fn timeout(mut self, timeout: Duration) -> ConnectionBuilder {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Connect even if the server uses another protocol version than this
+    /// library. Later packages may fail to decode!
+    pub fn allow_version_mismatch(mut self) -> ConnectionBuilder {
+        self.allow_version_mismatch = true;
+        self
+    }
+
+    /// Ask the server to compress big results. Only used if the server
+    /// supports it.
+    pub fn with_compression(mut self, compression: bool) -> ConnectionBuilder {
+        self.compression = compression;
+        self
+    }
+
+    /// Ping the server every `interval` while the connection is idle, see
+    /// `Connection::is_alive`.
+    pub fn heartbeat(mut self, interval: Duration) -> ConnectionBuilder {
+        self.heartbeat = Some(interval);
+        self
+    }
+
+    /// Let `execute` reconnect if the connection failed or was poisoned
+    /// before, see `Connection::execute_with_retry`. A select is sent again
+    /// once, other queries fail if they may have reached the server.
+    pub fn auto_reconnect(mut self, auto_reconnect: bool) -> ConnectionBuilder {
+        self.auto_reconnect = auto_reconnect;
+        self
+    }
+
+    /// Connect over TLS. The certificate of the server has to be signed by
+    /// one of `root_certs` and be valid for the address.
+    #[cfg(feature = "tls")]
+    pub fn with_tls(mut self, root_certs: Vec<Certificate>) -> ConnectionBuilder {
+        self.tls_roots = Some(root_certs);
+        self
+    }
+
+    /// Establish the connection and log in.
+    pub fn connect(self) -> Result<Connection, Error> {
+        Connection::connect_with(self)
+    }
+}
+
+/// Counters of a connection since it was established or the last
+/// `Connection::reset_stats`, see `Connection::stats`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ConnectionStats {
+    /// Executed queries, including prepared statements and the queries of
+    /// batches
+    pub queries: u64,
+    /// Bytes written to and read from the server after the login
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    /// Time from sending queries until their results were received
+    pub query_time: Duration,
+}
+
+/// The stream of a connection. It is poisoned if a package was not read
+/// completely, e.g. because it was unexpected or the payload timed out,
+/// because the rest of that package would be read as the next one. The same
+/// goes for a command that was sent only partly.
+struct Channel {
+    stream: Stream,
+    poisoned: bool,
+    stats: ConnectionStats,
+}
+
+impl Channel {
+    /// Counts `count` queries that were sent at `start`
+    fn count_queries(&mut self, count: u64, start: Instant) {
+        self.stats.queries += count;
+        self.stats.query_time += start.elapsed();
+    }
+
+    /// Like `send_cmd`, but poisons the channel if the command failed after a
+    /// part of it was written.
+    fn send_cmd(&mut self, cmd: Command, size: u64) -> Result<(), Error> {
+        let sent = self.stats.bytes_sent;
+        let res = send_cmd(self, cmd, size);
+        if res.is_err() && self.stats.bytes_sent != sent {
+            self.poisoned = true;
+        }
+        res
+    }
+
+    /// Like `receive_any`, but poisons the channel unless a whole package was
+    /// read. Error packages are read completely.
+    fn receive_any(&mut self, cmds: &[PkgType], size: u64) -> Result<PkgType, Error> {
+        let res = receive_any(self, cmds, size);
+        match res {
+            Ok(_) | Err(Error::Server(_)) | Err(Error::Cancelled) => {},
+            Err(_) => self.poisoned = true,
+        }
+        res
+    }
+
+    /// Decode the payload following a package header, a failure poisons the
+    /// channel.
+    fn decode<T: Decodable>(&mut self, size: u64) -> Result<T, Error> {
+        let res = decode_bounded(self, size).map_err(Error::from);
+        if res.is_err() {
+            self.poisoned = true;
+        }
+        res
+    }
+
+    /// Like `receive_result`, but a failure poisons the channel.
+    fn receive_result(&mut self, pkg: PkgType, size: u64) -> Result<QueryResult, Error> {
+        let res = receive_result(self, pkg, size);
+        if res.is_err() {
+            self.poisoned = true;
+        }
+        res
+    }
+
+    fn receive(&mut self, cmd: PkgType, size: u64) -> Result<(), Error> {
+        self.receive_any(&[cmd], size).map(|_| ())
+    }
+}
+
+impl Read for Channel {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let count = try!(self.stream.read(buf));
+        self.stats.bytes_received += count as u64;
+        Ok(count)
+    }
+}
+
+impl Write for Channel {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let count = try!(self.stream.write(buf));
+        self.stats.bytes_sent += count as u64;
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+/// Stores TCPConnection with a server. Contains IP, Port, Login data,
+/// greeting from server and the maximum size of sent and received messages.
+/// The options it was created with are kept to be able to reconnect.
 pub struct Connection {
     ip: String,
     port: u16,
-    tcp: TcpStream,
+    tcp: Arc<Mutex<Channel>>,
     greeting: Greeting,
     user_data: Login,
+    max_size: u64,
+    options: ConnectionBuilder,
+    /// Selected by `use_db`, selected again by `reconnect`
+    database: Option<String>,
+    /// Read timeout while waiting for the result of a query
+    query_timeout: Option<Duration>,
+    alive: Arc<AtomicBool>,
+    /// Dropping it stops the heartbeat thread
+    _heartbeat: Option<Sender<()>>,
 }
 
 impl Connection {
-    /// Establish connection to specified address and port.
+    /// Establish connection to specified address and port. The address is
+    /// either an IPv4 address or a host name, which is resolved via DNS.
+    /// See `ConnectionBuilder` for more options.
     pub fn connect(addr: String, port: u16, usern: String, passwd: String)
         -> Result<Connection, Error>
     {
-        // Parse IPv4 address from String
-        let tmp_addr = match std::net::Ipv4Addr::from_str(&addr) {
-            Ok(tmp_addr) => tmp_addr,
-            Err(e) => return Err(e.into())
-        };
+        ConnectionBuilder::new().address(&addr).port(port).user(&usern)
+            .password(&passwd).connect()
+    }
 
-        // Establish Tcp connection
-        let mut tmp_tcp = match TcpStream::connect((tmp_addr, port)) {
-            Ok(tmp_tcp) => tmp_tcp,
-            Err(e) => return Err(e.into())
+    /// Establish connection to specified address and port. Connecting and
+    /// every later read/ write fail with `Error::Timeout` after `timeout`.
+    pub fn connect_timeout(addr: String, port: u16, usern: String,
+        passwd: String, timeout: Duration) -> Result<Connection, Error>
+    {
+        ConnectionBuilder::new().address(&addr).port(port).user(&usern)
+            .password(&passwd).timeout(timeout).connect()
+    }
+
+    /// Connects to the server of `options` and receives the greeting
+    fn open(options: &ConnectionBuilder) -> Result<(Stream, Greeting), Error> {
+        let addr = &options.address[..];
+        let port = options.port;
+
+        // Parse IPv4 address from String, else resolve the host name
+        let socks: Vec<SocketAddr> = match Ipv4Addr::from_str(addr) {
+            Ok(tmp_addr) => vec![SocketAddr::V4(SocketAddrV4::new(tmp_addr, port))],
+            Err(_) => match (addr, port).to_socket_addrs() {
+                Ok(socks) => socks.collect(),
+                Err(_) => return Err(Error::UnknownHost)
+            }
         };
+        if socks.is_empty() {
+            return Err(Error::UnknownHost)
+        }
+
+        // Establish Tcp connection, trying the addresses in order
+        let tcp = try!(connect_any(&socks, options.timeout));
+        let mut tmp_tcp = try!(wrap_stream(options, tcp));
 
         // Greeting message
-        match receive(&mut tmp_tcp, PkgType::Greet) {
+        match receive(&mut tmp_tcp, PkgType::Greet, HEADER_SIZE) {
             Ok(_) => {},
             Err(e) => return Err(e)
         };
         let greet: Greeting =
-            try!(decode_from(&mut tmp_tcp, SizeLimit::Bounded(1024)));
+            try!(decode_bounded(&mut tmp_tcp, HEADER_SIZE));
+        if greet.protocol_version != PROTOCOL_VERSION && !options.allow_version_mismatch {
+            return Err(Error::ProtocolMismatch { client: PROTOCOL_VERSION,
+                server: greet.protocol_version })
+        }
+        Ok((tmp_tcp, greet))
+    }
+
+    fn connect_with(options: ConnectionBuilder) -> Result<Connection, Error> {
+        let addr = options.address.clone();
+        let port = options.port;
+        let timeout = options.timeout;
+        let (mut tmp_tcp, greet) = try!(Connection::open(&options));
 
-        // Login package
-        let log = Login { username: usern, password: passwd };
+        // Login package, only containing the salted hash of the password
+        let log = Login { username: options.user.clone(),
+            password_hash: auth::hash_password(&greet.salt, &options.password),
+            compression: options.compression && greet.compression };
         match encode_into(&PkgType::Login, &mut tmp_tcp,
-            SizeLimit::Bounded(1024))
+            SizeLimit::Bounded(HEADER_SIZE))
         {
             Ok(_) => {},
             Err(e) => return Err(e.into())
         }
 
         // Login data
-        match encode_into(&log, &mut tmp_tcp, SizeLimit::Bounded(1024)) {
+        match encode_into(&log, &mut tmp_tcp, SizeLimit::Bounded(HEADER_SIZE)) {
             Ok(_) => {},
             Err(e) => return Err(e.into())
         }
 
         // Get Login response - either user is authorized or unauthorized
         let status: PkgType =
-            try!(decode_from(&mut tmp_tcp, SizeLimit::Bounded(1024)));
+            try!(decode_bounded(&mut tmp_tcp, HEADER_SIZE));
         match status {
-            PkgType::AccGranted =>
-                Ok(Connection { ip: addr, port: port, tcp: tmp_tcp,
-                    greeting: greet, user_data: log} ),
+            PkgType::AccGranted => {
+                let tcp = Arc::new(Mutex::new(Channel { stream: tmp_tcp, poisoned: false,
+                    stats: ConnectionStats::default() }));
+                let alive = Arc::new(AtomicBool::new(true));
+                let heartbeat = options.heartbeat.map(|interval| {
+                    let (stop, stopped) = mpsc::channel();
+                    let (tcp, alive) = (tcp.clone(), alive.clone());
+                    thread::spawn(move || heartbeat(tcp, alive, stopped, interval, timeout));
+                    stop
+                });
+                Ok(Connection { ip: addr, port: port, tcp: tcp,
+                    greeting: greet, user_data: log,
+                    max_size: DEFAULT_MAX_MESSAGE_SIZE, options: options, database: None,
+                    query_timeout: None, alive: alive, _heartbeat: heartbeat })
+            },
             PkgType::AccDenied =>
                 Err(Error::Auth),
+            // e.g. too many connections
+            PkgType::Error =>
+                Err(Error::Server(try!(decode_bounded(&mut tmp_tcp, HEADER_SIZE)))),
             _ => Err(Error::UnexpectedPkg)
         }
     }
 
+    /// Limit the size of queries sent and results received over this
+    /// connection to `size` bytes. Package headers are always limited to 1024
+    /// bytes. Messages exceeding the limit produce an `Error::Encode` or
+    /// `Error::Decode` and poison the connection, since their header was
+    /// already sent or read. `DEFAULT_MAX_MESSAGE_SIZE` by default.
+    pub fn with_max_message_size(mut self, size: u64) -> Connection {
+        self.max_size = size;
+        self
+    }
+
+    /// Establish a new connection to the same server with the same login,
+    /// e.g. after the old one was dropped, and select the database selected
+    /// by `use_db` again. Returns `Error::Auth` if the login is no longer
+    /// valid.
+    pub fn reconnect(&mut self) -> Result<(), Error> {
+        let mut conn = try!(Connection::connect_with(self.options.clone()));
+        conn.max_size = self.max_size;
+        conn.query_timeout = self.query_timeout;
+        conn.tcp.lock().unwrap().stats = self.stats();
+        if let Some(ref name) = self.database {
+            try!(conn.use_db(name));
+        }
+        *self = conn;
+        Ok(())
+    }
+
+    /// Like `execute`, but reconnects once and executes the query again if
+    /// the connection failed. Only a select is sent twice, other queries
+    /// are only executed again if they were not sent the first time, since
+    /// the server may have executed them before the connection failed.
+    pub fn execute_with_retry(&mut self, query: String) -> Result<QueryResult, Error> {
+        // the server fails on a query that does not parse, too
+        let read_only = Connection::is_read_only(&query).unwrap_or(false);
+        let timeout = self.query_timeout;
+        match self.send_and_receive(Command::Query(query.clone()), timeout) {
+            Err((ref e, sent)) if e.is_connection_error() && (read_only || !sent) => {},
+            res => return res.map_err(|(e, _)| e)
+        }
+        try!(self.reconnect());
+        self.execute_cmd(Command::Query(query))
+    }
+
+    /// Check whether the query only reads, without sending it: true for
+    /// select and describe, false for definitions and other manipulations
+    /// like insert or use. Fails with `Error::Parse` if it does not parse.
+    pub fn is_read_only(query: &str) -> Result<bool, Error> {
+        match server::parse::parse(query) {
+            Ok(Query::ManipulationStmt(ManipulationStmt::Select(_))) |
+            Ok(Query::ManipulationStmt(ManipulationStmt::Describe(_))) => Ok(true),
+            Ok(_) => Ok(false),
+            Err(e) => Err(Error::Parse(e)),
+        }
+    }
+
     /// Send ping-command to server and receive Ok-package
     pub fn ping(&mut self) -> Result<(), Error> {
-        match send_cmd(&mut self.tcp, Command::Ping, 1024) {
+        let mut tcp = try!(self.lock());
+        match tcp.send_cmd(Command::Ping, self.max_size) {
             Ok(_) => {},
             Err(e) => return Err(e)
         };
-        match receive(&mut self.tcp, PkgType::Ok) {
+        match tcp.receive(PkgType::Ok, self.max_size) {
             Ok(_) => Ok(()),
             Err(err) => Err(err)
         }
     }
 
+    /// Return false once a ping of the heartbeat failed. Always true if the
+    /// connection was created without `ConnectionBuilder::heartbeat`.
+    pub fn is_alive(&self) -> bool {
+        self.alive.load(Ordering::SeqCst)
+    }
+
     /// Send quit-command to server and receive Ok-package
     pub fn quit(&mut self) -> Result<(), Error> {
-        match send_cmd(&mut self.tcp, Command::Quit, 1024) {
+        let mut tcp = try!(self.lock());
+        match tcp.send_cmd(Command::Quit, self.max_size) {
+            Ok(_) => {},
+            Err(e) => return Err(e)
+        };
+        match tcp.receive(PkgType::Ok, self.max_size) {
+            Ok(_) => Ok(()),
+            Err(err) => Err(err)
+        }
+    }
+
+    /// Send begin-command to server and receive Ok-package. Opens a
+    /// transaction, which is closed by `commit` or `rollback`.
+    pub fn begin(&mut self) -> Result<(), Error> {
+        let mut tcp = try!(self.lock());
+        match tcp.send_cmd(Command::Begin, self.max_size) {
+            Ok(_) => {},
+            Err(e) => return Err(e)
+        };
+        match tcp.receive(PkgType::Ok, self.max_size) {
+            Ok(_) => Ok(()),
+            Err(err) => Err(err)
+        }
+    }
+
+    /// Send commit-command to server and receive Ok-package. Fails with
+    /// `Error::Server` if no transaction is open.
+    pub fn commit(&mut self) -> Result<(), Error> {
+        let mut tcp = try!(self.lock());
+        match tcp.send_cmd(Command::Commit, self.max_size) {
+            Ok(_) => {},
+            Err(e) => return Err(e)
+        };
+        match tcp.receive(PkgType::Ok, self.max_size) {
+            Ok(_) => Ok(()),
+            Err(err) => Err(err)
+        }
+    }
+
+    /// Send rollback-command to server and receive Ok-package. Undoes the
+    /// changes of the transaction, fails with `Error::Server` if no
+    /// transaction is open.
+    pub fn rollback(&mut self) -> Result<(), Error> {
+        let mut tcp = try!(self.lock());
+        match tcp.send_cmd(Command::Rollback, self.max_size) {
             Ok(_) => {},
             Err(e) => return Err(e)
         };
-        match receive(&mut self.tcp, PkgType::Ok) {
+        match tcp.receive(PkgType::Ok, self.max_size) {
             Ok(_) => Ok(()),
             Err(err) => Err(err)
         }
     }
 
-    // TODO: Return results (response-package)
-    pub fn execute(&mut self, query: String) -> Result<DataSet, Error> {
-        match send_cmd(&mut self.tcp, Command::Query(query), 1024) {
+    /// Select the database for the following queries. Fails with
+    /// `Error::Server` if the database does not exist.
+    pub fn use_db(&mut self, name: &str) -> Result<(), Error> {
+        {
+            let mut tcp = try!(self.lock());
+            try!(tcp.send_cmd(Command::Use(name.into()), self.max_size));
+            try!(tcp.receive(PkgType::Ok, self.max_size));
+        }
+        self.database = Some(name.into());
+        Ok(())
+    }
+
+    /// Let the server parse the query without executing it. Fails with
+    /// `Error::Server` if the query is not valid.
+    pub fn validate(&mut self, query: &str) -> Result<(), Error> {
+        let mut tcp = try!(self.lock());
+        try!(tcp.send_cmd(Command::Validate(query.into()), self.max_size));
+        try!(tcp.receive(PkgType::Ok, self.max_size));
+        Ok(())
+    }
+
+    /// Let the server parse the query and return it as normalized SQL, the
+    /// query is not executed. Fails with `Error::Server` if the query is
+    /// not valid.
+    pub fn explain(&mut self, query: &str) -> Result<String, Error> {
+        let mut tcp = try!(self.lock());
+        try!(tcp.send_cmd(Command::Explain(query.into()), self.max_size));
+        try!(tcp.receive(PkgType::Explain, self.max_size));
+        Ok(try!(tcp.decode(self.max_size)))
+    }
+
+    /// Return the names of the tables in the current database. Fails with
+    /// `Error::Server` if no database is selected.
+    pub fn list_tables(&mut self) -> Result<Vec<String>, Error> {
+        let mut tcp = try!(self.lock());
+        match tcp.send_cmd(Command::ListTables, self.max_size) {
             Ok(_) => {},
             Err(e) => return Err(e)
         };
-        match receive(&mut self.tcp, PkgType::Response) {
+        try!(tcp.receive(PkgType::Response, self.max_size));
+        let rows: ResultSet = try!(tcp.decode(self.max_size));
+        Ok(preprocess(&rows).into_iter()
+            .filter_map(|row| row.get_str(0).map(|s| s.to_string()))
+            .collect())
+    }
+
+    /// Return name, type and constraints of every column of the table. Fails
+    /// with `Error::Server` if the table does not exist.
+    pub fn describe(&mut self, table: &str) -> Result<Vec<ColumnInfo>, Error> {
+        let mut tcp = try!(self.lock());
+        match tcp.send_cmd(Command::Describe(table.into()), self.max_size) {
+            Ok(_) => {},
+            Err(e) => return Err(e)
+        };
+        try!(tcp.receive(PkgType::Response, self.max_size));
+        let rows: ResultSet = try!(tcp.decode(self.max_size));
+        Ok(rows.columns)
+    }
+
+    /// Send query to server and receive the resulting rows one batch at a
+    /// time, so big results do not have to fit into memory at once. Every
+    /// batch is limited by `with_max_message_size`. Statements without
+    /// result rows yield an empty stream.
+    ///
+    /// The connection can not be used until the stream is dropped, dropping
+    /// it early skips the remaining rows.
+    pub fn execute_stream(&mut self, query: String) -> Result<RowStream, Error> {
+        let mut tcp = try!(self.lock());
+        let start = Instant::now();
+        match tcp.send_cmd(Command::StreamQuery(query), self.max_size) {
+            Ok(_) => {},
+            Err(e) => return Err(e)
+        };
+        let expected = [PkgType::StreamStart, PkgType::AffectedRows];
+        let pkg = tcp.receive_any(&expected, self.max_size);
+        // the time until the first row can be read
+        tcp.count_queries(1, start);
+        let (columns, done) = match try!(pkg) {
+            PkgType::StreamStart => {
+                let columns: Vec<Column> = try!(tcp.decode(self.max_size));
+                (columns, false)
+            },
+            _ => {
+                let _: u64 = try!(tcp.decode(HEADER_SIZE));
+                (vec![], true)
+            }
+        };
+        let columns = Rc::new(columns);
+        Ok(RowStream { tcp: tcp, max_size: self.max_size,
+            batch: Rows::from_batch(&[], columns.clone()), columns: columns, done: done })
+    }
+
+    /// Send query to server and call `f` with every row of the result as soon
+    /// as it is received, like `execute_stream`. Returns the number of rows
+    /// `f` was called with.
+    ///
+    /// If `f` returns `ControlFlow::Break`, no more rows are passed to it and
+    /// the query is cancelled, so the server stops sending them.
+    pub fn execute_many<F>(&mut self, query: String, mut f: F) -> Result<u64, Error>
+        where F: FnMut(Row) -> ControlFlow
+    {
+        let token = self.cancel_token();
+        let mut stream = try!(self.execute_stream(query));
+        let mut count = 0;
+        while let Some(row) = stream.next() {
+            count += 1;
+            if f(try!(row)) == ControlFlow::Break {
+                // without the cancel, dropping the stream still skips the
+                // rest, it only takes longer
+                if !stream.done {
+                    let _ = token.cancel();
+                }
+                break
+            }
+        }
+        // dropping the stream skips the rows that were sent before the cancel
+        Ok(count)
+    }
+
+    /// Send query to server and receive either the resulting rows or the
+    /// number of affected rows. Like `execute_with_retry` if the connection
+    /// was built with `ConnectionBuilder::auto_reconnect`.
+    pub fn execute(&mut self, query: String) -> Result<QueryResult, Error> {
+        if self.options.auto_reconnect {
+            return self.execute_with_retry(query)
+        }
+        self.execute_cmd(Command::Query(query))
+    }
+
+    /// Let queries and prepared statements fail with `Error::Timeout` if
+    /// their result does not arrive within `timeout`. `None` waits as long as
+    /// the timeout of `ConnectionBuilder::timeout`, which is the default. The
+    /// late result would be read as answer of the next command, so the
+    /// connection is poisoned after a timeout.
+    pub fn set_query_timeout(&mut self, timeout: Option<Duration>) {
+        self.query_timeout = timeout;
+    }
+
+    /// Like `execute`, but with the given timeout instead of the one set by
+    /// `set_query_timeout`.
+    pub fn execute_with_timeout(&mut self, query: String, timeout: Duration)
+        -> Result<QueryResult, Error>
+    {
+        self.send_and_receive(Command::Query(query), Some(timeout)).map_err(|(e, _)| e)
+    }
+
+    /// Prepare a query with `?` placeholders for parameters on the server.
+    /// Execute it with `PreparedStatement::execute`.
+    pub fn prepare(&mut self, query: String) -> Result<PreparedStatement, Error> {
+        let mut tcp = try!(self.lock());
+        match tcp.send_cmd(Command::Prepare(query), self.max_size) {
+            Ok(_) => {},
+            Err(e) => return Err(e)
+        };
+        match tcp.receive(PkgType::Prepared, self.max_size) {
             Ok(_) => {
-                let rows: ResultSet =
-                    try!(decode_from(&mut self.tcp, SizeLimit::Infinite));
-                let dataset = preprocess (&rows);
-                Ok(dataset)
+                let info: PreparedInfo =
+                    try!(tcp.decode(HEADER_SIZE));
+                Ok(PreparedStatement { id: info.id,
+                    param_count: info.param_count as usize })
             },
             Err(err) => Err(err)
         }
     }
 
+    /// Send command executing a query and receive its result.
+    fn execute_cmd(&mut self, cmd: Command) -> Result<QueryResult, Error> {
+        let timeout = self.query_timeout;
+        self.send_and_receive(cmd, timeout).map_err(|(e, _)| e)
+    }
+
+    /// Like `execute_cmd`, but the error is returned with whether the
+    /// command was sent before it occured. An incompletely sent command is
+    /// not executed by the server. If a timeout is given, it replaces the
+    /// read timeout of the connection while waiting for the result.
+    fn send_and_receive(&mut self, cmd: Command, timeout: Option<Duration>)
+        -> Result<QueryResult, (Error, bool)>
+    {
+        let mut tcp = try!(self.lock().map_err(|e| (e, false)));
+        let start = Instant::now();
+        match tcp.send_cmd(cmd, self.max_size) {
+            Ok(_) => {},
+            Err(e) => return Err((e, false))
+        };
+        if timeout.is_some() {
+            if let Err(e) = tcp.stream.set_read_timeout(timeout) {
+                tcp.poisoned = true;
+                return Err((e.into(), true))
+            }
+        }
+        let expected = [PkgType::Response, PkgType::CompressedResponse, PkgType::AffectedRows];
+        let res = match tcp.receive_any(&expected, self.max_size) {
+            Ok(pkg) => tcp.receive_result(pkg, self.max_size),
+            Err(err) => Err(err)
+        };
+        tcp.count_queries(1, start);
+        if timeout.is_some() {
+            if let Err(e) = tcp.stream.set_read_timeout(self.options.timeout) {
+                tcp.poisoned = true;
+                return Err((e.into(), true))
+            }
+        }
+        res.map_err(|e| (e, true))
+    }
+
+    /// Send all queries at once and receive the results of the executed
+    /// ones in the same order. A failed query has its error as result, with
+    /// `BatchMode::StopOnError` it is the last one. The outer error is only
+    /// returned if the connection failed.
+    pub fn execute_batch(&mut self, queries: &[String], mode: BatchMode)
+        -> Result<Vec<Result<QueryResult, Error>>, Error>
+    {
+        let mut tcp = try!(self.lock());
+        let start = Instant::now();
+        match tcp.send_cmd(Command::Batch(queries.to_vec(), mode), self.max_size) {
+            Ok(_) => {},
+            Err(e) => return Err(e)
+        };
+        let expected = [PkgType::Response, PkgType::CompressedResponse, PkgType::AffectedRows,
+            PkgType::BatchEnd];
+        let mut results = Vec::new();
+        loop {
+            match tcp.receive_any(&expected, self.max_size) {
+                Ok(PkgType::BatchEnd) => {
+                    tcp.count_queries(results.len() as u64, start);
+                    return Ok(results)
+                },
+                Ok(pkg) => {
+                    // a broken result poisons the connection, unlike a failed query
+                    let res = try!(tcp.receive_result(pkg, self.max_size));
+                    results.push(Ok(res))
+                },
+                Err(e @ Error::Server(_)) | Err(e @ Error::Cancelled) => results.push(Err(e)),
+                Err(e) => return Err(e)
+            }
+        }
+    }
+
+    /// Execute the semicolon separated queries of a script file, e.g. a
+    /// migration, in order. The script may contain `--`, `#` and `/* */`
+    /// comments. The queries are sent at once like `execute_batch` with
+    /// `BatchMode::StopOnError`, so the first failing query returns its error
+    /// and the rest is not executed.
+    pub fn execute_file(&mut self, path: &Path) -> Result<Vec<QueryResult>, Error> {
+        let mut script = String::new();
+        try!(File::open(path).and_then(|mut f| f.read_to_string(&mut script)));
+        let queries: Vec<String> = match server::parse::split_script(&script) {
+            Ok(queries) => queries.into_iter().map(|q| q.to_string()).collect(),
+            Err((_, e)) => return Err(Error::Parse(e)),
+        };
+        let mut results = Vec::new();
+        for res in try!(self.execute_batch(&queries, BatchMode::StopOnError)) {
+            results.push(try!(res));
+        }
+        Ok(results)
+    }
+
+    /// Return the number of executed queries, the bytes sent and received
+    /// and the time spent waiting for results. The counters are kept by
+    /// `reconnect`.
+    pub fn stats(&self) -> ConnectionStats {
+        self.tcp.lock().unwrap().stats
+    }
+
+    /// Set the counters of `stats` to zero, e.g. before a benchmark.
+    pub fn reset_stats(&mut self) {
+        self.tcp.lock().unwrap().stats = ConnectionStats::default();
+    }
+
+    /// Return a handle to cancel running queries of this connection from
+    /// another thread.
+    pub fn cancel_token(&self) -> CancelToken {
+        CancelToken { options: self.options.clone(),
+            connection_id: self.greeting.connection_id, cancel_key: self.greeting.cancel_key }
+    }
+
+    /// Ask the server for its version, start time and number of open
+    /// connections.
+    pub fn server_info(&mut self) -> Result<ServerInfo, Error> {
+        let mut tcp = try!(self.lock());
+        match tcp.send_cmd(Command::ServerInfo, self.max_size) {
+            Ok(_) => {},
+            Err(e) => return Err(e)
+        };
+        try!(tcp.receive(PkgType::ServerInfo, self.max_size));
+        Ok(try!(tcp.decode(HEADER_SIZE)))
+    }
+
+    /// Lock the stream for a command. Fails with `Error::Poisoned` if an
+    /// earlier command did not read its answer completely.
+    fn lock(&self) -> Result<MutexGuard<Channel>, Error> {
+        let tcp = self.tcp.lock().unwrap();
+        if tcp.poisoned {
+            return Err(Error::Poisoned)
+        }
+        Ok(tcp)
+    }
+
+    /// Return true if an answer was not read or a command not sent
+    /// completely, e.g. because it was an unexpected package or timed out
+    /// halfway. All commands fail
+    /// with `Error::Poisoned` afterwards, `reconnect` fixes the connection.
+    pub fn is_poisoned(&self) -> bool {
+        self.tcp.lock().unwrap().poisoned
+    }
+
     /// Return server version number.
     pub fn get_version(&self) -> u8 {
         self.greeting.protocol_version
@@ -215,6 +1176,78 @@ impl Connection {
     }
 }
 
+/// Pings the server every `interval` until a ping fails or `stopped` is
+/// disconnected by dropping the connection.
+fn heartbeat(tcp: Arc<Mutex<Channel>>, alive: Arc<AtomicBool>, stopped: Receiver<()>,
+    interval: Duration, timeout: Option<Duration>)
+{
+    loop {
+        match stopped.recv_timeout(interval) {
+            Err(RecvTimeoutError::Timeout) => {},
+            _ => return
+        }
+        // A running command notices a dead connection by itself
+        let mut tcp = match tcp.try_lock() {
+            Ok(tcp) => tcp,
+            Err(_) => continue
+        };
+        if tcp.poisoned {
+            alive.store(false, Ordering::SeqCst);
+            return
+        }
+        // No answer within the interval counts as dead, too
+        let res = tcp.stream.set_read_timeout(Some(interval)).map_err(Error::from)
+            .and_then(|_| tcp.send_cmd(Command::Ping, HEADER_SIZE))
+            .and_then(|_| tcp.receive(PkgType::Ok, HEADER_SIZE))
+            .and_then(|_| tcp.stream.set_read_timeout(timeout).map_err(Error::from));
+        if res.is_err() {
+            alive.store(false, Ordering::SeqCst);
+            return
+        }
+    }
+}
+
+/// Performs the TLS handshake if TLS was requested
+#[cfg(feature = "tls")]
+fn wrap_stream(options: &ConnectionBuilder, tcp: TcpStream) -> Result<Stream, Error> {
+    match options.tls_roots {
+        Some(ref roots) =>
+            Ok(try!(server::net::stream::tls_connect(tcp, &options.address, roots))),
+        None => Ok(Stream::Plain(tcp)),
+    }
+}
+
+#[cfg(not(feature = "tls"))]
+fn wrap_stream(_: &ConnectionBuilder, tcp: TcpStream) -> Result<Stream, Error> {
+    Ok(Stream::Plain(tcp))
+}
+
+/// Connect to the first reachable address of `socks`. Return the error of the
+/// last address if none is reachable.
+fn connect_any(socks: &[SocketAddr], timeout: Option<Duration>)
+    -> Result<TcpStream, Error>
+{
+    let mut last_err = None;
+    for sock in socks {
+        let res = match timeout {
+            Some(t) => TcpStream::connect_timeout(sock, t),
+            None => TcpStream::connect(sock)
+        };
+        match res {
+            Ok(tcp) => {
+                try!(tcp.set_read_timeout(timeout));
+                try!(tcp.set_write_timeout(timeout));
+                return Ok(tcp)
+            },
+            Err(e) => last_err = Some(e)
+        }
+    }
+    match last_err {
+        Some(e) => Err(e.into()),
+        None => Err(Error::UnknownHost)
+    }
+}
+
 /// Return current library version.
 #[allow(dead_code)]
 fn get_lib_version() -> u8 {
@@ -222,35 +1255,993 @@ fn get_lib_version() -> u8 {
 }
 
 /// Send command package with actual command, e.g. quit, ping, query.
+/// The command itself is limited to `size`.
 fn send_cmd<W: Write>(mut s: &mut W, cmd: Command, size: u64)
     -> Result<(), Error>
 {
-    try!(encode_into(&PkgType::Command, s, SizeLimit::Bounded(1024)));
+    try!(encode_into(&PkgType::Command, s, SizeLimit::Bounded(HEADER_SIZE)));
     try!(encode_into(&cmd, &mut s, SizeLimit::Bounded(size)));
     Ok(())
 }
 
+/// Decode a message of at most `limit` bytes. Every message from the server
+/// is read with it, so a length prefix claiming more bytes fails with
+/// `DecodingError::SizeLimit` before they are allocated.
+fn decode_bounded<T: Decodable, R: Read>(s: &mut R, limit: u64) -> Result<T, DecodingError> {
+    decode_from(s, SizeLimit::Bounded(limit))
+}
+
+/// Receive the result of a query following the package header `pkg`.
+fn receive_result<R: Read>(s: &mut R, pkg: PkgType, size: u64)
+    -> Result<QueryResult, Error>
+{
+    match pkg {
+        PkgType::Response => {
+            let rows: ResultSet = try!(decode_bounded(s, size));
+            Ok(QueryResult::Rows(preprocess(&rows)))
+        },
+        PkgType::CompressedResponse => {
+            let rows = try!(server::net::read_compressed_result(s, SizeLimit::Bounded(size)));
+            Ok(QueryResult::Rows(preprocess(&rows)))
+        },
+        _ => {
+            let count: u64 = try!(decode_bounded(s, HEADER_SIZE));
+            Ok(QueryResult::Affected(count))
+        }
+    }
+}
+
 /// Match received packages to expected packages.
-fn receive(s: &mut TcpStream, cmd: PkgType) -> Result<(), Error> {
-    let status: PkgType = try!(decode_from(s, SizeLimit::Bounded(1024)));
+fn receive<R: Read>(s: &mut R, cmd: PkgType, size: u64) -> Result<(), Error> {
+    receive_any(s, &[cmd], size).map(|_| ())
+}
+
+/// Match received package to one of the expected packages and return it.
+/// Messages following the package header are limited to `size`.
+fn receive_any<R: Read>(s: &mut R, cmds: &[PkgType], size: u64)
+    -> Result<PkgType, Error>
+{
+    let status: PkgType = match decode_bounded(s, HEADER_SIZE) {
+        Ok(status) => status,
+        // bincode reports a connection closed by the server as invalid encoding
+        Err(DecodingError::InvalidEncoding(ref e)) if e.to_string().starts_with("Unexpected EOF") =>
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                "connection closed by the server").into()),
+        // no package type known to this library
+        Err(DecodingError::InvalidEncoding(_)) => return Err(Error::UnexpectedPkg),
+        Err(e) => return Err(e.into())
+    };
 
     if status == PkgType::Error {
-        let err : ClientErrMsg = try!(decode_from(s, SizeLimit::Infinite));
+        let err : ClientErrMsg = try!(decode_bounded(s, size));
+        if err.is_cancelled() {
+            return Err(Error::Cancelled)
+        }
         return Err(Error::Server(err))
     }
 
-    if status != cmd {
-        match status {
-            PkgType::Ok => {},
-            PkgType::Response => {
-                let _ : ResultSet = try!(decode_from(s, SizeLimit::Infinite));
-            },
-            PkgType::Greet => {
-                let _ : Greeting = try!(decode_from(s, SizeLimit::Infinite));
-            },
-            _ => {}
-        }
+    // The payload of an unexpected package is not read, the stream can not
+    // be used anymore
+    if !cmds.contains(&status) {
         return Err(Error::UnexpectedPkg)
     }
-    Ok(())
+    Ok(status)
+}
+
+/// Stub server on a local port: accepts one connection in a thread of its
+/// own, logs it in with connection id 1 and hands the stream to `serve`.
+/// Returns the port and the thread, which ends with the stream.
+#[cfg(test)]
+fn stub_server<T, F>(serve: F) -> (u16, thread::JoinHandle<T>)
+    where T: Send + 'static, F: FnOnce(TcpStream) -> T + Send + 'static
+{
+    stub_server_on(0, serve)
+}
+
+/// Like `stub_server`, but listens on the given port
+#[cfg(test)]
+fn stub_server_on<T, F>(port: u16, serve: F) -> (u16, thread::JoinHandle<T>)
+    where T: Send + 'static, F: FnOnce(TcpStream) -> T + Send + 'static
+{
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind(("127.0.0.1", port)).unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = thread::spawn(move || {
+        let mut stream = listener.accept().unwrap().0;
+        server::net::do_handshake(&mut stream, 1, 0).unwrap();
+        server::net::send_info_package(&mut stream, PkgType::AccGranted).unwrap();
+        serve(stream)
+    });
+    (port, server)
+}
+
+#[test]
+fn test_connect_timeout() {
+    use std::net::TcpListener;
+    use std::time::Instant;
+
+    // the connection is never accepted, so the greeting never comes
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let start = Instant::now();
+    let res = Connection::connect_timeout("127.0.0.1".into(), port,
+        "admin".into(), "pw".into(), Duration::from_millis(100));
+    match res {
+        Err(Error::Timeout) => {},
+        _ => panic!("expected a timeout")
+    }
+    assert!(start.elapsed() < Duration::from_secs(5));
+    drop(listener);
+}
+
+#[test]
+fn test_connect_hostname() {
+    // "localhost" may resolve to ::1 first, but the listener only accepts
+    // on 127.0.0.1, so this also checks that the next address is tried
+    let (port, server) = stub_server(|_| ());
+
+    let conn = Connection::connect("localhost".into(), port, "admin".into(), "pw".into());
+    assert_eq!(conn.unwrap().get_ip(), "localhost");
+    server.join().unwrap();
+}
+
+#[test]
+fn test_connect_unknown_host() {
+    let res = Connection::connect("no.such.host.invalid".into(), 4242,
+        "admin".into(), "pw".into());
+    match res {
+        Err(Error::UnknownHost) => {},
+        _ => panic!("expected an unknown host error")
+    }
+}
+
+#[test]
+fn test_max_message_size() {
+    use std::io::Cursor;
+
+    let mut vec = Vec::new();
+    let _ = encode_into(&PkgType::Response, &mut vec, SizeLimit::Bounded(1024));
+    let _ = encode_into(&ResultSet { data: vec![0; 64], columns: vec![] }, &mut vec,
+        SizeLimit::Infinite);
+
+    // the header fits, but the result set does not
+    let mut cursor = Cursor::new(vec);
+    let pkg = receive_any(&mut cursor, &[PkgType::Response], 16).unwrap();
+    match receive_result(&mut cursor, pkg, 16) {
+        Err(Error::Decode(DecodingError::SizeLimit)) => {},
+        _ => panic!("expected a size limit error")
+    }
+
+    let mut out = Vec::new();
+    let res = send_cmd(&mut out, Command::Query("select * from t".into()), 8);
+    match res {
+        Err(Error::Encode(EncodingError::SizeLimit)) => {},
+        _ => panic!("expected a size limit error")
+    }
+}
+
+#[test]
+fn test_enormous_length_prefix() {
+    use std::io::Cursor;
+
+    // an error package whose message claims to be 2^62 bytes long
+    let mut vec = Vec::new();
+    let _ = encode_into(&PkgType::Error, &mut vec, SizeLimit::Bounded(HEADER_SIZE));
+    vec.extend_from_slice(&[0, 1, 0x40, 0, 0, 0, 0, 0, 0, 0, b'x']);
+    let mut cursor = Cursor::new(vec);
+    match receive_any(&mut cursor, &[PkgType::Ok], DEFAULT_MAX_MESSAGE_SIZE) {
+        Err(Error::Decode(DecodingError::SizeLimit)) => {},
+        _ => panic!("expected a size limit error")
+    }
+
+    // a result set claiming 2^62 bytes of data, followed by endless zeros
+    let mut vec = Vec::new();
+    vec.extend_from_slice(&[0x40, 0, 0, 0, 0, 0, 0, 0]);
+    let mut stream = Cursor::new(vec).chain(io::repeat(0));
+    match receive_result(&mut stream, PkgType::Response, 4096) {
+        Err(Error::Decode(DecodingError::SizeLimit)) => {},
+        _ => panic!("expected a size limit error")
+    }
+}
+
+#[test]
+fn test_login_hash_differs_per_connection() {
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (tx, rx) = mpsc::channel();
+    let server = thread::spawn(move || {
+        for _ in 0..2 {
+            let mut stream = listener.accept().unwrap().0;
+            let login = match server::net::do_handshake(&mut stream, 1, 0).unwrap().0 {
+                server::net::Hello::Login(login) => login,
+                _ => panic!("expected a login")
+            };
+            server::net::send_info_package(&mut stream, PkgType::AccGranted).unwrap();
+            tx.send(login.password_hash).unwrap();
+        }
+    });
+
+    for _ in 0..2 {
+        Connection::connect("127.0.0.1".into(), port, "admin".into(), "pw".into()).unwrap();
+    }
+    let (first, second) = (rx.recv().unwrap(), rx.recv().unwrap());
+    assert!(first != second);
+    assert!(first != b"pw".to_vec());
+    server.join().unwrap();
+}
+
+#[test]
+fn test_reconnect() {
+    // both connections select the database, the new one answers one ping
+    fn serve(mut stream: TcpStream, ping: bool) {
+        assert_eq!(server::net::read_commands(&mut stream).unwrap(), Command::Use("db".into()));
+        server::net::send_info_package(&mut stream, PkgType::Ok).unwrap();
+        if ping {
+            assert_eq!(server::net::read_commands(&mut stream).unwrap(), Command::Ping);
+            server::net::send_info_package(&mut stream, PkgType::Ok).unwrap();
+        }
+    }
+
+    let (port, server) = stub_server(|stream| serve(stream, false));
+    let mut conn =
+        Connection::connect("127.0.0.1".into(), port, "admin".into(), "pw".into()).unwrap();
+    conn.use_db("db").unwrap();
+
+    // listener and stream are dropped, the connection is dead
+    server.join().unwrap();
+    assert!(conn.ping().is_err());
+
+    let (_, server) = stub_server_on(port, |stream| serve(stream, true));
+    conn.reconnect().unwrap();
+    conn.ping().unwrap();
+    server.join().unwrap();
+}
+
+#[test]
+fn test_retry_only_select() {
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+
+    // stub server: closes the first two connections after receiving a
+    // query, the third one gets an answer
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (tx, rx) = mpsc::channel();
+    let server = thread::spawn(move || {
+        for &answer in &[false, false, true] {
+            let mut stream = listener.accept().unwrap().0;
+            server::net::do_handshake(&mut stream, 1, 0).unwrap();
+            server::net::send_info_package(&mut stream, PkgType::AccGranted).unwrap();
+            tx.send(server::net::read_commands(&mut stream).unwrap()).unwrap();
+            if answer {
+                server::net::send_response_package(&mut stream,
+                    ResultSet { data: vec![], columns: vec![] }).unwrap();
+            }
+        }
+    });
+
+    // the insert may have been executed already, so it is not sent again
+    let mut conn =
+        Connection::connect("127.0.0.1".into(), port, "admin".into(), "pw".into()).unwrap();
+    assert!(conn.execute_with_retry("insert into t values (1)".into()).is_err());
+    conn.reconnect().unwrap();
+    match conn.execute_with_retry("select * from t".into()) {
+        Ok(QueryResult::Rows(_)) => {},
+        _ => panic!("expected rows")
+    }
+    server.join().unwrap();
+    let queries: Vec<_> = rx.iter().collect();
+    assert_eq!(queries, vec![Command::Query("insert into t values (1)".into()),
+        Command::Query("select * from t".into()), Command::Query("select * from t".into())]);
+}
+
+#[test]
+fn test_is_read_only() {
+    assert_eq!(Connection::is_read_only("select * from t where a = 1").unwrap(), true);
+    assert_eq!(Connection::is_read_only("describe t").unwrap(), true);
+    assert_eq!(Connection::is_read_only("insert into t values (1)").unwrap(), false);
+    assert_eq!(Connection::is_read_only("drop table t").unwrap(), false);
+    match Connection::is_read_only("selec * frm t") {
+        Err(Error::Parse(_)) => {},
+        other => panic!("expected a parse error, got {:?}", other.ok())
+    }
+}
+
+#[test]
+fn test_auto_reconnect() {
+    use test_server::{Answer, TestServer};
+
+    // the first two connections are dropped after a query, the third one
+    // gets the insert again, since it was not sent to a dropped connection
+    let server = TestServer::new().answer(Answer::Close)
+        .next_connection().answer(Answer::Rows(ResultSet { data: vec![], columns: vec![] }))
+        .answer(Answer::Close)
+        .next_connection().answer(Answer::Affected(1)).start();
+
+    let mut conn = ConnectionBuilder::new().port(server.port()).auto_reconnect(true)
+        .connect().unwrap();
+    match conn.execute("select * from t".into()) {
+        Ok(QueryResult::Rows(_)) => {},
+        other => panic!("expected rows, got {:?}", other.err())
+    }
+    // the insert may have been executed, so it is not replayed
+    assert!(conn.execute("insert into t values (1)".into()).is_err());
+    assert!(conn.is_poisoned());
+    match conn.execute("insert into t values (1)".into()) {
+        Ok(QueryResult::Affected(1)) => {},
+        other => panic!("expected one affected row, got {:?}", other.err())
+    }
+    let queries: Vec<_> = ["select * from t", "select * from t", "insert into t values (1)",
+        "insert into t values (1)"].iter().map(|q| Command::Query(q.to_string())).collect();
+    assert_eq!(server.join(), queries);
+}
+
+#[test]
+fn test_transaction_commands() {
+    use test_server::{Answer, TestServer};
+
+    // the server refuses the second commit and the rollback
+    let no_transaction = || Answer::Error(server::net::Error::NoTransaction.into());
+    let server = TestServer::new().answer(Answer::Ok).answer(Answer::Ok)
+        .answer(no_transaction()).answer(no_transaction()).start();
+
+    let mut conn =
+        Connection::connect("127.0.0.1".into(), server.port(), "admin".into(), "pw".into())
+        .unwrap();
+    conn.begin().unwrap();
+    conn.commit().unwrap();
+    match conn.commit() {
+        Err(Error::Server(ref e)) if e.msg == "no transaction open" => {},
+        _ => panic!("expected a server error")
+    }
+    assert!(conn.rollback().is_err());
+    assert_eq!(server.join(),
+        vec![Command::Begin, Command::Commit, Command::Commit, Command::Rollback]);
+}
+
+#[test]
+fn test_partial_send_poisons() {
+    use test_server::{Answer, TestServer};
+
+    // the header of the oversized query is sent before its encoding fails
+    let server = TestServer::new().next_connection().answer(Answer::Ok).start();
+    let mut conn = ConnectionBuilder::new().port(server.port()).connect().unwrap()
+        .with_max_message_size(16);
+    match conn.execute("select * from a_table_with_a_long_name".into()) {
+        Err(Error::Encode(EncodingError::SizeLimit)) => {},
+        other => panic!("expected a size limit error, got {:?}", other.err())
+    }
+    assert!(conn.is_poisoned());
+    match conn.ping() {
+        Err(Error::Poisoned) => {},
+        _ => panic!("expected a poisoned connection")
+    }
+
+    conn.reconnect().unwrap();
+    conn.ping().unwrap();
+    assert_eq!(server.join(), vec![Command::Ping]);
+}
+
+#[test]
+fn test_execute_canned_answers() {
+    use test_server::{Answer, TestServer};
+
+    let server = TestServer::new()
+        .answer(Answer::Rows(ResultSet { data: vec![], columns: vec![] }))
+        .answer(Answer::Affected(3)).answer(Answer::Close).start();
+    let mut conn = ConnectionBuilder::new().port(server.port()).connect().unwrap();
+    match conn.execute("select * from t".into()) {
+        Ok(QueryResult::Rows(_)) => {},
+        _ => panic!("expected rows")
+    }
+    match conn.execute("delete from t".into()) {
+        Ok(QueryResult::Affected(3)) => {},
+        _ => panic!("expected 3 affected rows")
+    }
+    assert!(conn.execute("select * from t".into()).is_err());
+    assert_eq!(server.join().len(), 3);
+}
+
+#[test]
+fn test_protocol_mismatch() {
+    use test_server::TestServer;
+
+    // stub servers greeting with a newer protocol version
+    let server = TestServer::new().protocol_version(PROTOCOL_VERSION + 1).start();
+    let builder = ConnectionBuilder::new().port(server.port()).user("admin").password("pw");
+    match builder.clone().connect() {
+        Err(Error::ProtocolMismatch { client, server })
+            if client == PROTOCOL_VERSION && server == PROTOCOL_VERSION + 1 => {},
+        _ => panic!("expected a protocol mismatch")
+    }
+    server.join();
+
+    let server = TestServer::new().protocol_version(PROTOCOL_VERSION + 1).start();
+    let conn = builder.port(server.port()).allow_version_mismatch().connect().unwrap();
+    assert_eq!(conn.get_version(), PROTOCOL_VERSION + 1);
+    drop(conn);
+    server.join();
+}
+
+#[test]
+fn test_prepared_insert() {
+    // stub server: prepares one statement and records its executions
+    let (port, server) = stub_server(|mut stream| {
+        let q = match server::net::read_commands(&mut stream).unwrap() {
+            Command::Prepare(q) => q,
+            _ => panic!("expected prepare")
+        };
+        let count = server::parse::count_params(&q).unwrap() as u64;
+        server::net::send_prepared_package(&mut stream,
+            PreparedInfo { id: 7, param_count: count }).unwrap();
+
+        let mut executed = Vec::new();
+        for _ in 0..2 {
+            match server::net::read_commands(&mut stream).unwrap() {
+                Command::ExecutePrepared(7, params) => executed.push(params),
+                _ => panic!("expected execute")
+            }
+            server::net::send_affected_rows_package(&mut stream, 1).unwrap();
+        }
+        executed
+    });
+
+    let mut conn =
+        Connection::connect("127.0.0.1".into(), port, "admin".into(), "pw".into()).unwrap();
+    let stmt = conn.prepare("insert into users values (?, ?)".into()).unwrap();
+    assert_eq!(stmt.get_param_count(), 2);
+
+    // wrong parameter count is rejected before anything is sent
+    match stmt.execute(&mut conn, &[Value::Int(1)]) {
+        Err(Error::ParamCount { expected: 2, given: 1 }) => {},
+        _ => panic!("expected a parameter count error")
+    }
+    for &(id, name) in &[(1, "bob"), (2, "alice")] {
+        match stmt.execute(&mut conn, &[Value::Int(id), Value::Str(name.into())]) {
+            Ok(QueryResult::Affected(1)) => {},
+            _ => panic!("expected one affected row")
+        }
+    }
+
+    assert_eq!(server.join().unwrap(),
+        vec![vec![Value::Int(1), Value::Str("bob".into())],
+            vec![Value::Int(2), Value::Str("alice".into())]]);
+}
+
+#[test]
+fn test_cancel_query() {
+    use std::net::TcpListener;
+
+    // stub server: the query of connection 5 runs until it is cancelled
+    // over a second connection, which sends the cancel key instead of a login
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = thread::spawn(move || {
+        let mut stream = listener.accept().unwrap().0;
+        server::net::do_handshake(&mut stream, 5, 77).unwrap();
+        server::net::send_info_package(&mut stream, PkgType::AccGranted).unwrap();
+        match server::net::read_commands(&mut stream).unwrap() {
+            Command::Query(_) => {},
+            _ => panic!("expected a query")
+        }
+
+        let mut side = listener.accept().unwrap().0;
+        match server::net::do_handshake(&mut side, 6, 0).unwrap().0 {
+            server::net::Hello::Cancel(5, 77) => {},
+            _ => panic!("expected a cancel request")
+        }
+        server::net::send_info_package(&mut side, PkgType::Ok).unwrap();
+
+        server::net::send_error_package(&mut stream,
+            server::net::Error::Cancelled.into()).unwrap();
+    });
+
+    let mut conn =
+        Connection::connect("127.0.0.1".into(), port, "admin".into(), "pw".into()).unwrap();
+    let token = conn.cancel_token();
+    let canceller = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        token.cancel().unwrap();
+    });
+    match conn.execute("select * from big".into()) {
+        Err(Error::Cancelled) => {},
+        _ => panic!("expected the query to be cancelled")
+    }
+    canceller.join().unwrap();
+    server.join().unwrap();
+}
+
+#[test]
+fn test_execute_stream() {
+    const ROWS: usize = 100000;
+
+    // stub server: answers the query with ROWS rows of one Int column
+    let (port, server) = stub_server(|mut stream| {
+        match server::net::read_commands(&mut stream).unwrap() {
+            Command::StreamQuery(_) => {},
+            _ => panic!("expected a streamed query")
+        }
+        let mut data = Vec::new();
+        for i in 0..ROWS {
+            data.extend(&[(i >> 24) as u8, (i >> 16) as u8, (i >> 8) as u8, i as u8]);
+        }
+        let rows = ResultSet {
+            data: data,
+            columns: vec![Column::new("id", SqlType::Int, false, "", true)]
+        };
+        server::net::send_streamed_response(&mut stream, rows, &AtomicBool::new(false))
+            .unwrap();
+    });
+
+    let mut conn =
+        Connection::connect("127.0.0.1".into(), port, "admin".into(), "pw".into()).unwrap();
+    let mut stream = conn.execute_stream("select * from big".into()).unwrap();
+    assert_eq!(stream.columns()[0].name, "id");
+
+    // never more than one batch is held in memory
+    let batch_rows = server::net::STREAM_BATCH_SIZE / 4;
+    let mut count = 0;
+    while let Some(row) = stream.next() {
+        assert!(stream.buffered() < batch_rows);
+        assert_eq!(row.unwrap().get_i64(0), Some(count as i64));
+        count += 1;
+    }
+    assert_eq!(count, ROWS);
+    server.join().unwrap();
+}
+
+#[test]
+fn test_execute_many() {
+    use std::net::TcpListener;
+    const ROWS: usize = 100000;
+
+    // stub server: streams ROWS rows until the query of connection 5 is
+    // cancelled over a second connection
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = thread::spawn(move || {
+        let mut stream = listener.accept().unwrap().0;
+        server::net::do_handshake(&mut stream, 5, 77).unwrap();
+        server::net::send_info_package(&mut stream, PkgType::AccGranted).unwrap();
+        match server::net::read_commands(&mut stream).unwrap() {
+            Command::StreamQuery(_) => {},
+            _ => panic!("expected a streamed query")
+        }
+
+        let cancel = Arc::new(AtomicBool::new(false));
+        let side = {
+            let cancel = cancel.clone();
+            thread::spawn(move || {
+                let mut side = listener.accept().unwrap().0;
+                match server::net::do_handshake(&mut side, 6, 0).unwrap().0 {
+                    server::net::Hello::Cancel(5, 77) => {},
+                    _ => panic!("expected a cancel request")
+                }
+                cancel.store(true, Ordering::SeqCst);
+                server::net::send_info_package(&mut side, PkgType::Ok).unwrap();
+            })
+        };
+        let mut data = Vec::new();
+        for i in 0..ROWS {
+            data.extend(&[(i >> 24) as u8, (i >> 16) as u8, (i >> 8) as u8, i as u8]);
+        }
+        let rows = ResultSet {
+            data: data,
+            columns: vec![Column::new("id", SqlType::Int, false, "", true)]
+        };
+        server::net::send_streamed_response(&mut stream, rows, &cancel).unwrap();
+        side.join().unwrap();
+
+        // the connection can be used after the cancelled stream
+        match server::net::read_commands(&mut stream).unwrap() {
+            Command::Ping => {},
+            _ => panic!("expected a ping")
+        }
+        server::net::send_info_package(&mut stream, PkgType::Ok).unwrap();
+    });
+
+    let mut conn =
+        Connection::connect("127.0.0.1".into(), port, "admin".into(), "pw".into()).unwrap();
+    let mut ids = Vec::new();
+    let count = conn.execute_many("select * from big".into(), |row| {
+        ids.push(row.get_i64(0).unwrap());
+        if ids.len() < 10 { ControlFlow::Continue } else { ControlFlow::Break }
+    }).unwrap();
+    assert_eq!(count, 10);
+    assert_eq!(ids, (0..10).collect::<Vec<_>>());
+    conn.ping().unwrap();
+    server.join().unwrap();
+}
+
+#[test]
+fn test_heartbeat() {
+    let interval = Duration::from_millis(200);
+
+    // stub server: answers two pings and then goes away
+    let (port, server) = stub_server(|mut stream| {
+        for _ in 0..2 {
+            assert_eq!(server::net::read_commands(&mut stream).unwrap(), Command::Ping);
+            server::net::send_info_package(&mut stream, PkgType::Ok).unwrap();
+        }
+    });
+
+    let conn = ConnectionBuilder::new().port(port).user("admin").password("pw")
+        .heartbeat(interval).connect().unwrap();
+    assert!(conn.is_alive());
+    server.join().unwrap();
+    assert!(conn.is_alive());
+
+    // the next ping fails
+    thread::sleep(interval * 2);
+    assert!(!conn.is_alive());
+}
+
+#[test]
+fn test_heartbeat_stops_on_drop() {
+    let (port, server) = stub_server(|mut stream| {
+        // the heartbeat thread does not keep the connection open
+        stream.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+        assert_eq!(stream.read(&mut [0; 16]).unwrap(), 0);
+    });
+    let conn = ConnectionBuilder::new().port(port).user("admin").password("pw")
+        .heartbeat(Duration::from_secs(10)).connect().unwrap();
+    drop(conn);
+    server.join().unwrap();
+}
+
+#[test]
+fn test_execute_batch() {
+    // stub server: every query containing "fail" fails, the others change
+    // one row
+    let (port, server) = stub_server(|mut stream| {
+        for _ in 0..2 {
+            let (queries, mode) = match server::net::read_commands(&mut stream).unwrap() {
+                Command::Batch(queries, mode) => (queries, mode),
+                _ => panic!("expected a batch")
+            };
+            for q in queries {
+                if q.contains("fail") {
+                    server::net::send_error_package(&mut stream,
+                        server::net::Error::UnknownTable.into()).unwrap();
+                    if mode == BatchMode::StopOnError {
+                        break
+                    }
+                } else {
+                    server::net::send_affected_rows_package(&mut stream, 1).unwrap();
+                }
+            }
+            server::net::send_info_package(&mut stream, PkgType::BatchEnd).unwrap();
+        }
+    });
+
+    let mut conn =
+        Connection::connect("127.0.0.1".into(), port, "admin".into(), "pw".into()).unwrap();
+    let queries: Vec<String> = vec!["insert 1".into(), "fail".into(), "insert 2".into()];
+    let kinds = |results: Vec<Result<QueryResult, Error>>| -> Vec<&'static str> {
+        results.into_iter().map(|r| match r {
+            Ok(QueryResult::Affected(1)) => "ok",
+            Err(Error::Server(_)) => "err",
+            _ => panic!("unexpected result")
+        }).collect()
+    };
+
+    let results = conn.execute_batch(&queries, BatchMode::StopOnError).unwrap();
+    assert_eq!(kinds(results), vec!["ok", "err"]);
+    let results = conn.execute_batch(&queries, BatchMode::CollectAll).unwrap();
+    assert_eq!(kinds(results), vec!["ok", "err", "ok"]);
+    server.join().unwrap();
+}
+
+#[test]
+fn test_execute_file() {
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    // stub server: answers the batch of both statements
+    let (port, server) = stub_server(|mut stream| {
+        let queries = match server::net::read_commands(&mut stream).unwrap() {
+            Command::Batch(queries, BatchMode::StopOnError) => queries,
+            _ => panic!("expected a batch")
+        };
+        server::net::send_affected_rows_package(&mut stream, 0).unwrap();
+        server::net::send_affected_rows_package(&mut stream, 1).unwrap();
+        server::net::send_info_package(&mut stream, PkgType::BatchEnd).unwrap();
+        queries
+    });
+
+    let path = env::temp_dir().join(format!("uosql-execute-file-{}.sql", process::id()));
+    File::create(&path).unwrap().write_all(b"-- create and fill t\n\
+        create table t (a int primary key);\ninsert into t values (1);\n").unwrap();
+    let mut conn = ConnectionBuilder::new().port(port).connect().unwrap();
+    let results = conn.execute_file(&path).unwrap();
+    assert_eq!(results.len(), 2);
+    match (&results[0], &results[1]) {
+        (&QueryResult::Affected(0), &QueryResult::Affected(1)) => {},
+        _ => panic!("expected the affected rows")
+    }
+    assert_eq!(server.join().unwrap(), vec!["create table t (a int primary key)".to_string(),
+        "insert into t values (1)".to_string()]);
+
+    // nothing is sent for a script that can not be parsed or read
+    File::create(&path).unwrap().write_all(b"create table t (a int); selec").unwrap();
+    match conn.execute_file(&path) {
+        Err(Error::Parse(_)) => {},
+        _ => panic!("expected a parse error")
+    }
+    fs::remove_file(&path).unwrap();
+    match conn.execute_file(&path) {
+        Err(Error::Io(ref e)) if e.kind() == io::ErrorKind::NotFound => {},
+        _ => panic!("expected an io error")
+    }
+}
+
+#[test]
+fn test_error_source_chain() {
+    use std::error::Error as StdError;
+
+    fn chain(err: &dyn StdError) -> Vec<String> {
+        let mut chain = vec![err.to_string()];
+        let mut source = err.source();
+        while let Some(e) = source {
+            chain.push(e.to_string());
+            source = e.source();
+        }
+        chain
+    }
+
+    let err: Error = io::Error::new(io::ErrorKind::ConnectionReset, "reset by peer").into();
+    assert_eq!(chain(&err), vec!["IO error occured: reset by peer", "reset by peer"]);
+
+    let err: Error = Ipv4Addr::from_str("1.2.3").unwrap_err().into();
+    assert_eq!(chain(&err).len(), 2);
+    assert!(chain(&err)[0].starts_with("wrong IPv4 address format: "));
+
+    let err = Error::Decode(DecodingError::SizeLimit);
+    assert!(err.source().is_some());
+
+    // errors without a cause end the chain
+    assert_eq!(chain(&Error::Auth), vec!["could not authenticate user"]);
+}
+
+// starts a server which accepts logins as admin/pw
+#[cfg(test)]
+fn start_server() -> (Arc<server::Server>, u16, thread::JoinHandle<()>) {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    let config = server::Config {
+        max_connections: 10,
+        worker_threads: 2,
+        shutdown_timeout: 1,
+        log_queries: false,
+        users: vec![auth::Credentials {
+            username: "admin".into(),
+            password_hash: auth::stored_hash("pw")
+        }],
+        query_cache_size: 0,
+        parse_cache_size: 0,
+        .. server::Config::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 0, "data".into())
+    };
+    let server = Arc::new(server::Server::bind(config).unwrap());
+    let port = server.local_addr().unwrap().port();
+    let runner = {
+        let server = server.clone();
+        thread::spawn(move || server.run())
+    };
+    (server, port, runner)
+}
+
+#[test]
+fn test_server_info() {
+    let (server, port, runner) = start_server();
+    let mut conn =
+        Connection::connect("127.0.0.1".into(), port, "admin".into(), "pw".into()).unwrap();
+    let info = conn.server_info().unwrap();
+    assert_eq!(info.protocol_version, server::net::PROTOCOL_VERSION);
+    assert_eq!(info.protocol_version, conn.get_version());
+    assert!(!info.version.is_empty());
+    assert!(info.started > 0);
+    assert_eq!(info.connections, 1);
+
+    drop(conn);
+    server.shutdown();
+    runner.join().unwrap();
+}
+
+#[test]
+fn test_validate() {
+    let (server, port, runner) = start_server();
+    let mut conn =
+        Connection::connect("127.0.0.1".into(), port, "admin".into(), "pw".into()).unwrap();
+
+    // no database is selected and the table doesn't exist, the query is not executed
+    assert!(conn.validate("select name from foo where id = 1").is_ok());
+    match conn.validate("select name form foo") {
+        Err(Error::Server(ref e)) =>
+            assert_eq!(e.msg, "parsing error: expected a keyword, found `form` at position 12"),
+        other => panic!("expected a server error, found {:?}", other),
+    }
+    // the connection works after the error
+    assert!(conn.validate("drop table foo").is_ok());
+
+    drop(conn);
+    server.shutdown();
+    runner.join().unwrap();
+}
+
+#[test]
+fn test_explain() {
+    let (server, port, runner) = start_server();
+    let mut conn =
+        Connection::connect("127.0.0.1".into(), port, "admin".into(), "pw".into()).unwrap();
+
+    let text = conn.explain("create table foo (id int primary key)").unwrap();
+    assert_eq!(text, "CREATE TABLE foo (id INT PRIMARY KEY)");
+    let text = conn.explain("select a, count(*) from t where b * 2 > 10 group by a").unwrap();
+    assert_eq!(text, "SELECT a, COUNT(*) FROM t WHERE b * 2 > 10 GROUP BY a");
+    match conn.explain("create foo") {
+        Err(Error::Server(_)) => {},
+        other => panic!("expected a server error, found {:?}", other),
+    }
+    // nothing was executed, no database is selected
+    assert!(conn.list_tables().is_err());
+
+    drop(conn);
+    server.shutdown();
+    runner.join().unwrap();
+}
+
+#[test]
+fn test_stats() {
+    let (server, port, runner) = start_server();
+    let mut conn =
+        Connection::connect("127.0.0.1".into(), port, "admin".into(), "pw".into()).unwrap();
+    assert_eq!(conn.stats(), ConnectionStats::default());
+
+    // failed queries are counted as well, no database is selected
+    for _ in 0..3 {
+        assert!(conn.execute("select * from foo".into()).is_err());
+    }
+    let results = conn.execute_batch(&["drop table foo".into(), "drop table bar".into()],
+        BatchMode::CollectAll).unwrap();
+    assert_eq!(results.len(), 2);
+    conn.ping().unwrap();
+
+    let stats = conn.stats();
+    assert_eq!(stats.queries, 5);
+    assert!(stats.bytes_sent > 0);
+    assert!(stats.bytes_received > 0);
+    assert!(stats.query_time > Duration::from_secs(0));
+
+    conn.reset_stats();
+    assert_eq!(conn.stats(), ConnectionStats::default());
+
+    drop(conn);
+    server.shutdown();
+    runner.join().unwrap();
+}
+
+#[test]
+fn test_poisoned_connection() {
+    // stub server: answers the query with the wrong package
+    let (port, server) = stub_server(|mut stream| {
+        server::net::read_commands(&mut stream).unwrap();
+        server::net::send_prepared_package(&mut stream,
+            PreparedInfo { id: 1, param_count: 0 }).unwrap();
+        // no further command is sent
+        let mut rest = Vec::new();
+        stream.read_to_end(&mut rest).unwrap();
+        assert!(rest.is_empty());
+    });
+
+    let mut conn =
+        Connection::connect("127.0.0.1".into(), port, "admin".into(), "pw".into()).unwrap();
+    assert!(!conn.is_poisoned());
+    match conn.execute("select * from t".into()) {
+        Err(Error::UnexpectedPkg) => {},
+        _ => panic!("expected an unexpected package")
+    }
+    assert!(conn.is_poisoned());
+    for _ in 0..2 {
+        match conn.execute("select * from t".into()) {
+            Err(Error::Poisoned) => {},
+            _ => panic!("expected a poisoned connection")
+        }
+    }
+    assert!(conn.ping().is_err());
+    drop(conn);
+    server.join().unwrap();
+}
+
+#[test]
+fn test_payload_timeout_poisons() {
+    // stub server: sends the header and half of the answer, then stalls
+    let (port, server) = stub_server(|mut stream| {
+        server::net::read_commands(&mut stream).unwrap();
+        let mut answer = Vec::new();
+        encode_into(&PkgType::Response, &mut answer, SizeLimit::Bounded(1024)).unwrap();
+        encode_into(&ResultSet { data: vec![0; 64], columns: vec![] }, &mut answer,
+            SizeLimit::Infinite).unwrap();
+        stream.write_all(&answer[..answer.len() / 2]).unwrap();
+        // no further command is sent
+        let mut rest = Vec::new();
+        stream.read_to_end(&mut rest).unwrap();
+        assert!(rest.is_empty());
+    });
+
+    let mut conn = ConnectionBuilder::new().port(port).user("admin").password("pw")
+        .timeout(Duration::from_millis(200)).connect().unwrap();
+    match conn.execute("select * from t".into()) {
+        Err(Error::Timeout) => {},
+        _ => panic!("expected a timeout")
+    }
+    assert!(conn.is_poisoned());
+    match conn.execute("select * from t".into()) {
+        Err(Error::Poisoned) => {},
+        _ => panic!("expected a poisoned connection")
+    }
+    drop(conn);
+    server.join().unwrap();
+}
+
+#[test]
+fn test_query_timeout() {
+    use std::time::Instant;
+
+    // stub server: answers every query after 300 milliseconds
+    let slow = |mut stream: TcpStream| {
+        while let Ok(_) = server::net::read_commands(&mut stream) {
+            thread::sleep(Duration::from_millis(300));
+            if server::net::send_affected_rows_package(&mut stream, 1).is_err() {
+                break
+            }
+        }
+    };
+
+    let (port, server) = stub_server(slow);
+    let mut conn = ConnectionBuilder::new().port(port).user("admin").password("pw")
+        .connect().unwrap();
+    conn.set_query_timeout(Some(Duration::from_millis(100)));
+    let start = Instant::now();
+    match conn.execute("select * from t".into()) {
+        Err(Error::Timeout) => {},
+        _ => panic!("expected a timeout")
+    }
+    assert!(start.elapsed() < Duration::from_millis(300));
+    // the late answer must not be read as result of the next query
+    assert!(conn.is_poisoned());
+    match conn.execute("select * from t".into()) {
+        Err(Error::Poisoned) => {},
+        _ => panic!("expected a poisoned connection")
+    }
+    drop(conn);
+    server.join().unwrap();
+
+    // a longer timeout for a single query, the other ones keep the short one
+    let (port, server) = stub_server(slow);
+    let mut conn = ConnectionBuilder::new().port(port).user("admin").password("pw")
+        .connect().unwrap();
+    conn.set_query_timeout(Some(Duration::from_millis(100)));
+    match conn.execute_with_timeout("delete from t".into(), Duration::from_secs(5)) {
+        Ok(QueryResult::Affected(1)) => {},
+        _ => panic!("expected one affected row")
+    }
+    match conn.execute("select * from t".into()) {
+        Err(Error::Timeout) => {},
+        _ => panic!("expected a timeout")
+    }
+    drop(conn);
+    server.join().unwrap();
+}
+
+#[test]
+fn test_error_code() {
+    let err = Error::Server(server::net::Error::UnknownTable.into());
+    assert_eq!(err.error_code(), Some(ErrorCode::UnknownTable));
+    assert_eq!(Error::Auth.error_code(), Some(ErrorCode::Auth));
+    assert_eq!(Error::Timeout.error_code(), None);
 }
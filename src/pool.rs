@@ -0,0 +1,233 @@
+//! A pool of logged in connections to the same server.
+//!
+//! Connecting costs a handshake and a login, so applications sending many
+//! short queries can borrow already established connections instead:
+//!
+//! ```no_run
+//! # use uosql::{ConnectionBuilder, ConnectionPool, Error};
+//! # fn run() -> Result<(), Error> {
+//! let options = ConnectionBuilder::new().user("admin").password("pw");
+//! let pool = try!(ConnectionPool::new(options, 4));
+//! let mut conn = try!(pool.get());
+//! try!(conn.execute("select * from users".into()));
+//! // dropping `conn` gives it back to the pool
+//! # Ok(())
+//! # }
+//! ```
+//!
+use std::ops::{Deref, DerefMut};
+use std::sync::{Condvar, Mutex};
+
+use super::{Connection, ConnectionBuilder, Error};
+
+/// Holds up to `size` connections, all opened with the same options. The
+/// pool can be shared between threads, e.g. in an `Arc`.
+pub struct ConnectionPool {
+    options: ConnectionBuilder,
+    size: usize,
+    state: Mutex<State>,
+    returned: Condvar,
+}
+
+struct State {
+    /// Connections not handed out at the moment
+    idle: Vec<Connection>,
+    /// Connections in total, including the ones handed out
+    open: usize,
+}
+
+impl ConnectionPool {
+    /// Opens `size` connections with `options`. Fails if any of them can't
+    /// be established, e.g. because the login is wrong.
+    ///
+    /// # Panics
+    /// If `size` is 0.
+    pub fn new(options: ConnectionBuilder, size: usize) -> Result<ConnectionPool, Error> {
+        assert!(size > 0, "a pool needs at least one connection");
+
+        let mut idle = Vec::with_capacity(size);
+        for _ in 0..size {
+            idle.push(try!(options.clone().connect()));
+        }
+        Ok(ConnectionPool {
+            options: options,
+            size: size,
+            state: Mutex::new(State { idle: idle, open: size }),
+            returned: Condvar::new(),
+        })
+    }
+
+    /// Borrows a connection, blocks until one is available. The connection
+    /// is pinged first and replaced by a new one if the ping fails. Returns
+    /// an error only if the replacement can't be established either.
+    pub fn get(&self) -> Result<PooledConnection, Error> {
+        let idle;
+        {
+            let mut state = self.state.lock().unwrap();
+            loop {
+                if let Some(conn) = state.idle.pop() {
+                    idle = Some(conn);
+                    break;
+                }
+                // a connection that failed to be replaced left a free slot
+                if state.open < self.size {
+                    state.open += 1;
+                    idle = None;
+                    break;
+                }
+                state = self.returned.wait(state).unwrap();
+            }
+        }
+
+        // Network IO happens without holding the lock
+        let res = match idle {
+            Some(mut conn) => match conn.ping() {
+                Ok(_) => Ok(conn),
+                // the server closed it or it timed out while idle
+                Err(_) => conn.reconnect().map(|_| conn),
+            },
+            None => self.options.clone().connect(),
+        };
+
+        match res {
+            Ok(conn) => Ok(PooledConnection { pool: self, conn: Some(conn) }),
+            Err(e) => {
+                self.state.lock().unwrap().open -= 1;
+                self.returned.notify_one();
+                Err(e)
+            }
+        }
+    }
+
+    /// The maximum number of connections.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// The number of connections not handed out at the moment.
+    pub fn idle(&self) -> usize {
+        self.state.lock().unwrap().idle.len()
+    }
+}
+
+/// A connection borrowed from a `ConnectionPool`. Derefs to `Connection`
+/// and goes back to the pool when dropped.
+pub struct PooledConnection<'a> {
+    pool: &'a ConnectionPool,
+    conn: Option<Connection>,
+}
+
+impl<'a> Deref for PooledConnection<'a> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.conn.as_ref().unwrap()
+    }
+}
+
+impl<'a> DerefMut for PooledConnection<'a> {
+    fn deref_mut(&mut self) -> &mut Connection {
+        self.conn.as_mut().unwrap()
+    }
+}
+
+impl<'a> Drop for PooledConnection<'a> {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            self.pool.state.lock().unwrap().idle.push(conn);
+            self.pool.returned.notify_one();
+        }
+    }
+}
+
+/// Stub server answering pings and queries on every accepted connection.
+/// Returns the port and the number of accepted connections.
+#[cfg(test)]
+fn stub_server() -> (u16, ::std::sync::Arc<::std::sync::atomic::AtomicUsize>) {
+    use std::net::TcpListener;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use server::net;
+    use types::{Command, PkgType};
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let accepted = Arc::new(AtomicUsize::new(0));
+    let counter = accepted.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let id = counter.fetch_add(1, Ordering::SeqCst) as u64;
+            thread::spawn(move || {
+                net::do_handshake(&mut stream, id, 0).unwrap();
+                net::send_info_package(&mut stream, PkgType::AccGranted).unwrap();
+                loop {
+                    match net::read_commands(&mut stream) {
+                        Ok(Command::Ping) =>
+                            net::send_info_package(&mut stream, PkgType::Ok).unwrap(),
+                        Ok(Command::Query(_)) =>
+                            net::send_affected_rows_package(&mut stream, 1).unwrap(),
+                        _ => return
+                    }
+                }
+            });
+        }
+    });
+    (port, accepted)
+}
+
+#[test]
+fn test_concurrent_queries() {
+    use std::sync::Arc;
+    use std::sync::atomic::Ordering;
+    use std::thread;
+    use super::QueryResult;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<ConnectionPool>();
+
+    let (port, accepted) = stub_server();
+    let options = ConnectionBuilder::new().port(port).user("admin").password("pw");
+    let pool = Arc::new(ConnectionPool::new(options, 2).unwrap());
+
+    let threads: Vec<_> = (0..8).map(|_| {
+        let pool = pool.clone();
+        thread::spawn(move || {
+            for _ in 0..10 {
+                let mut conn = pool.get().unwrap();
+                match conn.execute("insert into t values (1)".into()).unwrap() {
+                    QueryResult::Affected(1) => {},
+                    _ => panic!("expected one affected row")
+                }
+            }
+        })
+    }).collect();
+    for t in threads {
+        t.join().unwrap();
+    }
+
+    // all queries went over the two connections of the pool
+    assert_eq!(accepted.load(Ordering::SeqCst), 2);
+    assert_eq!(pool.idle(), 2);
+}
+
+#[test]
+fn test_replace_dead_connection() {
+    use types::{Command, PkgType};
+
+    // the first connection is closed right after the login, the second one
+    // answers the ping of the pool
+    let (port, server) = super::stub_server(|_| ());
+    let options = ConnectionBuilder::new().port(port).user("admin").password("pw");
+    let pool = ConnectionPool::new(options, 1).unwrap();
+    server.join().unwrap();
+
+    let (_, server) = super::stub_server_on(port, |mut stream| {
+        assert_eq!(server::net::read_commands(&mut stream).unwrap(), Command::Ping);
+        server::net::send_info_package(&mut stream, PkgType::Ok).unwrap();
+    });
+    let mut conn = pool.get().unwrap();
+    conn.ping().unwrap();
+    server.join().unwrap();
+}
@@ -0,0 +1,120 @@
+//! A stub server for the tests of the client: it accepts connections on an
+//! ephemeral port, logs them in and answers each command with the next
+//! canned package, without a database behind it.
+//!
+//! ```ignore
+//! let server = TestServer::new().answer(Answer::Ok).start();
+//! let mut conn = try!(ConnectionBuilder::new().port(server.port()).connect());
+//! try!(conn.ping());
+//! assert_eq!(server.join(), vec![Command::Ping]);
+//! ```
+//!
+use std::net::TcpListener;
+use std::thread;
+
+use bincode::SizeLimit;
+use bincode::rustc_serialize::encode_into;
+use server::net;
+use server::storage::ResultSet;
+
+use super::types::{ClientErrMsg, Command, Greeting, PkgType};
+
+/// A package sent as answer to a command
+pub enum Answer {
+    Ok,
+    Rows(ResultSet),
+    Affected(u64),
+    Error(ClientErrMsg),
+    /// Close the connection without an answer
+    Close,
+}
+
+/// Options of the stub server, `start` binds and runs it.
+pub struct TestServer {
+    protocol_version: u8,
+    /// The answers of each connection
+    connections: Vec<Vec<Answer>>,
+}
+
+impl TestServer {
+    /// A server speaking the protocol of the client, with no answers.
+    pub fn new() -> TestServer {
+        TestServer { protocol_version: net::PROTOCOL_VERSION, connections: vec![vec![]] }
+    }
+
+    /// Greet the client with another protocol version.
+    pub fn protocol_version(mut self, version: u8) -> TestServer {
+        self.protocol_version = version;
+        self
+    }
+
+    /// Answer the next command with `answer`.
+    pub fn answer(mut self, answer: Answer) -> TestServer {
+        self.connections.last_mut().unwrap().push(answer);
+        self
+    }
+
+    /// Accept another connection after the current one was closed, the
+    /// following answers are sent over it.
+    pub fn next_connection(mut self) -> TestServer {
+        self.connections.push(vec![]);
+        self
+    }
+
+    /// Bind to an ephemeral port and serve the connections one after another
+    /// in a thread of its own. A connection is closed after its last answer.
+    pub fn start(self) -> RunningServer {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let thread = thread::spawn(move || {
+            let mut received = Vec::new();
+            for answers in self.connections {
+                let mut stream = listener.accept().unwrap().0;
+                let greet = Greeting::make_greeting(self.protocol_version, "stub".into(), 1, 0);
+                encode_into(&PkgType::Greet, &mut stream, SizeLimit::Bounded(1024)).unwrap();
+                encode_into(&greet, &mut stream, SizeLimit::Bounded(1024)).unwrap();
+                // a client rejecting the greeting does not log in
+                if net::read_login(&mut stream).is_err() {
+                    continue
+                }
+                net::send_info_package(&mut stream, PkgType::AccGranted).unwrap();
+
+                for answer in answers {
+                    match net::read_commands(&mut stream) {
+                        Ok(cmd) => received.push(cmd),
+                        Err(_) => break,
+                    }
+                    match answer {
+                        Answer::Ok => net::send_info_package(&mut stream, PkgType::Ok),
+                        Answer::Rows(rows) => net::send_response_package(&mut stream, rows),
+                        Answer::Affected(count) =>
+                            net::send_affected_rows_package(&mut stream, count),
+                        Answer::Error(err) => net::send_error_package(&mut stream, err),
+                        Answer::Close => break,
+                    }.unwrap();
+                }
+            }
+            received
+        });
+        RunningServer { port: port, thread: thread }
+    }
+}
+
+/// A started `TestServer`
+pub struct RunningServer {
+    port: u16,
+    thread: thread::JoinHandle<Vec<Command>>,
+}
+
+impl RunningServer {
+    /// The port the server listens on
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Wait for the connections to end, returns the commands received over
+    /// all of them.
+    pub fn join(self) -> Vec<Command> {
+        self.thread.join().unwrap()
+    }
+}
@@ -11,7 +11,7 @@ extern crate cookie;
 extern crate url;
 extern crate server;
 
-use uosql::Connection;
+use uosql::{Connection, QueryResult};
 use std::io::Read;
 use uosql::Error;
 use std::collections::HashMap;
@@ -181,6 +181,27 @@ fn main() {
                             Error::Server(_) => {
                                 "Network Error."
                             },
+                            Error::Timeout => {
+                                "Server did not respond in time."
+                            },
+                            Error::UnknownHost => {
+                                "Could not find specified server."
+                            },
+                            Error::ProtocolMismatch { .. } => {
+                                "Server uses an incompatible protocol version."
+                            },
+                            Error::ParamCount { .. } => {
+                                "Wrong number of parameters."
+                            },
+                            Error::Cancelled => {
+                                "Query was cancelled."
+                            },
+                            Error::Poisoned => {
+                                "Connection out of sync with the server. Reconnect."
+                            },
+                            Error::Parse(_) => {
+                                "Invalid query."
+                            },
                         };
                         let mut data = HashMap::new();
                         data.insert("err", errstr);
@@ -259,7 +280,10 @@ fn main() {
                 }
             };
 
-            let res_output = display_html(&mut result);
+            let res_output = match result {
+                QueryResult::Rows(ref mut rows) => display_html(rows),
+                QueryResult::Affected(n) => format!("{} row(s) affected", n),
+            };
             data.insert("result", res_output);
         }
 
@@ -324,6 +348,11 @@ fn display_meta_html (table: &mut DataSet) -> String {
                 SqlType::Int => "int".to_string(),
                 SqlType::Bool => "bool".to_string(),
                 SqlType::Char(p) => format!("Char({})", p),
+                SqlType::VarChar(p) => format!("VarChar({})", p),
+                SqlType::Float => "float".to_string(),
+                SqlType::Double => "double".to_string(),
+                SqlType::Date => "date".to_string(),
+                SqlType::Timestamp => "timestamp".to_string(),
             },
             None => "none".to_string(),
         };
@@ -399,10 +428,20 @@ fn display_data_html (table: &mut DataSet) -> String {
                                     &format!("<td>{}</td>", val).to_string()),
                                 None => result.push_str("<td>none</td>"),
                             },
-                        SqlType::Char(_) =>
+                        SqlType::Char(_) | SqlType::VarChar(_) =>
                             result.push_str(&format!("<td>{}</td>",
                                 table.next_char_by_idx(i)
-                                .unwrap_or("none".to_string())))
+                                .unwrap_or("none".to_string()))),
+                        SqlType::Float | SqlType::Double =>
+                            match table.next_float_by_idx(i) {
+                                Some(val) => result.push_str(
+                                    &format!("<td>{}</td>", val).to_string()),
+                                None => result.push_str("<td>none</td>"),
+                            },
+                        SqlType::Date | SqlType::Timestamp =>
+                            result.push_str(&format!("<td>{}</td>",
+                                table.next_datetime_by_idx(i)
+                                .unwrap_or("none".to_string()))),
                     }
                 },
                 None => continue